@@ -0,0 +1,9 @@
+#![allow(clippy::needless_return)]
+
+extern crate image;
+extern crate num_traits;
+extern crate generic_array;
+extern crate typenum;
+extern crate flate2;
+
+pub mod pbrt;
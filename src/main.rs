@@ -1,83 +1,134 @@
-mod pbrt;
-
-extern crate image;
-extern crate num_traits;
-extern crate generic_array;
-extern crate typenum;
-
-use std::f64;
-use std::fs::File;
-
-use pbrt::geometry::vector::Vector3;
+#![allow(clippy::needless_return)]
+
+extern crate hello_world;
+
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+use hello_world::pbrt;
+use pbrt::accelerators::bvh::BVHAccel;
+use pbrt::camera;
+use pbrt::camera::Camera;
+use pbrt::camera::perspective::PerspectiveCamera;
+use pbrt::film::Film;
+use pbrt::filter::GaussianFilter;
+use pbrt::geometry::Bounds2f;
+use pbrt::geometry::Bounds2i;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::geometry::Vector2f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::shape::Shape;
 use pbrt::geometry::sphere::Sphere;
-
-
-pub struct CameraSettings {
-    pub width: i64,
-    pub height: i64,
-    pub inverse_width: f64,
-    pub inverse_height: f64,
-    pub fov: f64,
-    pub aspect_ratio: f64,
-    pub angle: f64,
-}
-
-impl CameraSettings {
-    pub fn new(width: i64, height: i64) -> CameraSettings {
-        let fov = 30.0;
-        return CameraSettings {
-            width,
-            height,
-            inverse_width: 1.0 / (width as f64),
-            inverse_height: 1.0 / (height as f64),
-            fov,
-            aspect_ratio: (width as f64) / (height as f64),
-            angle: (f64::consts::PI * 0.5 * fov / 180.0).tan(),
-        };
-    }
+use pbrt::geometry::transform::Transform;
+use pbrt::geometry::triangle;
+use pbrt::geometry::triangle::TriangleMesh;
+use pbrt::io::exr;
+use pbrt::io::exr::Compression;
+use pbrt::io::obj;
+use pbrt::io::ply;
+use pbrt::lowdiscrepancy::RandomizeStrategy;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::adaptive::AdaptiveSampler;
+use pbrt::sampler::bluenoise::BlueNoiseSampler;
+use pbrt::sampler::halton::HaltonSampler;
+use pbrt::sampler::pmj02::PMJ02Sampler;
+use pbrt::sampler::random::RandomSampler;
+use pbrt::sampler::sobol::SobolSampler;
+use pbrt::sampler::stratified::StratifiedSampler;
+use pbrt::sampler::zerotwosequence::ZeroTwoSequenceSampler;
+use pbrt::spectrum::RGBSpectrum;
+use pbrt::tonemap::ToneCurve;
+use pbrt::tonemap::ToneMapping;
+
+
+fn trace(ray: &Ray, scene: &BVHAccel) -> RGBSpectrum {
+	match scene.intersect(ray) {
+		None => RGBSpectrum::new(0.0),
+		Some((_, isect)) => {
+			let facing = Vector3f::from(isect.n).dot(&isect.wo).abs();
+			RGBSpectrum::new(facing)
+		}
+	}
 }
 
-fn trace(origin: Vector3<f64>, direction: Vector3<f64>, spheres: &Vec<Sphere>, depth: i64) -> image::Luma<u8> {
-	let mut t_near = f64::MAX;
-
-	//var sphere geometry.Sphere
-	let mut found_sphere = false;
-
-	// Find sphere intersection
-	let mut t0: f64;
-    let mut t1: f64;
-	let mut intersects: bool;
-
-	for (i, _) in (0..spheres.len()).enumerate() {
-		let resp = spheres[i].intersects(&origin, &direction);
-
-        t0 = resp.0;
-        t1 = resp.1;
-        intersects = resp.2;
 
-		if intersects {
-			if t0 < 0.0 {
-				t0 = t1;
-			}
-			if t0 < t_near {
-				t_near = t0;
-				found_sphere = true;
-			}
-		}
-	}
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
 
-	if !found_sphere {
-		return image::Luma([0u8])
-	}
 
-	return image::Luma([255u8])
+// Builds a sampler by name, taking at least `spp` samples per pixel.
+fn make_sampler(name: &str, spp: i64, sample_bounds: &Bounds2i) -> Result<Box<dyn Sampler>, String> {
+    match name {
+        "02sequence" => Ok(Box::new(ZeroTwoSequenceSampler::new(spp, 4, 0))),
+        "bluenoise" => Ok(Box::new(BlueNoiseSampler::new(spp, 0))),
+        "halton" => Ok(Box::new(HaltonSampler::new(spp, sample_bounds, RandomizeStrategy::PermuteDigits, false, 0))),
+        "pmj02" => Ok(Box::new(PMJ02Sampler::new(spp, 0))),
+        "random" => Ok(Box::new(RandomSampler::new(spp, 0))),
+        "sobol" => Ok(Box::new(SobolSampler::new(spp, sample_bounds, RandomizeStrategy::Owen, 0))),
+        "stratified" => {
+            // The squarest grid with at least spp cells
+            let x = (spp as f64).sqrt().ceil() as usize;
+            let y = (spp as usize).div_ceil(x);
+            Ok(Box::new(StratifiedSampler::new(x, y, true, 5, 0)))
+        }
+        _ => Err(format!("unknown sampler \"{}\" (expected 02sequence, bluenoise, halton, pmj02, random, sobol or stratified)", name)),
+    }
 }
 
 
+// With --adaptive, --spp is the most samples a pixel takes and --min-spp
+// the fewest; --aov writes the image with the samples each pixel took and
+// their luminance mean and variance.
+// Usage: hello_world [--exposure <stops>] [--tonemap clamp|reinhard|aces|agx]
+//                    [--dither] [--spp <n>]
+//                    [--adaptive <max relative error>] [--min-spp <n>] [--aov samples.exr]
+//                    [--sampler 02sequence|bluenoise|halton|pmj02|random|sobol|stratified]
+//                    [mesh.obj|mesh.ply]
 fn main() {
+    let mut tone_mapping = ToneMapping::default();
+    let mut sampler_name = "stratified".to_string();
+    let mut spp = 16;
+    let mut min_spp = 4;
+    let mut max_error = None;
+    let mut aov_path = None;
+    let mut mesh_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--exposure" => {
+                tone_mapping.exposure = args.next().and_then(|v| v.parse().ok())
+                    .unwrap_or_else(|| exit_with("--exposure needs a number of stops"));
+            }
+            "--tonemap" => {
+                let name = args.next().unwrap_or_else(|| exit_with("--tonemap needs a curve name"));
+                tone_mapping.curve = name.parse::<ToneCurve>().unwrap_or_else(|e| exit_with(&e));
+            }
+            "--dither" => tone_mapping.dither = true,
+            "--sampler" => sampler_name = args.next().unwrap_or_else(|| exit_with("--sampler needs a name")),
+            "--spp" => {
+                spp = args.next().and_then(|v| v.parse().ok()).filter(|&n| n > 0)
+                    .unwrap_or_else(|| exit_with("--spp needs a positive sample count"));
+            }
+            "--min-spp" => {
+                min_spp = args.next().and_then(|v| v.parse().ok()).filter(|&n| n > 0)
+                    .unwrap_or_else(|| exit_with("--min-spp needs a positive sample count"));
+            }
+            "--adaptive" => {
+                max_error = Some(args.next().and_then(|v| v.parse::<f64>().ok()).filter(|&e| e > 0.0)
+                    .unwrap_or_else(|| exit_with("--adaptive needs a positive relative error")));
+            }
+            "--aov" => aov_path = Some(args.next().unwrap_or_else(|| exit_with("--aov needs a path"))),
+            _ => mesh_path = Some(arg),
+        }
+    }
 
-    let mut spheres: Vec<Sphere> = Vec::new();
-//    spheres.push(Sphere::new(Vector3::new()));
+    let mut shapes: Vec<Box<dyn Shape>> = Vec::new();
 
     let n = 9;
 
@@ -91,34 +142,80 @@ fn main() {
 				if x > 0.0 {
 					radius = 2.0;
 				}
-				spheres.push(Sphere::new(Vector3::new(x, y, z), radius))
+				shapes.push(Box::new(Sphere::new(Transform::translate(&Vector3f::new(x, y, z)), radius)))
 			}
 		}
 	}
 
-    let camera = CameraSettings::new(192, 108);
-    let origin = Vector3::new(0.0, 0.0, 0.0);
-
-    let imgbuf = image::ImageBuffer::from_fn(camera.width as u32, camera.height as u32, |x, y| {
-        let xx = (2.0 * ((x as f64 + 0.5) * camera.inverse_width) - 1.0) * camera.angle * camera.aspect_ratio;
-		let yy = (1.0 - 2.0 * ((y as f64 + 0.5) * camera.inverse_height)) * camera.angle;
-
-        let mut ray_dir = Vector3::new(xx, yy, -1.0);
-//        ray_dir.normalize();
-        let nor2 = ray_dir.length_squared();
-        if nor2 > 0.0 {
-            let inv_nor = 1.0 / nor2.sqrt();
-            ray_dir.x *= inv_nor;
-            ray_dir.y *= inv_nor;
-            ray_dir.z *= inv_nor;
+    if let Some(path) = mesh_path {
+        let meshes = if path.ends_with(".ply") {
+            ply::load_ply(&path, &Transform::identity()).map(|mesh| vec![Arc::new(mesh)]).map_err(|e| e.to_string())
+        } else {
+            obj::load_obj(&path, &Transform::identity()).map(|scene| {
+                for warning in scene.warnings.iter() {
+                    eprintln!("warning: {}", warning);
+                }
+                scene.meshes.into_iter().map(|m| m.mesh).collect()
+            }).map_err(|e| e.to_string())
+        };
+        let meshes: Vec<Arc<TriangleMesh>> = meshes.unwrap_or_else(|e| exit_with(&e));
+        for mesh in meshes.iter() {
+            for tri in triangle::create_triangles(mesh) {
+                shapes.push(Box::new(tri));
+            }
         }
+    }
 
-        return trace(origin, ray_dir, &spheres, 0);
-    });
-
-    let ref mut fout = File::create("test.png").unwrap();
-
-    image::ImageLuma8(imgbuf).save(fout, image::PNG).unwrap();
-
+    let scene = BVHAccel::new(shapes, 4);
+
+    let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+    let filter = Box::new(GaussianFilter::new(Vector2f::new(2.0, 2.0), 2.0));
+    let mut film = Film::new(Point2i::new(192, 108), &full_window, filter, 35.0);
+    film.tone_mapping = tone_mapping;
+    // Flip z so the camera looks down -z with +x to the right
+    let camera_to_world = AnimatedTransform::fixed(Transform::scale(1.0, 1.0, -1.0));
+    let screen_window = camera::default_screen_window(&film.full_resolution);
+    let camera = PerspectiveCamera::new(camera_to_world, &screen_window, 0.0, 1.0, 0.0, 1e6, 30.0, &film);
+
+    // Samples cover the filter's reach past the image edges
+    let sample_bounds = film.sample_bounds();
+    let mut sampler = make_sampler(&sampler_name, spp, &sample_bounds).unwrap_or_else(|e| exit_with(&e));
+    if let Some(max_error) = max_error {
+        sampler = Box::new(AdaptiveSampler::new(sampler, min_spp.min(spp), max_error));
+    }
+    for y in sample_bounds.min.y..sample_bounds.max.y {
+        for x in sample_bounds.min.x..sample_bounds.max.x {
+            let pixel = Point2i::new(x, y);
+            sampler.start_pixel(&pixel);
+            loop {
+                let sample = sampler.get_camera_sample(&pixel);
+                let l = match camera.generate_ray(&sample) {
+                    Some((weight, ray)) => {
+                        let l = trace(&ray, &scene);
+                        film.add_sample(&sample.p_film, &l, weight);
+                        l * weight
+                    }
+                    None => RGBSpectrum::new(0.0),
+                };
+                sampler.record_radiance(&l);
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+            film.record_sample_stats(&pixel, &sampler.state().radiance_stats);
+        }
+    }
 
-}
\ No newline at end of file
+    if let Err(e) = film.write_image("test.png") {
+        exit_with(&e.to_string());
+    }
+    if let Some(path) = aov_path {
+        let mut image = film.exr_image().unwrap_or_else(|e| exit_with(&e));
+        if let Err(e) = film.add_sample_stats(&mut image) {
+            exit_with(&e);
+        }
+        if let Err(e) = exr::save_exr(&path, &image, Compression::Zip) {
+            exit_with(&e.to_string());
+        }
+    }
+}
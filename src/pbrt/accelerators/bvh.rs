@@ -0,0 +1,377 @@
+use pbrt::geometry::Bounds3f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::interaction::SurfaceInteraction;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::shape::Shape;
+
+
+const N_BUCKETS: usize = 12;
+
+#[derive(Clone, Copy, Debug)]
+struct BVHPrimitiveInfo {
+    primitive_number: usize,
+    bounds: Bounds3f,
+    centroid: Point3f,
+}
+
+impl BVHPrimitiveInfo {
+    fn new(primitive_number: usize, bounds: Bounds3f) -> BVHPrimitiveInfo {
+        BVHPrimitiveInfo {
+            primitive_number,
+            bounds,
+            centroid: (bounds.min + bounds.max) * 0.5,
+        }
+    }
+}
+
+struct BVHBuildNode {
+    bounds: Bounds3f,
+    children: Option<(Box<BVHBuildNode>, Box<BVHBuildNode>)>,
+    split_axis: u8,
+    first_prim_offset: usize,
+    n_primitives: usize,
+}
+
+impl BVHBuildNode {
+    fn leaf(first_prim_offset: usize, n_primitives: usize, bounds: Bounds3f) -> BVHBuildNode {
+        BVHBuildNode { bounds, children: None, split_axis: 0, first_prim_offset, n_primitives }
+    }
+
+    fn interior(split_axis: u8, c0: BVHBuildNode, c1: BVHBuildNode) -> BVHBuildNode {
+        BVHBuildNode {
+            bounds: c0.bounds.union(&c1.bounds),
+            children: Some((Box::new(c0), Box::new(c1))),
+            split_axis,
+            first_prim_offset: 0,
+            n_primitives: 0,
+        }
+    }
+}
+
+// Depth-first flattened node. Leaves store the offset of their first
+// primitive, interior nodes the offset of their second child (the first child
+// immediately follows its parent).
+#[derive(Clone, Copy, Debug)]
+struct LinearBVHNode {
+    bounds: Bounds3f,
+    offset: usize,
+    // Leaves of coincident centroids can hold any number of primitives
+    n_primitives: u32,
+    axis: u8,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BucketInfo {
+    count: usize,
+    bounds: Option<Bounds3f>,
+}
+
+
+pub struct BVHAccel {
+    primitives: Vec<Box<dyn Shape>>,
+    nodes: Vec<LinearBVHNode>,
+}
+
+impl BVHAccel {
+    pub fn new(primitives: Vec<Box<dyn Shape>>, max_prims_in_node: usize) -> BVHAccel {
+        let max_prims_in_node = max_prims_in_node.clamp(1, 255);
+        if primitives.is_empty() {
+            return BVHAccel { primitives, nodes: Vec::new() };
+        }
+
+        let mut primitive_info: Vec<BVHPrimitiveInfo> = primitives.iter()
+            .enumerate()
+            .map(|(i, p)| BVHPrimitiveInfo::new(i, p.world_bound()))
+            .collect();
+
+        let mut total_nodes = 0;
+        let mut ordered_prims: Vec<usize> = Vec::with_capacity(primitives.len());
+        let root = recursive_build(&mut primitive_info, max_prims_in_node, &mut total_nodes, &mut ordered_prims);
+
+        // Reorder primitives so each leaf references a contiguous range
+        let mut slots: Vec<Option<Box<dyn Shape>>> = primitives.into_iter().map(Some).collect();
+        let primitives = ordered_prims.iter().map(|&i| slots[i].take().unwrap()).collect();
+
+        let mut nodes = Vec::with_capacity(total_nodes);
+        flatten_bvh_tree(&root, &mut nodes);
+
+        return BVHAccel { primitives, nodes };
+    }
+
+    pub fn world_bound(&self) -> Option<Bounds3f> {
+        return self.nodes.first().map(|n| n.bounds);
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<(f64, SurfaceInteraction)> {
+        let mut ray = *ray;
+        let mut hit: Option<(f64, SurfaceInteraction)> = None;
+        self.traverse(&mut ray, |ray, primitive| {
+            if let Some((t_hit, isect)) = primitive.intersect(ray) {
+                ray.t_max = t_hit;
+                hit = Some((t_hit, isect));
+            }
+            return false;
+        });
+        return hit;
+    }
+
+    pub fn intersect_p(&self, ray: &Ray) -> bool {
+        let mut ray = *ray;
+        let mut hit = false;
+        self.traverse(&mut ray, |ray, primitive| {
+            hit = primitive.intersect_p(ray);
+            return hit;
+        });
+        return hit;
+    }
+
+    // Calls `visit` on the primitives of each leaf the ray reaches, nearer
+    // children first. `visit` may shorten the ray, and ends the traversal by
+    // returning true.
+    fn traverse<F: FnMut(&mut Ray, &dyn Shape) -> bool>(&self, ray: &mut Ray, mut visit: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inv_dir = Vector3f::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+        let dir_is_neg = [
+            (inv_dir.x < 0.0) as u8,
+            (inv_dir.y < 0.0) as u8,
+            (inv_dir.z < 0.0) as u8,
+        ];
+
+        let mut to_visit = [0usize; 64];
+        let mut to_visit_offset = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounds.intersect_p_inv(ray, &inv_dir, &dir_is_neg) {
+                if node.n_primitives > 0 {
+                    for primitive in &self.primitives[node.offset..node.offset + node.n_primitives as usize] {
+                        if visit(ray, primitive.as_ref()) {
+                            return;
+                        }
+                    }
+                    if to_visit_offset == 0 {
+                        break;
+                    }
+                    to_visit_offset -= 1;
+                    current = to_visit[to_visit_offset];
+                } else if dir_is_neg[node.axis as usize] == 1 {
+                    // Visit the second child first when the ray runs backwards along the split axis
+                    to_visit[to_visit_offset] = current + 1;
+                    to_visit_offset += 1;
+                    current = node.offset;
+                } else {
+                    to_visit[to_visit_offset] = node.offset;
+                    to_visit_offset += 1;
+                    current += 1;
+                }
+            } else {
+                if to_visit_offset == 0 {
+                    break;
+                }
+                to_visit_offset -= 1;
+                current = to_visit[to_visit_offset];
+            }
+        }
+    }
+}
+
+
+// Moves the elements matching `pred` to the front, returning how many there are.
+fn partition<T, F: Fn(&T) -> bool>(items: &mut [T], pred: F) -> usize {
+    let mut first = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(first, i);
+            first += 1;
+        }
+    }
+    return first;
+}
+
+fn recursive_build(
+    primitive_info: &mut [BVHPrimitiveInfo],
+    max_prims_in_node: usize,
+    total_nodes: &mut usize,
+    ordered_prims: &mut Vec<usize>,
+) -> BVHBuildNode {
+    *total_nodes += 1;
+
+    let bounds = primitive_info.iter()
+        .skip(1)
+        .fold(primitive_info[0].bounds, |b, info| b.union(&info.bounds));
+    let n_primitives = primitive_info.len();
+
+    let make_leaf = |primitive_info: &[BVHPrimitiveInfo], ordered_prims: &mut Vec<usize>| {
+        let first_prim_offset = ordered_prims.len();
+        ordered_prims.extend(primitive_info.iter().map(|info| info.primitive_number));
+        return BVHBuildNode::leaf(first_prim_offset, primitive_info.len(), bounds);
+    };
+
+    if n_primitives == 1 {
+        return make_leaf(primitive_info, ordered_prims);
+    }
+
+    // Choose the split dimension from the spread of primitive centroids
+    let centroid_bounds = primitive_info.iter()
+        .skip(1)
+        .fold(Bounds3f::from_point(primitive_info[0].centroid), |b, info| b.union_point(&info.centroid));
+    let dim = centroid_bounds.diagonal().max_dimension();
+    let (c_min, c_max) = (centroid_bounds.min[dim], centroid_bounds.max[dim]);
+
+    if c_max == c_min {
+        return make_leaf(primitive_info, ordered_prims);
+    }
+
+    let mid = if n_primitives <= 2 {
+        // Too few primitives for buckets to pay off; split into equal halves
+        let mid = n_primitives / 2;
+        primitive_info.select_nth_unstable_by(mid, |a, b| a.centroid[dim].partial_cmp(&b.centroid[dim]).unwrap());
+        mid
+    } else {
+        // Surface area heuristic over bucketed centroids
+        let bucket_of = |info: &BVHPrimitiveInfo| {
+            let b = (N_BUCKETS as f64 * (info.centroid[dim] - c_min) / (c_max - c_min)) as usize;
+            return b.min(N_BUCKETS - 1);
+        };
+
+        let mut buckets = [BucketInfo { count: 0, bounds: None }; N_BUCKETS];
+        for info in primitive_info.iter() {
+            let bucket = &mut buckets[bucket_of(info)];
+            bucket.count += 1;
+            bucket.bounds = Some(match bucket.bounds {
+                Some(b) => b.union(&info.bounds),
+                None => info.bounds,
+            });
+        }
+
+        let union_of = |range: &[BucketInfo]| {
+            let mut count = 0;
+            let mut bounds: Option<Bounds3f> = None;
+            for bucket in range.iter() {
+                count += bucket.count;
+                if let Some(b) = bucket.bounds {
+                    bounds = Some(bounds.map_or(b, |u| u.union(&b)));
+                }
+            }
+            return (count, bounds.map_or(0.0, |b| b.surface_area()));
+        };
+
+        // Cost of splitting after each bucket, relative to a traversal cost of 1/8
+        let mut min_cost = f64::MAX;
+        let mut min_cost_split_bucket = 0;
+        for i in 0..N_BUCKETS - 1 {
+            let (count0, area0) = union_of(&buckets[..=i]);
+            let (count1, area1) = union_of(&buckets[i + 1..]);
+            let cost = 0.125 + (count0 as f64 * area0 + count1 as f64 * area1) / bounds.surface_area();
+            if cost < min_cost {
+                min_cost = cost;
+                min_cost_split_bucket = i;
+            }
+        }
+
+        let leaf_cost = n_primitives as f64;
+        if n_primitives <= max_prims_in_node && min_cost >= leaf_cost {
+            return make_leaf(primitive_info, ordered_prims);
+        }
+
+        let mid = partition(primitive_info, |info| bucket_of(info) <= min_cost_split_bucket);
+        if mid == 0 || mid == n_primitives {
+            // Degenerate bucket split (e.g. all centroids within one bucket)
+            let mid = n_primitives / 2;
+            primitive_info.select_nth_unstable_by(mid, |a, b| a.centroid[dim].partial_cmp(&b.centroid[dim]).unwrap());
+            mid
+        } else {
+            mid
+        }
+    };
+
+    let (left, right) = primitive_info.split_at_mut(mid);
+    let c0 = recursive_build(left, max_prims_in_node, total_nodes, ordered_prims);
+    let c1 = recursive_build(right, max_prims_in_node, total_nodes, ordered_prims);
+    return BVHBuildNode::interior(dim, c0, c1);
+}
+
+fn flatten_bvh_tree(node: &BVHBuildNode, nodes: &mut Vec<LinearBVHNode>) -> usize {
+    let offset = nodes.len();
+    nodes.push(LinearBVHNode {
+        bounds: node.bounds,
+        offset: node.first_prim_offset,
+        n_primitives: node.n_primitives as u32,
+        axis: node.split_axis,
+    });
+
+    if let Some((ref c0, ref c1)) = node.children {
+        flatten_bvh_tree(c0, nodes);
+        let second_child_offset = flatten_bvh_tree(c1, nodes);
+        nodes[offset].offset = second_child_offset;
+    }
+    return offset;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::accelerators::bvh::BVHAccel;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::ray::Ray;
+    use pbrt::geometry::shape::Shape;
+    use pbrt::geometry::sphere::Sphere;
+    use pbrt::geometry::transform::Transform;
+    use pbrt::rng::Rng;
+
+    fn random_point(rng: &mut Rng, extent: f64) -> Vector3f {
+        return Vector3f::new(
+            (rng.uniform_f64() * 2.0 - 1.0) * extent,
+            (rng.uniform_f64() * 2.0 - 1.0) * extent,
+            (rng.uniform_f64() * 2.0 - 1.0) * extent,
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng::new(5);
+        let mut spheres: Vec<Sphere> = Vec::new();
+        for _ in 0..300 {
+            let center = random_point(&mut rng, 10.0);
+            spheres.push(Sphere::new(Transform::translate(&center), 0.2 + rng.uniform_f64()));
+        }
+        let shapes: Vec<Box<dyn Shape>> = spheres.iter().map(|s| Box::new(*s) as Box<dyn Shape>).collect();
+        let bvh = BVHAccel::new(shapes, 4);
+
+        let mut n_hits = 0;
+        for i in 0..2000 {
+            let origin = Point3f::from(random_point(&mut rng, 15.0));
+            let direction = random_point(&mut rng, 1.0);
+            let t_max = if i % 2 == 0 { f64::INFINITY } else { rng.uniform_f64() * 20.0 };
+            let ray = Ray::new(origin, direction, t_max, 0.0);
+
+            let nearest = spheres.iter()
+                .filter_map(|s| s.intersect(&ray).map(|(t, _)| t))
+                .fold(None, |best: Option<f64>, t| Some(best.map_or(t, |b| b.min(t))));
+            assert_eq!(bvh.intersect(&ray).map(|(t, _)| t), nearest);
+            assert_eq!(bvh.intersect_p(&ray), nearest.is_some());
+            n_hits += nearest.is_some() as usize;
+        }
+        // Both hits and misses were exercised
+        assert!(n_hits > 200 && n_hits < 1800, "{} hits", n_hits);
+    }
+
+    #[test]
+    fn keeps_every_coincident_primitive() {
+        // More primitives than a u16 count, all sharing one centroid, with
+        // the largest last
+        let n = 70000;
+        let shapes: Vec<Box<dyn Shape>> = (0..n)
+            .map(|i| Box::new(Sphere::new(Transform::identity(), 1.0 + i as f64 / n as f64)) as Box<dyn Shape>)
+            .collect();
+        let bvh = BVHAccel::new(shapes, 4);
+        let ray = Ray::new(Point3f::new(0.0, 0.0, 10.0), Vector3f::new(0.0, 0.0, -1.0), f64::INFINITY, 0.0);
+        let (t, _) = bvh.intersect(&ray).unwrap();
+        assert!((t - (9.0 - (n - 1) as f64 / n as f64)).abs() < 1e-9);
+    }
+}
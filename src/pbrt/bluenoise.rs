@@ -0,0 +1,186 @@
+use std::sync::OnceLock;
+
+use pbrt;
+use pbrt::geometry::Point2i;
+use pbrt::rng::Rng;
+
+
+pub const BLUE_NOISE_RESOLUTION: usize = 64;
+
+// Blue noise value in (0, 1) for pixel `p`, from a tile repeated across the
+// image. Each texture index reads the tile at its own toroidal offset, so
+// different dimensions get differently placed but equally blue patterns.
+pub fn blue_noise(texture_index: usize, p: &Point2i) -> f64 {
+    let offset = pbrt::mix_bits(texture_index as u64);
+    let res = BLUE_NOISE_RESOLUTION as i64;
+    let x = (p.x + (offset & 0xffff) as i64).rem_euclid(res) as usize;
+    let y = (p.y + (offset >> 32 & 0xffff) as i64).rem_euclid(res) as usize;
+    return (tile()[y * BLUE_NOISE_RESOLUTION + x] as f64 + 0.5) / (BLUE_NOISE_RESOLUTION * BLUE_NOISE_RESOLUTION) as f64;
+}
+
+// Ranks 0..RESOLUTION^2 laid out so any threshold gives evenly spread
+// pixels, built once by Ulichney's void-and-cluster method.
+fn tile() -> &'static [u32] {
+    static TILE: OnceLock<Vec<u32>> = OnceLock::new();
+    return TILE.get_or_init(|| void_and_cluster(&mut Rng::new(0)));
+}
+
+
+const SIGMA: f64 = 1.5;
+// Gaussian weights beyond this many pixels are negligible
+const RADIUS: i64 = 7;
+
+// Sum of Gaussian weights from the set pixels around each pixel, on a torus.
+#[derive(Clone)]
+struct Energy {
+    kernel: Vec<f64>,
+    values: Vec<f64>,
+}
+
+impl Energy {
+    fn new() -> Energy {
+        let mut kernel = Vec::new();
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                kernel.push((-((dx * dx + dy * dy) as f64) / (2.0 * SIGMA * SIGMA)).exp());
+            }
+        }
+        Energy { kernel, values: vec![0.0; BLUE_NOISE_RESOLUTION * BLUE_NOISE_RESOLUTION] }
+    }
+
+    fn splat(&mut self, i: usize, sign: f64) {
+        let res = BLUE_NOISE_RESOLUTION as i64;
+        let (x, y) = ((i % BLUE_NOISE_RESOLUTION) as i64, (i / BLUE_NOISE_RESOLUTION) as i64);
+        let mut k = 0;
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                let j = (y + dy).rem_euclid(res) * res + (x + dx).rem_euclid(res);
+                self.values[j as usize] += sign * self.kernel[k];
+                k += 1;
+            }
+        }
+    }
+
+    // Of the pixels where pattern[i] == which, the one with the most energy.
+    fn tightest_cluster(&self, pattern: &[bool], which: bool) -> usize {
+        let candidates = (0..self.values.len()).filter(|&i| pattern[i] == which);
+        return candidates.fold(None, |best: Option<usize>, i| match best {
+            Some(b) if self.values[b] >= self.values[i] => best,
+            _ => Some(i),
+        }).unwrap();
+    }
+
+    // Of the pixels where pattern[i] == which, the one with the least energy.
+    fn largest_void(&self, pattern: &[bool], which: bool) -> usize {
+        let candidates = (0..self.values.len()).filter(|&i| pattern[i] == which);
+        return candidates.fold(None, |best: Option<usize>, i| match best {
+            Some(b) if self.values[b] <= self.values[i] => best,
+            _ => Some(i),
+        }).unwrap();
+    }
+}
+
+fn void_and_cluster(rng: &mut Rng) -> Vec<u32> {
+    let n = BLUE_NOISE_RESOLUTION * BLUE_NOISE_RESOLUTION;
+    let mut ranks = vec![0u32; n];
+
+    // A random tenth of the pixels, relaxed by moving the tightest cluster
+    // into the largest void until that changes nothing
+    let mut pattern = vec![false; n];
+    let mut energy = Energy::new();
+    let mut n_initial = 0;
+    while n_initial < n / 10 {
+        let i = rng.uniform_u32_below(n as u32) as usize;
+        if !pattern[i] {
+            pattern[i] = true;
+            energy.splat(i, 1.0);
+            n_initial += 1;
+        }
+    }
+    loop {
+        let cluster = energy.tightest_cluster(&pattern, true);
+        pattern[cluster] = false;
+        energy.splat(cluster, -1.0);
+        let void = energy.largest_void(&pattern, false);
+        pattern[void] = true;
+        energy.splat(void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    // Rank the initial pixels by taking away tightest clusters
+    {
+        let mut pattern = pattern.clone();
+        let mut energy = energy.clone();
+        for rank in (0..n_initial).rev() {
+            let cluster = energy.tightest_cluster(&pattern, true);
+            pattern[cluster] = false;
+            energy.splat(cluster, -1.0);
+            ranks[cluster] = rank as u32;
+        }
+    }
+
+    // Fill the largest voids up to half the pixels
+    let mut rank = n_initial;
+    while rank < n / 2 {
+        let void = energy.largest_void(&pattern, false);
+        pattern[void] = true;
+        energy.splat(void, 1.0);
+        ranks[void] = rank as u32;
+        rank += 1;
+    }
+
+    // Past half, the unset pixels are the minority: fill their tightest
+    // clusters instead
+    let mut energy = Energy::new();
+    for (i, &set) in pattern.iter().enumerate() {
+        if !set {
+            energy.splat(i, 1.0);
+        }
+    }
+    while rank < n {
+        let cluster = energy.tightest_cluster(&pattern, false);
+        pattern[cluster] = true;
+        energy.splat(cluster, -1.0);
+        ranks[cluster] = rank as u32;
+        rank += 1;
+    }
+    return ranks;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::bluenoise;
+    use pbrt::geometry::Point2i;
+
+    #[test]
+    fn tile_is_a_high_pass_permutation() {
+        let res = bluenoise::BLUE_NOISE_RESOLUTION as i64;
+        let mut values = Vec::new();
+        for y in 0..res {
+            for x in 0..res {
+                values.push(bluenoise::blue_noise(0, &Point2i::new(x, y)));
+            }
+        }
+        // Every rank appears once
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(sorted.iter().enumerate().all(|(i, &v)| v == (i as f64 + 0.5) / (res * res) as f64));
+
+        // Neighbours differ far more than for white noise, where the mean
+        // absolute difference is 1/3
+        let mut difference = 0.0;
+        for y in 0..res {
+            for x in 0..res {
+                let v = bluenoise::blue_noise(0, &Point2i::new(x, y));
+                difference += (v - bluenoise::blue_noise(0, &Point2i::new(x + 1, y))).abs();
+            }
+        }
+        assert!(difference / (res * res) as f64 > 0.4);
+
+        // Tiles repeat, and other textures are shifted copies
+        assert_eq!(bluenoise::blue_noise(3, &Point2i::new(-1, 5)), bluenoise::blue_noise(3, &Point2i::new(res - 1, res + 5)));
+    }
+}
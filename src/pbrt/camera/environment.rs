@@ -0,0 +1,116 @@
+use std::f64;
+
+use pbrt;
+use pbrt::camera::Camera;
+use pbrt::camera::CameraSample;
+use pbrt::film::Film;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::ray::Ray;
+use pbrt::sampling;
+
+
+// How film positions are laid out over the sphere of directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EnvironmentMapping {
+    // Latitude-longitude, with +y at the top row of the image.
+    Equirectangular,
+    // Octahedral equal-area layout; needs a square film.
+    EqualArea,
+}
+
+// Sees every direction around the camera position, producing a full
+// 360x180 degree panorama. Camera space +y is up.
+pub struct EnvironmentCamera {
+    camera_to_world: AnimatedTransform,
+    shutter_open: f64,
+    shutter_close: f64,
+    mapping: EnvironmentMapping,
+    resolution: Point2f,
+}
+
+impl EnvironmentCamera {
+    // Returns None if `mapping` is EqualArea and the film is not square.
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        shutter_open: f64,
+        shutter_close: f64,
+        mapping: EnvironmentMapping,
+        film: &Film,
+    ) -> Option<EnvironmentCamera> {
+        if mapping == EnvironmentMapping::EqualArea && film.full_resolution.x != film.full_resolution.y {
+            return None;
+        }
+        let resolution = Point2f::new(film.full_resolution.x as f64, film.full_resolution.y as f64);
+        return Some(EnvironmentCamera { camera_to_world, shutter_open, shutter_close, mapping, resolution });
+    }
+
+    // Camera space direction seen at raster position `p_film`.
+    pub fn direction(&self, p_film: &Point2f) -> Vector3f {
+        let uv = Point2f::new(p_film.x / self.resolution.x, p_film.y / self.resolution.y);
+        let dir = match self.mapping {
+            EnvironmentMapping::Equirectangular => {
+                let theta = f64::consts::PI * uv.y;
+                let phi = 2.0 * f64::consts::PI * uv.x;
+                Vector3f::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+            }
+            EnvironmentMapping::EqualArea => sampling::equal_area_square_to_sphere(&sampling::wrap_equal_area_square(&uv)),
+        };
+
+        // Both layouts put the pole on z; swap it onto y so it points up
+        return Vector3f::new(dir.x, dir.z, dir.y);
+    }
+}
+
+impl Camera for EnvironmentCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<(f64, Ray)> {
+        let time = pbrt::lerp(sample.time, self.shutter_open, self.shutter_close);
+        let ray = Ray::new(Point3f::new(0.0, 0.0, 0.0), self.direction(&sample.p_film), f64::INFINITY, time);
+        return Some((1.0, self.camera_to_world.transform_ray(&ray)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::camera::environment::EnvironmentCamera;
+    use pbrt::camera::environment::EnvironmentMapping;
+    use pbrt::film::Film;
+    use pbrt::filter::BoxFilter;
+    use pbrt::geometry::Bounds2f;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point2i;
+    use pbrt::geometry::Vector2f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::animated_transform::AnimatedTransform;
+    use pbrt::geometry::transform::Transform;
+
+    #[test]
+    fn covers_sphere() {
+        let camera_to_world = AnimatedTransform::fixed(Transform::identity());
+        let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let film = Film::new(Point2i::new(64, 64), &full_window, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+
+        let equirect = EnvironmentCamera::new(camera_to_world, 0.0, 1.0, EnvironmentMapping::Equirectangular, &film).unwrap();
+        assert!((equirect.direction(&Point2f::new(10.0, 0.0)).y - 1.0).abs() < 1e-9);
+        assert!((equirect.direction(&Point2f::new(10.0, 64.0)).y + 1.0).abs() < 1e-9);
+
+        // The octahedral layout is symmetric, so pixel center directions cancel
+        let equal_area = EnvironmentCamera::new(camera_to_world, 0.0, 1.0, EnvironmentMapping::EqualArea, &film).unwrap();
+        let mut sum = Vector3f::new(0.0, 0.0, 0.0);
+        for y in 0..64 {
+            for x in 0..64 {
+                let d = equal_area.direction(&Point2f::new(x as f64 + 0.5, y as f64 + 0.5));
+                assert!((d.length() - 1.0).abs() < 1e-9);
+                sum += d;
+            }
+        }
+        assert!(sum.length() < 1e-9);
+
+        let wide = Film::new(Point2i::new(128, 64), &full_window, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+        assert!(EnvironmentCamera::new(camera_to_world, 0.0, 1.0, EnvironmentMapping::Equirectangular, &wide).is_some());
+        assert!(EnvironmentCamera::new(camera_to_world, 0.0, 1.0, EnvironmentMapping::EqualArea, &wide).is_none());
+    }
+}
@@ -0,0 +1,161 @@
+pub mod environment;
+pub mod orthographic;
+pub mod perspective;
+pub mod realistic;
+
+use pbrt;
+use pbrt::film::Film;
+use pbrt::geometry::Bounds2f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::ray::RayDifferential;
+use pbrt::geometry::transform::Transform;
+use pbrt::sampling;
+
+
+// Where on the film (in raster space) and lens a camera ray starts, and when.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraSample {
+    pub p_film: Point2f,
+    pub p_lens: Point2f,
+    pub time: f64,
+}
+
+impl CameraSample {
+    pub fn new(p_film: Point2f, p_lens: Point2f, time: f64) -> CameraSample {
+        CameraSample { p_film, p_lens, time }
+    }
+}
+
+pub trait Camera {
+    // Returns the world space ray for `sample` and how much it should
+    // contribute to the image, or None if the sample produces no ray.
+    fn generate_ray(&self, sample: &CameraSample) -> Option<(f64, Ray)>;
+
+    // Like `generate_ray`, with offset rays for samples one pixel over in x
+    // and y. The default estimates them by finite differences.
+    fn generate_ray_differential(&self, sample: &CameraSample) -> Option<(f64, RayDifferential)> {
+        let (weight, ray) = self.generate_ray(sample)?;
+        let mut rd = RayDifferential::from_ray(&ray);
+
+        let shifted = |dx: f64, dy: f64| {
+            for eps in [0.05, -0.05].iter() {
+                let mut s = *sample;
+                s.p_film.x += dx * eps;
+                s.p_film.y += dy * eps;
+                if let Some((_, r)) = self.generate_ray(&s) {
+                    return Some((
+                        ray.origin + (r.origin - ray.origin) / *eps,
+                        ray.direction + (r.direction - ray.direction) / *eps,
+                    ));
+                }
+            }
+            return None;
+        };
+
+        let (rx_origin, rx_direction) = shifted(1.0, 0.0)?;
+        let (ry_origin, ry_direction) = shifted(0.0, 1.0)?;
+        rd.rx_origin = rx_origin;
+        rd.rx_direction = rx_direction;
+        rd.ry_origin = ry_origin;
+        rd.ry_direction = ry_direction;
+        rd.has_differentials = true;
+        return Some((weight, rd));
+    }
+}
+
+
+// The screen window pbrt uses by default: [-1, 1] along the shorter image
+// axis, stretched along the longer one to keep pixels square.
+pub fn default_screen_window(resolution: &Point2i) -> Bounds2f {
+    let frame = resolution.x as f64 / resolution.y as f64;
+    if frame > 1.0 {
+        return Bounds2f::new(Point2f::new(-frame, -1.0), Point2f::new(frame, 1.0));
+    }
+    return Bounds2f::new(Point2f::new(-1.0, -1.0 / frame), Point2f::new(1.0, 1.0 / frame));
+}
+
+
+// Shared state for cameras defined by a camera-to-screen projection.
+#[derive(Clone, Copy, Debug)]
+pub struct ProjectiveCamera {
+    pub camera_to_world: AnimatedTransform,
+    pub camera_to_screen: Transform,
+    pub raster_to_camera: Transform,
+    pub screen_to_raster: Transform,
+    pub raster_to_screen: Transform,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub lens_radius: f64,
+    pub focal_distance: f64,
+}
+
+impl ProjectiveCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        camera_to_screen: Transform,
+        screen_window: &Bounds2f,
+        shutter_open: f64,
+        shutter_close: f64,
+        lens_radius: f64,
+        focal_distance: f64,
+        film: &Film,
+    ) -> ProjectiveCamera {
+        // Raster y runs down the image while screen y runs up
+        let screen_to_raster = Transform::scale(film.full_resolution.x as f64, film.full_resolution.y as f64, 1.0)
+            * Transform::scale(
+                1.0 / (screen_window.max.x - screen_window.min.x),
+                1.0 / (screen_window.min.y - screen_window.max.y),
+                1.0,
+            )
+            * Transform::translate(&Vector3f::new(-screen_window.min.x, -screen_window.max.y, 0.0));
+        let raster_to_screen = screen_to_raster.inverse();
+        let raster_to_camera = camera_to_screen.inverse() * raster_to_screen;
+
+        ProjectiveCamera {
+            camera_to_world,
+            camera_to_screen,
+            raster_to_camera,
+            screen_to_raster,
+            raster_to_screen,
+            shutter_open,
+            shutter_close,
+            lens_radius,
+            focal_distance,
+        }
+    }
+
+    // The camera space point on the near plane under the sample's film
+    // position.
+    pub fn camera_point(&self, sample: &CameraSample) -> Point3f {
+        let p_film = Point3f::new(sample.p_film.x, sample.p_film.y, 0.0);
+        return self.raster_to_camera.transform_point(&p_film);
+    }
+
+    // The sample's time within the shutter interval.
+    pub fn time(&self, sample: &CameraSample) -> f64 {
+        return pbrt::lerp(sample.time, self.shutter_open, self.shutter_close);
+    }
+
+    // Moves a camera space ray onto the thin lens at `u_lens`, aiming it at
+    // the point where the original ray crosses the plane of focus.
+    pub fn thin_lens(&self, u_lens: &Point2f, origin: &Point3f, direction: &Vector3f) -> (Point3f, Vector3f) {
+        if self.lens_radius <= 0.0 {
+            return (*origin, *direction);
+        }
+
+        let p_lens = sampling::concentric_sample_disk(u_lens) * self.lens_radius;
+        let ft = self.focal_distance / direction.z;
+        let p_focus = *origin + *direction * ft;
+
+        let lens_origin = Point3f::new(origin.x + p_lens.x, origin.y + p_lens.y, origin.z);
+        let mut lens_direction = p_focus - lens_origin;
+        lens_direction.normalize();
+        return (lens_origin, lens_direction);
+    }
+}
@@ -0,0 +1,127 @@
+use std::f64;
+
+use pbrt::camera::Camera;
+use pbrt::camera::CameraSample;
+use pbrt::camera::ProjectiveCamera;
+use pbrt::film::Film;
+use pbrt::geometry::Bounds2f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::ray::RayDifferential;
+use pbrt::geometry::transform::Transform;
+
+
+// Parallel projection along +z in camera space. The screen window gives the
+// extent of the image in camera space units.
+pub struct OrthographicCamera {
+    projective: ProjectiveCamera,
+    dx_camera: Vector3f,
+    dy_camera: Vector3f,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        screen_window: &Bounds2f,
+        shutter_open: f64,
+        shutter_close: f64,
+        lens_radius: f64,
+        focal_distance: f64,
+        film: &Film,
+    ) -> OrthographicCamera {
+        let projective = ProjectiveCamera::new(
+            camera_to_world,
+            Transform::orthographic(0.0, 1.0),
+            screen_window,
+            shutter_open,
+            shutter_close,
+            lens_radius,
+            focal_distance,
+            film,
+        );
+
+        let dx_camera = projective.raster_to_camera.transform_vector(&Vector3f::new(1.0, 0.0, 0.0));
+        let dy_camera = projective.raster_to_camera.transform_vector(&Vector3f::new(0.0, 1.0, 0.0));
+
+        OrthographicCamera { projective, dx_camera, dy_camera }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<(f64, Ray)> {
+        let forward = Vector3f::new(0.0, 0.0, 1.0);
+        let (origin, direction) = self.projective.thin_lens(&sample.p_lens, &self.projective.camera_point(sample), &forward);
+
+        let ray = Ray::new(origin, direction, f64::INFINITY, self.projective.time(sample));
+        return Some((1.0, self.projective.camera_to_world.transform_ray(&ray)));
+    }
+
+    fn generate_ray_differential(&self, sample: &CameraSample) -> Option<(f64, RayDifferential)> {
+        let p_camera = self.projective.camera_point(sample);
+        let forward = Vector3f::new(0.0, 0.0, 1.0);
+
+        // Without a lens the offset rays are parallel to the main ray
+        let (origin, direction) = self.projective.thin_lens(&sample.p_lens, &p_camera, &forward);
+        let (rx_origin, rx_direction) = self.projective.thin_lens(&sample.p_lens, &(p_camera + self.dx_camera), &forward);
+        let (ry_origin, ry_direction) = self.projective.thin_lens(&sample.p_lens, &(p_camera + self.dy_camera), &forward);
+
+        let mut rd = RayDifferential::from_ray(&Ray::new(origin, direction, f64::INFINITY, self.projective.time(sample)));
+        rd.rx_origin = rx_origin;
+        rd.rx_direction = rx_direction;
+        rd.ry_origin = ry_origin;
+        rd.ry_direction = ry_direction;
+        rd.has_differentials = true;
+        return Some((1.0, self.projective.camera_to_world.transform_ray_differential(&rd)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::camera;
+    use pbrt::camera::Camera;
+    use pbrt::camera::CameraSample;
+    use pbrt::camera::orthographic::OrthographicCamera;
+    use pbrt::film::Film;
+    use pbrt::filter::BoxFilter;
+    use pbrt::geometry::Bounds2f;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point2i;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector2f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::animated_transform::AnimatedTransform;
+    use pbrt::geometry::transform::Transform;
+
+    #[test]
+    fn rays_are_parallel_across_the_screen_window() {
+        let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let film = Film::new(Point2i::new(200, 100), &full_window, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+        let screen_window = camera::default_screen_window(&film.full_resolution);
+        let camera = OrthographicCamera::new(AnimatedTransform::fixed(Transform::identity()), &screen_window, 0.0, 1.0, 0.0, 1e6, &film);
+
+        // Raster corners land on the screen window's corners, y flipped
+        let corners = [
+            (Point2f::new(0.0, 0.0), Point3f::new(-2.0, 1.0, 0.0)),
+            (Point2f::new(200.0, 0.0), Point3f::new(2.0, 1.0, 0.0)),
+            (Point2f::new(0.0, 100.0), Point3f::new(-2.0, -1.0, 0.0)),
+            (Point2f::new(200.0, 100.0), Point3f::new(2.0, -1.0, 0.0)),
+            (Point2f::new(100.0, 50.0), Point3f::new(0.0, 0.0, 0.0)),
+        ];
+        for &(p_film, origin) in corners.iter() {
+            let (weight, ray) = camera.generate_ray(&CameraSample::new(p_film, Point2f::new(0.5, 0.5), 0.0)).unwrap();
+            assert_eq!(weight, 1.0);
+            assert!((ray.origin - origin).length() < 1e-9);
+            assert!(ray.direction.x == 0.0 && ray.direction.y == 0.0 && ray.direction.z == 1.0);
+        }
+
+        // Offset rays are parallel, one pixel over
+        let sample = CameraSample::new(Point2f::new(30.0, 70.0), Point2f::new(0.5, 0.5), 0.0);
+        let (_, rd) = camera.generate_ray_differential(&sample).unwrap();
+        assert!(rd.has_differentials);
+        assert!((rd.rx_origin - rd.origin - Vector3f::new(0.02, 0.0, 0.0)).length() < 1e-9);
+        assert!((rd.ry_origin - rd.origin - Vector3f::new(0.0, -0.02, 0.0)).length() < 1e-9);
+        assert!(rd.rx_direction.z == 1.0 && rd.ry_direction.z == 1.0);
+    }
+}
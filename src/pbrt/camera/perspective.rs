@@ -0,0 +1,161 @@
+use std::f64;
+
+use pbrt::camera::Camera;
+use pbrt::camera::CameraSample;
+use pbrt::camera::ProjectiveCamera;
+use pbrt::film::Film;
+use pbrt::geometry::Bounds2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::ray::RayDifferential;
+use pbrt::geometry::transform::Transform;
+
+
+// Camera looking down +z in camera space, with `fov` spanning the shorter
+// axis of the default screen window. A zero lens radius gives a pinhole.
+pub struct PerspectiveCamera {
+    projective: ProjectiveCamera,
+    dx_camera: Vector3f,
+    dy_camera: Vector3f,
+}
+
+impl PerspectiveCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        screen_window: &Bounds2f,
+        shutter_open: f64,
+        shutter_close: f64,
+        lens_radius: f64,
+        focal_distance: f64,
+        fov: f64,
+        film: &Film,
+    ) -> PerspectiveCamera {
+        let projective = ProjectiveCamera::new(
+            camera_to_world,
+            Transform::perspective(fov, 1e-2, 1000.0),
+            screen_window,
+            shutter_open,
+            shutter_close,
+            lens_radius,
+            focal_distance,
+            film,
+        );
+
+        // Camera space offset between neighbouring pixels
+        let origin = projective.raster_to_camera.transform_point(&Point3f::new(0.0, 0.0, 0.0));
+        let dx_camera = projective.raster_to_camera.transform_point(&Point3f::new(1.0, 0.0, 0.0)) - origin;
+        let dy_camera = projective.raster_to_camera.transform_point(&Point3f::new(0.0, 1.0, 0.0)) - origin;
+
+        PerspectiveCamera { projective, dx_camera, dy_camera }
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<(f64, Ray)> {
+        let mut direction = Vector3f::from(self.projective.camera_point(sample));
+        direction.normalize();
+        let (origin, direction) = self.projective.thin_lens(&sample.p_lens, &Point3f::new(0.0, 0.0, 0.0), &direction);
+
+        let ray = Ray::new(origin, direction, f64::INFINITY, self.projective.time(sample));
+        return Some((1.0, self.projective.camera_to_world.transform_ray(&ray)));
+    }
+
+    fn generate_ray_differential(&self, sample: &CameraSample) -> Option<(f64, RayDifferential)> {
+        let p_camera = Vector3f::from(self.projective.camera_point(sample));
+        let pinhole = Point3f::new(0.0, 0.0, 0.0);
+
+        // Offset rays share the main ray's lens position, so they focus
+        // through the same point on the lens
+        let lens_ray = |mut d: Vector3f| {
+            d.normalize();
+            return self.projective.thin_lens(&sample.p_lens, &pinhole, &d);
+        };
+        let (origin, direction) = lens_ray(p_camera);
+        let (rx_origin, rx_direction) = lens_ray(p_camera + self.dx_camera);
+        let (ry_origin, ry_direction) = lens_ray(p_camera + self.dy_camera);
+
+        let mut rd = RayDifferential::from_ray(&Ray::new(origin, direction, f64::INFINITY, self.projective.time(sample)));
+        rd.rx_origin = rx_origin;
+        rd.rx_direction = rx_direction;
+        rd.ry_origin = ry_origin;
+        rd.ry_direction = ry_direction;
+        rd.has_differentials = true;
+        return Some((1.0, self.projective.camera_to_world.transform_ray_differential(&rd)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::camera;
+    use pbrt::camera::Camera;
+    use pbrt::camera::CameraSample;
+    use pbrt::camera::perspective::PerspectiveCamera;
+    use pbrt::film::Film;
+    use pbrt::filter::BoxFilter;
+    use pbrt::geometry::Bounds2f;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point2i;
+    use pbrt::geometry::Vector2f;
+    use pbrt::geometry::animated_transform::AnimatedTransform;
+    use pbrt::geometry::transform::Transform;
+
+    #[test]
+    fn rays_span_fov() {
+        let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let film = Film::new(Point2i::new(200, 100), &full_window, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+        let camera = PerspectiveCamera::new(
+            AnimatedTransform::fixed(Transform::identity()),
+            &camera::default_screen_window(&film.full_resolution),
+            0.0,
+            1.0,
+            0.0,
+            1e6,
+            90.0,
+            &film,
+        );
+
+        let (_, center) = camera.generate_ray(&CameraSample::new(Point2f::new(100.0, 50.0), Point2f::new(0.5, 0.5), 0.0)).unwrap();
+        assert!((center.direction.z - 1.0).abs() < 1e-9);
+
+        // A 90 degree fov puts the top edge at 45 degrees, and raster y runs down
+        let (_, top) = camera.generate_ray(&CameraSample::new(Point2f::new(100.0, 0.0), Point2f::new(0.5, 0.5), 0.0)).unwrap();
+        assert!((top.direction.y - top.direction.z).abs() < 1e-9);
+        assert!(top.direction.y > 0.0);
+
+        let (_, rd) = camera.generate_ray_differential(&CameraSample::new(Point2f::new(100.0, 50.0), Point2f::new(0.5, 0.5), 0.0)).unwrap();
+        assert!(rd.has_differentials);
+        assert!(rd.rx_direction.x > 0.0 && rd.ry_direction.y < 0.0);
+    }
+
+    #[test]
+    fn thin_lens_focuses() {
+        let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let film = Film::new(Point2i::new(64, 64), &full_window, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+        let camera = PerspectiveCamera::new(
+            AnimatedTransform::fixed(Transform::identity()),
+            &camera::default_screen_window(&film.full_resolution),
+            0.0,
+            1.0,
+            0.5,
+            10.0,
+            60.0,
+            &film,
+        );
+
+        // Every lens position sees the same point on the plane of focus
+        let p_film = Point2f::new(10.0, 40.0);
+        let focus = |u: Point2f| {
+            let (_, r) = camera.generate_ray_differential(&CameraSample::new(p_film, u, 0.0)).unwrap();
+            return (r.origin + r.direction * ((10.0 - r.origin.z) / r.direction.z), r);
+        };
+        let (p0, r0) = focus(Point2f::new(0.5, 0.5));
+        let (p1, r1) = focus(Point2f::new(0.9, 0.2));
+        assert!((p0 - p1).length() < 1e-9);
+        assert!((r1.origin - r0.origin).length() > 0.1);
+        assert!((r1.rx_origin - r1.origin).length() < 1e-12);
+    }
+}
@@ -0,0 +1,434 @@
+use std::f64;
+
+use pbrt;
+use pbrt::camera::Camera;
+use pbrt::camera::CameraSample;
+use pbrt::film::Film;
+use pbrt::geometry::Bounds2f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::transform::Transform;
+use pbrt::io::lens::LensElement;
+use pbrt::lowdiscrepancy;
+
+
+// Exit pupil bounds are tabulated for this many film radii.
+const N_PUPIL_BOUNDS: usize = 64;
+// Rays traced towards the rear element when bounding each exit pupil.
+const N_PUPIL_SAMPLES: u64 = 256 * 256;
+
+// A lens interface in meters. `aperture_radius` is half the file's diameter.
+#[derive(Clone, Copy, Debug)]
+struct LensElementInterface {
+    curvature_radius: f64,
+    thickness: f64,
+    eta: f64,
+    aperture_radius: f64,
+}
+
+// Traces rays from the film through a system of spherical lens elements.
+//
+// Lens system space has the film at z = 0 and the lens at negative z, with
+// the scene beyond it; camera space is the same with z flipped, so the camera
+// still looks down +z.
+pub struct RealisticCamera {
+    camera_to_world: AnimatedTransform,
+    shutter_open: f64,
+    shutter_close: f64,
+    simple_weighting: bool,
+    element_interfaces: Vec<LensElementInterface>,
+    exit_pupil_bounds: Vec<Bounds2f>,
+    resolution: Point2f,
+    physical_extent: Bounds2f,
+    film_diagonal: f64,
+}
+
+impl RealisticCamera {
+    // Focuses the lens at `focus_distance` meters from the film by moving it
+    // along the optical axis. Returns None if the lens cannot focus there.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        camera_to_world: AnimatedTransform,
+        shutter_open: f64,
+        shutter_close: f64,
+        aperture_diameter: f64,
+        focus_distance: f64,
+        simple_weighting: bool,
+        lens: &[LensElement],
+        film: &Film,
+    ) -> Option<RealisticCamera> {
+        let element_interfaces = lens.iter()
+            .map(|e| {
+                // The requested aperture can stop the lens down, but not open it up
+                let diameter = if e.curvature_radius == 0.0 {
+                    aperture_diameter.min(e.aperture_diameter)
+                } else {
+                    e.aperture_diameter
+                };
+                LensElementInterface {
+                    curvature_radius: e.curvature_radius * 0.001,
+                    thickness: e.thickness * 0.001,
+                    eta: e.eta,
+                    aperture_radius: diameter * 0.001 / 2.0,
+                }
+            })
+            .collect();
+
+        let mut camera = RealisticCamera {
+            camera_to_world,
+            shutter_open,
+            shutter_close,
+            simple_weighting,
+            element_interfaces,
+            exit_pupil_bounds: Vec::new(),
+            resolution: Point2f::new(film.full_resolution.x as f64, film.full_resolution.y as f64),
+            physical_extent: film.physical_extent(),
+            film_diagonal: film.diagonal,
+        };
+
+        let rear_thickness = camera.focus_thick_lens(focus_distance)?;
+        camera.element_interfaces.last_mut()?.thickness = rear_thickness;
+
+        camera.exit_pupil_bounds = (0..N_PUPIL_BOUNDS)
+            .map(|i| {
+                let r0 = i as f64 / N_PUPIL_BOUNDS as f64 * camera.film_diagonal / 2.0;
+                let r1 = (i + 1) as f64 / N_PUPIL_BOUNDS as f64 * camera.film_diagonal / 2.0;
+                camera.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+
+        return Some(camera);
+    }
+
+    fn lens_rear_z(&self) -> f64 {
+        return self.element_interfaces.last().map_or(0.0, |e| e.thickness);
+    }
+
+    fn lens_front_z(&self) -> f64 {
+        return self.element_interfaces.iter().map(|e| e.thickness).sum();
+    }
+
+    fn rear_element_radius(&self) -> f64 {
+        return self.element_interfaces.last().map_or(0.0, |e| e.aperture_radius);
+    }
+
+    // Follows a camera space ray leaving the film out through the front of
+    // the lens. Returns None if an element or the stop blocks it.
+    fn trace_lenses_from_film(&self, r_camera: &Ray) -> Option<Ray> {
+        let flip = Transform::scale(1.0, 1.0, -1.0);
+        let mut r_lens = flip.transform_ray(r_camera);
+
+        let mut element_z = 0.0;
+        for i in (0..self.element_interfaces.len()).rev() {
+            let element = &self.element_interfaces[i];
+            element_z -= element.thickness;
+
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, n) = if is_stop {
+                // Refraction at the previous element can turn the ray back towards the film
+                if r_lens.direction.z >= 0.0 {
+                    return None;
+                }
+                ((element_z - r_lens.origin.z) / r_lens.direction.z, Vector3f::new(0.0, 0.0, 0.0))
+            } else {
+                let radius = element.curvature_radius;
+                intersect_spherical_element(radius, element_z + radius, &r_lens)?
+            };
+
+            let p_hit = r_lens.origin + r_lens.direction * t;
+            if p_hit.x * p_hit.x + p_hit.y * p_hit.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            r_lens.origin = p_hit;
+
+            if !is_stop {
+                // Air gaps and the stop are written with an eta of zero
+                let eta_i = element.eta;
+                let eta_t = if i > 0 && self.element_interfaces[i - 1].eta != 0.0 {
+                    self.element_interfaces[i - 1].eta
+                } else {
+                    1.0
+                };
+                let mut wi = -r_lens.direction;
+                wi.normalize();
+                r_lens.direction = refract(&wi, &n, eta_i / eta_t)?;
+            }
+        }
+
+        return Some(flip.transform_ray(&r_lens));
+    }
+
+    // Follows a camera space ray from the scene in through the front of the
+    // lens towards the film.
+    fn trace_lenses_from_scene(&self, r_camera: &Ray) -> Option<Ray> {
+        let flip = Transform::scale(1.0, 1.0, -1.0);
+        let mut r_lens = flip.transform_ray(r_camera);
+
+        let mut element_z = -self.lens_front_z();
+        for (i, element) in self.element_interfaces.iter().enumerate() {
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, n) = if is_stop {
+                ((element_z - r_lens.origin.z) / r_lens.direction.z, Vector3f::new(0.0, 0.0, 0.0))
+            } else {
+                let radius = element.curvature_radius;
+                intersect_spherical_element(radius, element_z + radius, &r_lens)?
+            };
+
+            let p_hit = r_lens.origin + r_lens.direction * t;
+            if p_hit.x * p_hit.x + p_hit.y * p_hit.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            r_lens.origin = p_hit;
+
+            if !is_stop {
+                let eta_i = if i == 0 || self.element_interfaces[i - 1].eta == 0.0 {
+                    1.0
+                } else {
+                    self.element_interfaces[i - 1].eta
+                };
+                let eta_t = if element.eta != 0.0 { element.eta } else { 1.0 };
+                let mut wi = -r_lens.direction;
+                wi.normalize();
+                r_lens.direction = refract(&wi, &n, eta_i / eta_t)?;
+            }
+            element_z += element.thickness;
+        }
+
+        return Some(flip.transform_ray(&r_lens));
+    }
+
+    // Principal plane and focal point z of the lens system, as seen from the
+    // film side ([0]) and the scene side ([1]), found by tracing rays that
+    // enter parallel to the axis.
+    fn compute_thick_lens_approximation(&self) -> Option<([f64; 2], [f64; 2])> {
+        let x = 0.001 * self.film_diagonal;
+
+        let r_scene = Ray::new(Point3f::new(x, 0.0, self.lens_front_z() + 1.0), Vector3f::new(0.0, 0.0, -1.0), f64::INFINITY, 0.0);
+        let r_film = self.trace_lenses_from_scene(&r_scene)?;
+        let (pz0, fz0) = compute_cardinal_points(&r_scene, &r_film);
+
+        let r_film = Ray::new(Point3f::new(x, 0.0, self.lens_rear_z() - 1.0), Vector3f::new(0.0, 0.0, 1.0), f64::INFINITY, 0.0);
+        let r_scene = self.trace_lenses_from_film(&r_film)?;
+        let (pz1, fz1) = compute_cardinal_points(&r_film, &r_scene);
+
+        return Some(([pz0, pz1], [fz0, fz1]));
+    }
+
+    // Rear element to film distance that brings `focus_distance` into focus,
+    // from the thick lens equation.
+    fn focus_thick_lens(&self, focus_distance: f64) -> Option<f64> {
+        let (pz, fz) = self.compute_thick_lens_approximation()?;
+        let f = fz[0] - pz[0];
+        let z = -focus_distance;
+        let c = (pz[1] - z - pz[0]) * (pz[1] - z - 4.0 * f - pz[0]);
+        if c <= 0.0 {
+            return None;
+        }
+
+        let delta = 0.5 * (pz[1] - z + pz[0] - c.sqrt());
+        return Some(self.element_interfaces.last()?.thickness + delta);
+    }
+
+    // Bounds on the rear element of the points that rays from film points
+    // with x in [p_film_x0, p_film_x1] on the x axis can pass through.
+    fn bound_exit_pupil(&self, p_film_x0: f64, p_film_x1: f64) -> Bounds2f {
+        let rear_radius = self.rear_element_radius();
+        let proj_rear_bounds = Bounds2f::new(
+            Point2f::new(-1.5 * rear_radius, -1.5 * rear_radius),
+            Point2f::new(1.5 * rear_radius, 1.5 * rear_radius),
+        );
+
+        let mut pupil_bounds: Option<Bounds2f> = None;
+        for i in 0..N_PUPIL_SAMPLES {
+            let p_film = Point3f::new(
+                pbrt::lerp((i as f64 + 0.5) / N_PUPIL_SAMPLES as f64, p_film_x0, p_film_x1),
+                0.0,
+                0.0,
+            );
+            let p_rear = proj_rear_bounds.lerp(Point2f::new(
+                lowdiscrepancy::radical_inverse(2, i),
+                lowdiscrepancy::radical_inverse(3, i),
+            ));
+            let p_rear3 = Point3f::new(p_rear.x, p_rear.y, self.lens_rear_z());
+
+            let inside = pupil_bounds.is_some_and(|b| b.inside(&p_rear));
+            if inside || self.trace_lenses_from_film(&Ray::new(p_film, p_rear3 - p_film, f64::INFINITY, 0.0)).is_some() {
+                pupil_bounds = Some(pupil_bounds.map_or(Bounds2f::new(p_rear, p_rear), |b| b.union_point(&p_rear)));
+            }
+        }
+
+        // Pad by the sample spacing so thin slivers of the pupil are not lost
+        return match pupil_bounds {
+            Some(b) => b.expand(2.0 * proj_rear_bounds.diagonal().length() / (N_PUPIL_SAMPLES as f64).sqrt()),
+            None => proj_rear_bounds,
+        };
+    }
+
+    // Picks a point on the rear element for a ray from `p_film`, returning it
+    // with the area of the pupil bounds it was sampled from.
+    fn sample_exit_pupil(&self, p_film: &Point2f, lens_sample: &Point2f) -> (Point3f, f64) {
+        // The pupil bounds were computed along +x; rotate them to p_film
+        let r_film = (p_film.x * p_film.x + p_film.y * p_film.y).sqrt();
+        let r_index = (r_film / (self.film_diagonal / 2.0) * self.exit_pupil_bounds.len() as f64) as usize;
+        let pupil_bounds = self.exit_pupil_bounds[r_index.min(self.exit_pupil_bounds.len() - 1)];
+
+        let p_lens = pupil_bounds.lerp(*lens_sample);
+        let (sin_theta, cos_theta) = if r_film != 0.0 {
+            (p_film.y / r_film, p_film.x / r_film)
+        } else {
+            (0.0, 1.0)
+        };
+        let p_rear = Point3f::new(
+            cos_theta * p_lens.x - sin_theta * p_lens.y,
+            sin_theta * p_lens.x + cos_theta * p_lens.y,
+            self.lens_rear_z(),
+        );
+        return (p_rear, pupil_bounds.area());
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<(f64, Ray)> {
+        // The lens inverts the image, so flip x to keep it upright
+        let s = Point2f::new(sample.p_film.x / self.resolution.x, sample.p_film.y / self.resolution.y);
+        let p_film2 = self.physical_extent.lerp(s);
+        let p_film = Point3f::new(-p_film2.x, p_film2.y, 0.0);
+
+        let (p_rear, exit_pupil_bounds_area) = self.sample_exit_pupil(&Point2f::new(p_film.x, p_film.y), &sample.p_lens);
+        let time = pbrt::lerp(sample.time, self.shutter_open, self.shutter_close);
+        let r_film = Ray::new(p_film, p_rear - p_film, f64::INFINITY, time);
+        let ray = self.trace_lenses_from_film(&r_film)?;
+
+        let mut ray = self.camera_to_world.transform_ray(&ray);
+        ray.direction.normalize();
+
+        // Irradiance falls off with cos^4 of the angle to the film normal
+        let mut d = r_film.direction;
+        d.normalize();
+        let cos4_theta = (d.z * d.z) * (d.z * d.z);
+        let weight = if self.simple_weighting {
+            cos4_theta * exit_pupil_bounds_area / self.exit_pupil_bounds[0].area()
+        } else {
+            let rear_z = self.lens_rear_z();
+            (self.shutter_close - self.shutter_open) * (cos4_theta * exit_pupil_bounds_area) / (rear_z * rear_z)
+        };
+        return Some((weight, ray));
+    }
+}
+
+
+// Intersects a lens system space ray with the spherical element of `radius`
+// centered on the axis at `z_center`, returning t and the surface normal
+// facing back along the ray.
+fn intersect_spherical_element(radius: f64, z_center: f64, ray: &Ray) -> Option<(f64, Vector3f)> {
+    let o = ray.origin - Vector3f::new(0.0, 0.0, z_center);
+    let o = Vector3f::from(o);
+    let d = ray.direction;
+    let a = d.length_squared();
+    let b = 2.0 * d.dot(&o);
+    let c = o.length_squared() - radius * radius;
+    let (t0, t1) = pbrt::quadratic(a, b, c)?;
+
+    // Convex elements are hit first on the near side, concave on the far side
+    let use_closer_t = (d.z > 0.0) ^ (radius < 0.0);
+    let t = if use_closer_t { t0.min(t1) } else { t0.max(t1) };
+    if t < 0.0 {
+        return None;
+    }
+
+    let mut n = o + d * t;
+    n.normalize();
+    if n.dot(&-d) < 0.0 {
+        n = -n;
+    }
+    return Some((t, n));
+}
+
+// Snell's law for incident direction `wi` (pointing away from the surface)
+// and relative index `eta`. Returns None on total internal reflection.
+fn refract(wi: &Vector3f, n: &Vector3f, eta: f64) -> Option<Vector3f> {
+    let cos_theta_i = n.dot(wi);
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    return Some(-*wi * eta + *n * (eta * cos_theta_i - cos_theta_t));
+}
+
+// Principal plane and focal point z for a ray entering parallel to the axis
+// (`r_in`) and the same ray after it leaves the lens (`r_out`).
+fn compute_cardinal_points(r_in: &Ray, r_out: &Ray) -> (f64, f64) {
+    let tf = -r_out.origin.x / r_out.direction.x;
+    let fz = -(r_out.origin + r_out.direction * tf).z;
+    let tp = (r_in.origin.x - r_out.origin.x) / r_out.direction.x;
+    let pz = -(r_out.origin + r_out.direction * tp).z;
+    return (pz, fz);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use pbrt::camera::Camera;
+    use pbrt::camera::CameraSample;
+    use pbrt::camera::realistic::RealisticCamera;
+    use pbrt::film::Film;
+    use pbrt::filter::BoxFilter;
+    use pbrt::geometry::Bounds2f;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point2i;
+    use pbrt::geometry::Vector2f;
+    use pbrt::geometry::animated_transform::AnimatedTransform;
+    use pbrt::geometry::transform::Transform;
+    use pbrt::io::lens;
+
+    // Double Gauss 50mm f/2, US patent 2,673,491
+    const DGAUSS_50MM: &str = "
+        # radius  sep     n      aperture
+        29.475    3.76    1.67   25.2
+        84.83     0.12    1      25.2
+        19.275    4.025   1.67   23
+        40.77     3.275   1.699  23
+        12.75     5.705   1      18
+        0         4.5     0      17.1
+        -14.495   1.18    1.603  17
+        40.77     6.065   1.658  20
+        -20.385   0.19    1      20
+        437.065   3.22    1.717  20
+        -39.73    0       1      20
+    ";
+
+    #[test]
+    fn autofocus_converges() {
+        let elements = lens::read_lens(Cursor::new(DGAUSS_50MM), Path::new("dgauss.dat")).unwrap();
+        let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let film = Film::new(Point2i::new(64, 64), &full_window, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+        let camera = RealisticCamera::new(
+            AnimatedTransform::fixed(Transform::identity()),
+            0.0,
+            1.0,
+            17.1,
+            2.0,
+            true,
+            &elements,
+            &film,
+        ).unwrap();
+
+        // Rays from the film center through different parts of the lens meet
+        // on the optical axis at the focus distance
+        let center = Point2f::new(32.0, 32.0);
+        for &(u, v) in [(0.5, 0.5), (0.2, 0.5), (0.8, 0.5), (0.5, 0.2), (0.7, 0.7)].iter() {
+            let (weight, r) = camera.generate_ray(&CameraSample::new(center, Point2f::new(u, v), 0.0)).unwrap();
+            assert!(weight > 0.0);
+            let p = r.origin + r.direction * ((2.0 - r.origin.z) / r.direction.z);
+            assert!((p.x * p.x + p.y * p.y).sqrt() < 1e-3);
+        }
+    }
+}
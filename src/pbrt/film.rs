@@ -0,0 +1,305 @@
+use std::error;
+use std::path::Path;
+
+use pbrt::Spectrum;
+use pbrt::filter::Filter;
+use pbrt::geometry::Bounds2f;
+use pbrt::geometry::Bounds2i;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::io::exr::ExrImage;
+use pbrt::io::exr::PixelType;
+use pbrt::io::imageio;
+use pbrt::stats::VarianceEstimator;
+use pbrt::tonemap::ToneMapping;
+
+
+// Resolution of the tabulated filter over one quadrant of its support.
+const FILTER_TABLE_WIDTH: usize = 16;
+
+// Running sums for one pixel; the pixel value is rgb / filter_weight_sum.
+#[derive(Clone, Debug)]
+struct Pixel {
+    rgb: Spectrum,
+    filter_weight_sum: f64,
+    // Luminance of every camera sample the sampler took for this pixel,
+    // before filtering, whether or not it produced a ray
+    sample_stats: VarianceEstimator,
+}
+
+// Accumulates radiance samples in linear floating point. Values are only
+// resolved and quantized for display when the image is written.
+pub struct Film {
+    pub full_resolution: Point2i,
+    // Physical length of the film diagonal, in meters.
+    pub diagonal: f64,
+    // The pixels actually stored and written, from the crop window.
+    pub cropped_pixel_bounds: Bounds2i,
+    pub filter: Box<dyn Filter>,
+    // View transform for 8-bit output.
+    pub tone_mapping: ToneMapping,
+    // The filter sampled over its positive quadrant, relying on symmetry.
+    filter_table: Vec<f64>,
+    pixels: Vec<Pixel>,
+}
+
+impl Film {
+    // `crop_window` is in NDC, [0, 1]^2 covering the whole image. `diagonal`
+    // is given in millimeters, as on a real camera back.
+    pub fn new(full_resolution: Point2i, crop_window: &Bounds2f, filter: Box<dyn Filter>, diagonal: f64) -> Film {
+        let res = Point2f::new(full_resolution.x as f64, full_resolution.y as f64);
+        let cropped_pixel_bounds = Bounds2i::new(
+            Point2i::new((res.x * crop_window.min.x).ceil() as i64, (res.y * crop_window.min.y).ceil() as i64),
+            Point2i::new((res.x * crop_window.max.x).ceil() as i64, (res.y * crop_window.max.y).ceil() as i64),
+        );
+
+        let n_pixels = cropped_pixel_bounds.area().max(0) as usize;
+        let pixels = vec![Pixel { rgb: Spectrum::new(0.0), filter_weight_sum: 0.0, sample_stats: VarianceEstimator::default() }; n_pixels];
+
+        let radius = filter.radius();
+        let mut filter_table = Vec::with_capacity(FILTER_TABLE_WIDTH * FILTER_TABLE_WIDTH);
+        for y in 0..FILTER_TABLE_WIDTH {
+            for x in 0..FILTER_TABLE_WIDTH {
+                let p = Point2f::new(
+                    (x as f64 + 0.5) * radius.x / FILTER_TABLE_WIDTH as f64,
+                    (y as f64 + 0.5) * radius.y / FILTER_TABLE_WIDTH as f64,
+                );
+                filter_table.push(filter.evaluate(&p));
+            }
+        }
+
+        Film {
+            full_resolution,
+            diagonal: diagonal * 0.001,
+            cropped_pixel_bounds,
+            filter,
+            tone_mapping: ToneMapping::default(),
+            filter_table,
+            pixels,
+        }
+    }
+
+    // Raster area that samples must cover so every cropped pixel gets the
+    // full support of the filter.
+    pub fn sample_bounds(&self) -> Bounds2i {
+        let radius = self.filter.radius();
+        let b = &self.cropped_pixel_bounds;
+        return Bounds2i::new(
+            Point2i::new(
+                (b.min.x as f64 + 0.5 - radius.x).floor() as i64,
+                (b.min.y as f64 + 0.5 - radius.y).floor() as i64,
+            ),
+            Point2i::new(
+                (b.max.x as f64 - 0.5 + radius.x).ceil() as i64,
+                (b.max.y as f64 - 0.5 + radius.y).ceil() as i64,
+            ),
+        );
+    }
+
+    // Extent of the film in meters, centered on the optical axis.
+    pub fn physical_extent(&self) -> Bounds2f {
+        let aspect = self.full_resolution.y as f64 / self.full_resolution.x as f64;
+        let x = (self.diagonal * self.diagonal / (1.0 + aspect * aspect)).sqrt();
+        let y = aspect * x;
+        return Bounds2f::new(Point2f::new(-x / 2.0, -y / 2.0), Point2f::new(x / 2.0, y / 2.0));
+    }
+
+    fn pixel_offset(&self, p: &Point2i) -> Option<usize> {
+        let b = &self.cropped_pixel_bounds;
+        if p.x < b.min.x || p.x >= b.max.x || p.y < b.min.y || p.y >= b.max.y {
+            return None;
+        }
+        let width = b.max.x - b.min.x;
+        return Some(((p.y - b.min.y) * width + (p.x - b.min.x)) as usize);
+    }
+
+    // Splats radiance `l` arriving at raster position `p_film`, scaled by the
+    // camera's `sample_weight`, into every pixel within the filter radius.
+    pub fn add_sample(&mut self, p_film: &Point2f, l: &Spectrum, sample_weight: f64) {
+        // Pixel centers sit at half-integer raster coordinates
+        let radius = self.filter.radius();
+        let discrete = Point2f::new(p_film.x - 0.5, p_film.y - 0.5);
+        let b = self.cropped_pixel_bounds;
+        let x0 = ((discrete.x - radius.x).ceil() as i64).max(b.min.x);
+        let y0 = ((discrete.y - radius.y).ceil() as i64).max(b.min.y);
+        let x1 = ((discrete.x + radius.x).floor() as i64 + 1).min(b.max.x);
+        let y1 = ((discrete.y + radius.y).floor() as i64 + 1).min(b.max.y);
+
+        let table_index = |d: f64, r: f64| {
+            let i = (d.abs() / r * FILTER_TABLE_WIDTH as f64).floor() as usize;
+            return i.min(FILTER_TABLE_WIDTH - 1);
+        };
+
+        for y in y0..y1 {
+            let ify = table_index(y as f64 - discrete.y, radius.y);
+            for x in x0..x1 {
+                let ifx = table_index(x as f64 - discrete.x, radius.x);
+                let filter_weight = self.filter_table[ify * FILTER_TABLE_WIDTH + ifx];
+
+                if let Some(offset) = self.pixel_offset(&Point2i::new(x, y)) {
+                    let pixel = &mut self.pixels[offset];
+                    pixel.rgb += l.clone() * (sample_weight * filter_weight);
+                    pixel.filter_weight_sum += filter_weight;
+                }
+            }
+        }
+    }
+
+    // The resolved linear RGB value of pixel `p`, black if it has no samples.
+    pub fn pixel_rgb(&self, p: &Point2i) -> Spectrum {
+        let pixel = match self.pixel_offset(p) {
+            Some(offset) => &self.pixels[offset],
+            None => return Spectrum::new(0.0),
+        };
+        if pixel.filter_weight_sum == 0.0 {
+            return Spectrum::new(0.0);
+        }
+
+        // Negative filter lobes can push sums below zero
+        let mut rgb = pixel.rgb.clone() / pixel.filter_weight_sum;
+        for v in rgb.samples.iter_mut() {
+            *v = v.max(0.0);
+        }
+        return rgb;
+    }
+
+    // Keeps the sampler's statistics for pixel `p` once it is done. Pixels
+    // outside the crop window are ignored.
+    pub fn record_sample_stats(&mut self, p: &Point2i, stats: &VarianceEstimator) {
+        if let Some(offset) = self.pixel_offset(p) {
+            self.pixels[offset].sample_stats = *stats;
+        }
+    }
+
+    // Adds the samples taken in each cropped pixel and their luminance mean
+    // and variance to `image`, as "samples", "mean" and "variance" channels.
+    pub fn add_sample_stats(&self, image: &mut ExrImage) -> Result<(), String> {
+        let channel = |f: &dyn Fn(&VarianceEstimator) -> f64| -> Vec<f32> {
+            return self.pixels.iter().map(|p| f(&p.sample_stats) as f32).collect();
+        };
+        image.add_channel("samples", PixelType::Float, channel(&|s| s.count() as f64))?;
+        image.add_channel("mean", PixelType::Float, channel(&|s| s.mean()))?;
+        image.add_channel("variance", PixelType::Float, channel(&|s| s.variance()))?;
+        return Ok(());
+    }
+
+    // The cropped image, top row first.
+    fn cropped_rgb(&self) -> Vec<Spectrum> {
+        let b = self.cropped_pixel_bounds;
+        let mut rgb = Vec::with_capacity(b.area().max(0) as usize);
+        for y in b.min.y..b.max.y {
+            for x in b.min.x..b.max.x {
+                rgb.push(self.pixel_rgb(&Point2i::new(x, y)));
+            }
+        }
+        return rgb;
+    }
+
+    fn cropped_resolution(&self) -> Point2i {
+        let b = self.cropped_pixel_bounds;
+        return Point2i::new(b.max.x - b.min.x, b.max.y - b.min.y);
+    }
+
+    // The cropped image as linear half-float R, G and B channels, to which
+    // callers may add AOVs before saving.
+    pub fn exr_image(&self) -> Result<ExrImage, String> {
+        return imageio::exr_from_rgb(&self.cropped_rgb(), &self.cropped_resolution());
+    }
+
+    // Writes the cropped image, in a format chosen by the path's extension.
+    pub fn write_image<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn error::Error>> {
+        return imageio::write_image(path, &self.cropped_rgb(), &self.cropped_resolution(), &self.tone_mapping);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::Spectrum;
+    use pbrt::film::Film;
+    use pbrt::filter::BoxFilter;
+    use pbrt::filter::GaussianFilter;
+    use pbrt::filter::TriangleFilter;
+    use pbrt::geometry::Bounds2f;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point2i;
+    use pbrt::geometry::Vector2f;
+    use pbrt::stats::VarianceEstimator;
+
+    #[test]
+    fn crops_and_averages() {
+        let crop = Bounds2f::new(Point2f::new(0.25, 0.5), Point2f::new(0.75, 1.0));
+        let mut film = Film::new(Point2i::new(8, 4), &crop, Box::new(BoxFilter::new(Vector2f::new(0.5, 0.5))), 35.0);
+        assert!(film.cropped_pixel_bounds.min == Point2i::new(2, 2));
+        assert!(film.cropped_pixel_bounds.max == Point2i::new(6, 4));
+
+        film.add_sample(&Point2f::new(3.2, 2.7), &Spectrum::from_rgb(1.0, 2.0, 4.0), 1.0);
+        film.add_sample(&Point2f::new(3.9, 2.1), &Spectrum::from_rgb(3.0, 0.0, 0.0), 1.0);
+        // Outside the crop window
+        film.add_sample(&Point2f::new(0.5, 0.5), &Spectrum::new(1.0), 1.0);
+
+        assert!(film.pixel_rgb(&Point2i::new(3, 2)) == Spectrum::from_rgb(2.0, 1.0, 2.0));
+        assert!(film.pixel_rgb(&Point2i::new(4, 2)) == Spectrum::new(0.0));
+        assert!(film.pixel_rgb(&Point2i::new(0, 0)) == Spectrum::new(0.0));
+
+        // Statistics are kept for cropped pixels, row by row
+        let stats = |values: &[f64]| {
+            let mut stats = VarianceEstimator::default();
+            for &v in values.iter() {
+                stats.add(v);
+            }
+            return stats;
+        };
+        film.record_sample_stats(&Point2i::new(3, 2), &stats(&[1.0, 2.0, 3.0]));
+        film.record_sample_stats(&Point2i::new(5, 3), &stats(&[4.0; 4]));
+        film.record_sample_stats(&Point2i::new(0, 0), &stats(&[5.0; 8]));
+        let mut image = film.exr_image().unwrap();
+        film.add_sample_stats(&mut image).unwrap();
+        assert_eq!(image.channel("samples").unwrap().data, [0.0, 3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0]);
+        assert_eq!(image.channel("mean").unwrap().data, [0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0]);
+        assert_eq!(image.channel("variance").unwrap().data, [0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn splats_tabulated_filter_weights() {
+        let full_window = Bounds2f::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let mut film = Film::new(Point2i::new(8, 8), &full_window, Box::new(TriangleFilter::new(Vector2f::new(1.0, 1.0))), 35.0);
+        film.add_sample(&Point2f::new(3.0, 2.5), &Spectrum::new(2.0), 1.0);
+
+        // The table holds the filter at the middle of each of its 16 cells
+        // per unit of radius: offsets of 0, 0.5 and 1 pixel read 1/32, 17/32
+        // and 31/32 of the way out
+        let weight = |d: f64| 1.0 - d / 32.0;
+        for y in 0..8 {
+            for x in 0..8 {
+                let wx = match x { 2 | 3 => weight(17.0), _ => 0.0 };
+                let wy = match y { 2 => weight(1.0), 1 | 3 => weight(31.0), _ => 0.0 };
+                let pixel = &film.pixels[film.pixel_offset(&Point2i::new(x, y)).unwrap()];
+                assert_eq!(pixel.filter_weight_sum, wx * wy);
+                assert!(pixel.rgb == Spectrum::new(2.0 * wx * wy));
+            }
+        }
+    }
+
+    #[test]
+    fn sample_bounds_cover_the_filter() {
+        let crop = Bounds2f::new(Point2f::new(0.25, 0.5), Point2f::new(0.75, 1.0));
+        let filter = || Box::new(GaussianFilter::new(Vector2f::new(2.0, 1.5), 2.0));
+        let bounds = Film::new(Point2i::new(8, 4), &crop, filter(), 35.0).sample_bounds();
+        assert!(bounds.min == Point2i::new(0, 1) && bounds.max == Point2i::new(8, 5));
+
+        // Any sample that reaches a cropped pixel lies within the sample
+        // bounds; the filter's support ends exactly on their far edges
+        for i in 0..12 * 16 {
+            for j in 0..10 * 16 {
+                let p = Point2f::new(i as f64 / 16.0 - 2.0, j as f64 / 16.0 - 2.0);
+                let mut film = Film::new(Point2i::new(8, 4), &crop, filter(), 35.0);
+                film.add_sample(&p, &Spectrum::new(1.0), 1.0);
+                if film.pixels.iter().any(|pixel| pixel.filter_weight_sum > 0.0) {
+                    let (min, max) = (bounds.min, bounds.max);
+                    assert!(p.x >= min.x as f64 && p.x <= max.x as f64 && p.y >= min.y as f64 && p.y <= max.y as f64);
+                }
+            }
+        }
+    }
+}
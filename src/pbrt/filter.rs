@@ -0,0 +1,197 @@
+use std::f64;
+
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Vector2f;
+
+
+// Pixel reconstruction filter, centered on the origin and zero outside
+// [-radius, radius] in each dimension.
+pub trait Filter {
+    fn radius(&self) -> Vector2f;
+
+    fn evaluate(&self, p: &Point2f) -> f64;
+}
+
+
+pub struct BoxFilter {
+    radius: Vector2f,
+}
+
+impl BoxFilter {
+    pub fn new(radius: Vector2f) -> BoxFilter {
+        BoxFilter { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> Vector2f {
+        return self.radius;
+    }
+
+    fn evaluate(&self, _p: &Point2f) -> f64 {
+        return 1.0;
+    }
+}
+
+
+pub struct TriangleFilter {
+    radius: Vector2f,
+}
+
+impl TriangleFilter {
+    pub fn new(radius: Vector2f) -> TriangleFilter {
+        TriangleFilter { radius }
+    }
+}
+
+impl Filter for TriangleFilter {
+    fn radius(&self) -> Vector2f {
+        return self.radius;
+    }
+
+    fn evaluate(&self, p: &Point2f) -> f64 {
+        return (self.radius.x - p.x.abs()).max(0.0) * (self.radius.y - p.y.abs()).max(0.0);
+    }
+}
+
+
+// Gaussian falloff exp(-alpha * d^2), shifted down so it reaches zero at
+// the radius.
+pub struct GaussianFilter {
+    radius: Vector2f,
+    alpha: f64,
+    exp_x: f64,
+    exp_y: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: Vector2f, alpha: f64) -> GaussianFilter {
+        GaussianFilter {
+            radius,
+            alpha,
+            exp_x: (-alpha * radius.x * radius.x).exp(),
+            exp_y: (-alpha * radius.y * radius.y).exp(),
+        }
+    }
+
+    fn gaussian(&self, d: f64, expv: f64) -> f64 {
+        return ((-self.alpha * d * d).exp() - expv).max(0.0);
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> Vector2f {
+        return self.radius;
+    }
+
+    fn evaluate(&self, p: &Point2f) -> f64 {
+        return self.gaussian(p.x, self.exp_x) * self.gaussian(p.y, self.exp_y);
+    }
+}
+
+
+// Mitchell-Netravali cubic. B = C = 1/3 is the usual compromise between
+// ringing and blurring.
+pub struct MitchellFilter {
+    radius: Vector2f,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: Vector2f, b: f64, c: f64) -> MitchellFilter {
+        MitchellFilter { radius, b, c }
+    }
+
+    // The cubic over [-1, 1]
+    fn mitchell_1d(&self, x: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        let x = (2.0 * x).abs();
+        if x > 1.0 {
+            return ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)) * (1.0 / 6.0);
+        }
+        return ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b))
+            * (1.0 / 6.0);
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> Vector2f {
+        return self.radius;
+    }
+
+    fn evaluate(&self, p: &Point2f) -> f64 {
+        return self.mitchell_1d(p.x / self.radius.x) * self.mitchell_1d(p.y / self.radius.y);
+    }
+}
+
+
+// Sinc windowed by a wider sinc with `tau` lobes.
+pub struct LanczosSincFilter {
+    radius: Vector2f,
+    tau: f64,
+}
+
+impl LanczosSincFilter {
+    pub fn new(radius: Vector2f, tau: f64) -> LanczosSincFilter {
+        LanczosSincFilter { radius, tau }
+    }
+
+    fn windowed_sinc(&self, x: f64, radius: f64) -> f64 {
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+        return sinc(x) * sinc(x / self.tau);
+    }
+}
+
+impl Filter for LanczosSincFilter {
+    fn radius(&self) -> Vector2f {
+        return self.radius;
+    }
+
+    fn evaluate(&self, p: &Point2f) -> f64 {
+        return self.windowed_sinc(p.x, self.radius.x) * self.windowed_sinc(p.y, self.radius.y);
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    let x = x.abs();
+    if x < 1e-5 {
+        return 1.0;
+    }
+    return (f64::consts::PI * x).sin() / (f64::consts::PI * x);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::filter::Filter;
+    use pbrt::filter::GaussianFilter;
+    use pbrt::filter::LanczosSincFilter;
+    use pbrt::filter::MitchellFilter;
+    use pbrt::filter::TriangleFilter;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Vector2f;
+
+    #[test]
+    fn filters_vanish_at_radius() {
+        let radius = Vector2f::new(2.0, 1.0);
+        let filters: Vec<Box<dyn Filter>> = vec![
+            Box::new(TriangleFilter::new(radius)),
+            Box::new(GaussianFilter::new(radius, 2.0)),
+            Box::new(MitchellFilter::new(radius, 1.0 / 3.0, 1.0 / 3.0)),
+            Box::new(LanczosSincFilter::new(radius, 3.0)),
+        ];
+        for filter in filters.iter() {
+            assert!(filter.evaluate(&Point2f::new(0.0, 0.0)) > 0.0);
+            assert!(filter.evaluate(&Point2f::new(2.0, 0.0)).abs() < 1e-9);
+            assert!(filter.evaluate(&Point2f::new(0.3, -1.0)).abs() < 1e-9);
+            // Symmetric in each axis
+            let a = filter.evaluate(&Point2f::new(0.7, 0.4));
+            assert!((a - filter.evaluate(&Point2f::new(-0.7, -0.4))).abs() < 1e-12);
+        }
+    }
+}
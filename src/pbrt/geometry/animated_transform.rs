@@ -0,0 +1,313 @@
+use std::f64;
+
+use pbrt;
+use pbrt::geometry::Bounds3f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::quaternion::Quaternion;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::ray::RayDifferential;
+use pbrt::geometry::transform::Matrix4x4;
+use pbrt::geometry::transform::Transform;
+
+
+// Interpolates between two keyframe transforms by decomposing each into
+// translation, rotation and scale, so rotations stay rigid between frames.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimatedTransform {
+    start_transform: Transform,
+    end_transform: Transform,
+    start_time: f64,
+    end_time: f64,
+    actually_animated: bool,
+    has_rotation: bool,
+    t: [Vector3f; 2],
+    r: [Quaternion; 2],
+    s: [Matrix4x4; 2],
+}
+
+impl AnimatedTransform {
+    pub fn new(start_transform: Transform, start_time: f64, end_transform: Transform, end_time: f64) -> AnimatedTransform {
+        let actually_animated = start_transform != end_transform;
+        let (t0, r0, s0) = decompose(&start_transform.m);
+        let (t1, mut r1, s1) = decompose(&end_transform.m);
+
+        // Flip the second rotation if needed to take the shortest path
+        if r0.dot(&r1) < 0.0 {
+            r1 = -r1;
+        }
+        let has_rotation = r0.dot(&r1) < 0.9995;
+
+        AnimatedTransform {
+            start_transform,
+            end_transform,
+            start_time,
+            end_time,
+            actually_animated,
+            has_rotation,
+            t: [t0, t1],
+            r: [r0, r1],
+            s: [s0, s1],
+        }
+    }
+
+    // A transform that does not change over time.
+    pub fn fixed(transform: Transform) -> AnimatedTransform {
+        return AnimatedTransform::new(transform, 0.0, transform, 1.0);
+    }
+
+    pub fn is_animated(&self) -> bool {
+        return self.actually_animated;
+    }
+
+    pub fn has_scale(&self) -> bool {
+        return self.start_transform.has_scale() || self.end_transform.has_scale();
+    }
+
+    pub fn interpolate(&self, time: f64) -> Transform {
+        if !self.actually_animated || time <= self.start_time {
+            return self.start_transform;
+        }
+        if time >= self.end_time {
+            return self.end_transform;
+        }
+
+        let dt = (time - self.start_time) / (self.end_time - self.start_time);
+        let trans = self.t[0] * (1.0 - dt) + self.t[1] * dt;
+        let rotate = Quaternion::slerp(dt, &self.r[0], &self.r[1]);
+
+        let mut scale = Matrix4x4::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                scale.m[i][j] = pbrt::lerp(dt, self.s[0].m[i][j], self.s[1].m[i][j]);
+            }
+        }
+
+        // Translations and rotations invert exactly, leaving only the scale's
+        // 3x3 to invert; a singular one gives an infinite inverse rather
+        // than failing, as Transform::scale(0.0, ...) does
+        let scale = Transform::from_matrices(scale, inverse_linear(&scale));
+        return Transform::translate(&trans) * rotate.to_transform() * scale;
+    }
+
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        return self.interpolate(r.time).transform_ray(r);
+    }
+
+    pub fn transform_ray_differential(&self, r: &RayDifferential) -> RayDifferential {
+        return self.interpolate(r.time).transform_ray_differential(r);
+    }
+
+    pub fn transform_point(&self, time: f64, p: &Point3f) -> Point3f {
+        return self.interpolate(time).transform_point(p);
+    }
+
+    pub fn transform_vector(&self, time: f64, v: &Vector3f) -> Vector3f {
+        return self.interpolate(time).transform_vector(v);
+    }
+
+    // Bounds of `b` swept over the whole time range.
+    pub fn motion_bounds(&self, b: &Bounds3f) -> Bounds3f {
+        if !self.actually_animated {
+            return self.start_transform.transform_bounds(b);
+        }
+        if !self.has_rotation {
+            // Translation and scale interpolate linearly, so the endpoints bound the motion
+            return self.start_transform.transform_bounds(b).union(&self.end_transform.transform_bounds(b));
+        }
+
+        let mut bounds = self.bound_point_motion(&b.corner(0));
+        for corner in 1..8 {
+            bounds = bounds.union(&self.bound_point_motion(&b.corner(corner)));
+        }
+        return bounds;
+    }
+
+    // Conservative bounds on the path of a single point. The interpolated
+    // transform is T(t) + R(t) S(t) p, where S(t) p moves on a segment and R(t)
+    // rotates about a fixed axis, so the rotated point stays within the boxes
+    // swept by rotating each segment endpoint; the translation is added last.
+    pub fn bound_point_motion(&self, p: &Point3f) -> Bounds3f {
+        let a = apply_linear(&self.s[0], p);
+        let b = apply_linear(&self.s[1], p);
+
+        // R(t) = R0 * rotation about `axis` by theta * t
+        let delta = self.r[0].conjugate() * self.r[1];
+        let sin_half = delta.v.length();
+        let theta = 2.0 * sin_half.atan2(delta.w);
+        let mut axis = delta.v;
+        if sin_half > 0.0 {
+            axis /= sin_half;
+        }
+        let r0 = self.r[0].to_transform();
+
+        let arc = arc_bounds(&r0, &axis, theta, &a).union(&arc_bounds(&r0, &axis, theta, &b));
+        let t_min = Point3f::from(self.t[0]).min(&Point3f::from(self.t[1]));
+        let t_max = Point3f::from(self.t[0]).max(&Point3f::from(self.t[1]));
+
+        return Bounds3f {
+            min: arc.min + Vector3f::from(t_min),
+            max: arc.max + Vector3f::from(t_max),
+        };
+    }
+}
+
+
+// Splits a matrix into translation, rotation and scale (M = T R S) using
+// polar decomposition of the upper 3x3.
+fn decompose(m: &Matrix4x4) -> (Vector3f, Quaternion, Matrix4x4) {
+    let t = Vector3f::new(m.m[0][3], m.m[1][3], m.m[2][3]);
+
+    let mut mm = *m;
+    for i in 0..3 {
+        mm.m[i][3] = 0.0;
+        mm.m[3][i] = 0.0;
+    }
+    mm.m[3][3] = 1.0;
+
+    // Average R with its inverse transpose until it converges to a rotation
+    let mut r = mm;
+    for _ in 0..100 {
+        let r_it = match r.transpose().inverse() {
+            Some(inv) => inv,
+            None => break,
+        };
+        let mut r_next = Matrix4x4::identity();
+        for i in 0..4 {
+            for j in 0..4 {
+                r_next.m[i][j] = 0.5 * (r.m[i][j] + r_it.m[i][j]);
+            }
+        }
+
+        let mut norm: f64 = 0.0;
+        for i in 0..3 {
+            let n = (0..3).map(|j| (r.m[i][j] - r_next.m[i][j]).abs()).sum();
+            norm = norm.max(n);
+        }
+        r = r_next;
+        if norm <= 0.0001 {
+            break;
+        }
+    }
+
+    let rquat = Quaternion::from_transform(&Transform::from_matrices(r, r.transpose()));
+    let s = r.inverse().unwrap_or_else(Matrix4x4::identity) * mm;
+    return (t, rquat, s);
+}
+
+// Inverse of the upper 3x3 of `m` by cofactors, as a 4x4 matrix.
+fn inverse_linear(m: &Matrix4x4) -> Matrix4x4 {
+    let a = &m.m;
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        return a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0];
+    };
+    let inv_det = 1.0 / (a[0][0] * cofactor(0, 0) + a[0][1] * cofactor(0, 1) + a[0][2] * cofactor(0, 2));
+    let mut inv = Matrix4x4::identity();
+    for i in 0..3 {
+        for j in 0..3 {
+            inv.m[i][j] = cofactor(j, i) * inv_det;
+        }
+    }
+    return inv;
+}
+
+fn apply_linear(m: &Matrix4x4, p: &Point3f) -> Vector3f {
+    return Vector3f::new(
+        m.m[0][0] * p.x + m.m[0][1] * p.y + m.m[0][2] * p.z,
+        m.m[1][0] * p.x + m.m[1][1] * p.y + m.m[1][2] * p.z,
+        m.m[2][0] * p.x + m.m[2][1] * p.y + m.m[2][2] * p.z,
+    );
+}
+
+// Bounds of r0 applied to `x` rotated about `axis` by angles in [0, theta].
+fn arc_bounds(r0: &Transform, axis: &Vector3f, theta: f64, x: &Vector3f) -> Bounds3f {
+    // Circle x(phi) = c + u cos(phi) + v sin(phi), taken into world orientation
+    let c = *axis * axis.dot(x);
+    let u = r0.transform_vector(&(*x - c));
+    let v = r0.transform_vector(&axis.cross(x));
+    let c = r0.transform_vector(&c);
+
+    let at = |phi: f64| Point3f::from(c + u * phi.cos() + v * phi.sin());
+    let mut bounds = Bounds3f::new(at(0.0), at(theta));
+
+    // Each coordinate is extremal where its derivative -u sin + v cos vanishes
+    let (lo, hi) = if theta < 0.0 { (theta, 0.0) } else { (0.0, theta) };
+    for i in 0..3 {
+        let phi0 = v[i].atan2(u[i]);
+        for k in -2..3 {
+            let phi = phi0 + k as f64 * f64::consts::PI;
+            if phi > lo && phi < hi {
+                bounds = bounds.union_point(&at(phi));
+            }
+        }
+    }
+    return bounds;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Bounds3f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::animated_transform::AnimatedTransform;
+    use pbrt::geometry::transform::Transform;
+
+    #[test]
+    fn interpolates_keyframes() {
+        let start = Transform::translate(&Vector3f::new(1.0, 2.0, 3.0)) * Transform::scale(2.0, 2.0, 2.0);
+        let end = Transform::translate(&Vector3f::new(-1.0, 0.0, 5.0)) * Transform::rotate_z(90.0);
+        let at = AnimatedTransform::new(start, 0.0, end, 1.0);
+
+        let p = Point3f::new(1.0, 0.0, 0.0);
+        let near = |a: Point3f, b: Point3f| (a - b).length() < 1e-6;
+        assert!(near(at.transform_point(0.0, &p), start.transform_point(&p)));
+        assert!(near(at.transform_point(1.0, &p), end.transform_point(&p)));
+
+        // Halfway through a 90 degree turn about z
+        let rot = AnimatedTransform::new(Transform::identity(), 0.0, Transform::rotate_z(90.0), 1.0);
+        let half = 0.5f64.sqrt();
+        assert!(near(rot.transform_point(0.5, &p), Point3f::new(half, half, 0.0)));
+    }
+
+    #[test]
+    fn interpolated_inverse_matches() {
+        let start = Transform::translate(&Vector3f::new(1.0, 2.0, 3.0)) * Transform::scale(2.0, 1.0, 0.5);
+        let end = Transform::rotate(60.0, &Vector3f::new(1.0, 2.0, 3.0)) * Transform::scale(1.0, 3.0, 1.0);
+        let at = AnimatedTransform::new(start, 0.0, end, 1.0);
+        let t = at.interpolate(0.3);
+        let product = t.m * t.m_inv;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product.m[i][j] - expected).abs() < 1e-9);
+            }
+        }
+
+        // Flattening keyframes have no inverse, but still move points
+        let flat = Transform::scale(0.0, 1.0, 1.0);
+        let at = AnimatedTransform::new(flat, 0.0, Transform::translate(&Vector3f::new(0.0, 4.0, 0.0)) * flat, 1.0);
+        let p = at.transform_point(0.5, &Point3f::new(3.0, 1.0, 1.0));
+        assert!((p - Point3f::new(0.0, 3.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn motion_bounds_contain_path() {
+        let start = Transform::translate(&Vector3f::new(0.0, 0.0, 0.0));
+        let end = Transform::translate(&Vector3f::new(3.0, 1.0, 0.0))
+            * Transform::rotate(120.0, &Vector3f::new(1.0, 1.0, 0.0))
+            * Transform::scale(1.5, 1.0, 0.5);
+        let at = AnimatedTransform::new(start, 0.0, end, 1.0);
+
+        let b = Bounds3f::new(Point3f::new(-1.0, 2.0, 0.5), Point3f::new(0.5, 3.0, 1.0));
+        let motion = at.motion_bounds(&b).expand(1e-9);
+        for step in 0..=64 {
+            let time = step as f64 / 64.0;
+            for corner in 0..8 {
+                assert!(motion.inside(&at.transform_point(time, &b.corner(corner))));
+            }
+        }
+    }
+}
@@ -1,21 +1,48 @@
 use std::cmp;
+use std::mem;
 use std::ops;
 
-use num_traits::{Float, One, Zero};
+use num_traits::{Float, Num};
 
 use pbrt;
+use pbrt::geometry::ray::Ray;
 use pbrt::geometry::point::Point2;
 use pbrt::geometry::point::Point3;
 use pbrt::geometry::vector::Vector2;
 use pbrt::geometry::vector::Vector3;
 
 #[derive(Clone, Copy, Debug)]
-pub struct Bounds2<T: Float> {
+pub struct Bounds2<T> {
     pub min: Point2<T>,
     pub max: Point2<T>,
 }
 
-impl<T: Float> Bounds2<T> {
+impl<T: Num + Copy + PartialOrd> Bounds2<T> {
+    pub fn new(p1: Point2<T>, p2: Point2<T>) -> Bounds2<T> {
+        let (min_x, max_x) = if p1.x < p2.x { (p1.x, p2.x) } else { (p2.x, p1.x) };
+        let (min_y, max_y) = if p1.y < p2.y { (p1.y, p2.y) } else { (p2.y, p1.y) };
+        Bounds2 { min: Point2::new(min_x, min_y), max: Point2::new(max_x, max_y) }
+    }
+
+    pub fn union_point(&self, p: &Point2<T>) -> Bounds2<T> {
+        let min_x = if p.x < self.min.x { p.x } else { self.min.x };
+        let min_y = if p.y < self.min.y { p.y } else { self.min.y };
+        let max_x = if p.x > self.max.x { p.x } else { self.max.x };
+        let max_y = if p.y > self.max.y { p.y } else { self.max.y };
+        Bounds2 { min: Point2::new(min_x, min_y), max: Point2::new(max_x, max_y) }
+    }
+
+    pub fn inside(&self, p: &Point2<T>) -> bool {
+        return p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y;
+    }
+
+    pub fn expand(&self, delta: T) -> Bounds2<T> {
+        return Bounds2 {
+            min: Point2::new(self.min.x - delta, self.min.y - delta),
+            max: Point2::new(self.max.x + delta, self.max.y + delta),
+        };
+    }
+
     pub fn diagonal(&self) -> Vector2<T> {
         return self.max - self.min;
     }
@@ -33,13 +60,6 @@ impl<T: Float> Bounds2<T> {
         return 1;
     }
 
-    pub fn lerp(&self, other: Point2<T>) -> Point2<T> {
-        return Point2::new(
-            pbrt::lerp(other.x, self.min.x, self.max.x),
-            pbrt::lerp(other.y, self.min.y, self.max.y),
-        );
-    }
-
     pub fn offset(&self, other: Point2<T>) -> Vector2<T> {
         let mut o = other - self.min;
         if self.max.x > self.min.x {
@@ -53,7 +73,16 @@ impl<T: Float> Bounds2<T> {
     }
 }
 
-impl<T: Float> ops::Index<u8> for Bounds2<T> {
+impl<T: Float> Bounds2<T> {
+    pub fn lerp(&self, other: Point2<T>) -> Point2<T> {
+        return Point2::new(
+            pbrt::lerp(other.x, self.min.x, self.max.x),
+            pbrt::lerp(other.y, self.min.y, self.max.y),
+        );
+    }
+}
+
+impl<T> ops::Index<u8> for Bounds2<T> {
     type Output = Point2<T>;
 
     fn index(&self, index: u8) -> &Point2<T> {
@@ -64,7 +93,7 @@ impl<T: Float> ops::Index<u8> for Bounds2<T> {
     }
 }
 
-impl<T: Float> cmp::PartialEq for Bounds2<T> {
+impl<T: PartialEq> cmp::PartialEq for Bounds2<T> {
     fn eq(&self, other: &Bounds2<T>) -> bool {
         return self.min == other.min && self.max == other.max;
     }
@@ -80,9 +109,68 @@ pub struct Bounds3<T: Float> {
 }
 
 impl<T: Float> Bounds3<T> {
+    pub fn new(p1: Point3<T>, p2: Point3<T>) -> Bounds3<T> {
+        Bounds3 { min: p1.min(&p2), max: p1.max(&p2) }
+    }
+
+    pub fn from_point(p: Point3<T>) -> Bounds3<T> {
+        Bounds3 { min: p, max: p }
+    }
+
+    pub fn union_point(&self, p: &Point3<T>) -> Bounds3<T> {
+        Bounds3 { min: self.min.min(p), max: self.max.max(p) }
+    }
+
+    pub fn union(&self, other: &Bounds3<T>) -> Bounds3<T> {
+        Bounds3 { min: self.min.min(&other.min), max: self.max.max(&other.max) }
+    }
+
+    // The overlapping region of two boxes. Disjoint boxes produce a degenerate
+    // result with min > max on some axis.
+    pub fn intersect(&self, other: &Bounds3<T>) -> Bounds3<T> {
+        Bounds3 { min: self.min.max(&other.min), max: self.max.min(&other.max) }
+    }
+
+    pub fn overlaps(&self, other: &Bounds3<T>) -> bool {
+        let x = self.max.x >= other.min.x && self.min.x <= other.max.x;
+        let y = self.max.y >= other.min.y && self.min.y <= other.max.y;
+        let z = self.max.z >= other.min.z && self.min.z <= other.max.z;
+        return x && y && z;
+    }
+
+    pub fn inside(&self, p: &Point3<T>) -> bool {
+        return p.x >= self.min.x && p.x <= self.max.x
+            && p.y >= self.min.y && p.y <= self.max.y
+            && p.z >= self.min.z && p.z <= self.max.z;
+    }
+
+    // Like `inside`, but points on the upper boundary are considered outside.
+    pub fn inside_exclusive(&self, p: &Point3<T>) -> bool {
+        return p.x >= self.min.x && p.x < self.max.x
+            && p.y >= self.min.y && p.y < self.max.y
+            && p.z >= self.min.z && p.z < self.max.z;
+    }
+
+    pub fn expand(&self, delta: T) -> Bounds3<T> {
+        let d = Vector3::new(delta, delta, delta);
+        return Bounds3 { min: self.min - d, max: self.max + d };
+    }
+
+    // Center and radius of a sphere enclosing the box.
+    pub fn bounding_sphere(&self) -> (Point3<T>, T) {
+        let two = T::one() + T::one();
+        let center = (self.min + self.max) / two;
+        let radius = if self.inside(&center) {
+            (self.max - center).length()
+        } else {
+            T::zero()
+        };
+        return (center, radius);
+    }
+
     pub fn corner(&self, corner: u8) -> Point3<T> {
         return Point3::new(
-            self[(corner & 1)].x,
+            self[corner & 1].x,
             self[if corner & 2 > 0 { 1 } else { 0 }].y,
             self[if corner & 4 > 0 { 1 } else { 0 }].z,
         );
@@ -104,10 +192,13 @@ impl<T: Float> Bounds3<T> {
 
     pub fn maximum_extent(&self) -> u8 {
         let d = self.diagonal();
-        if d.x > d.y {
+        if d.x > d.y && d.x > d.z {
             return 0;
         }
-        return 1;
+        if d.y > d.z {
+            return 1;
+        }
+        return 2;
     }
 
     pub fn lerp(&self, other: Point3<T>) -> Point3<T> {
@@ -126,11 +217,76 @@ impl<T: Float> Bounds3<T> {
         if self.max.y > self.min.y {
             o.y = o.y / (self.max.y - self.min.y);
         }
+        if self.max.z > self.min.z {
+            o.z = o.z / (self.max.z - self.min.z);
+        }
 
         return o;
     }
 }
 
+impl Bounds3<f64> {
+    // Slab test returning the parametric range of the ray inside the box.
+    pub fn intersect_p(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let mut t0 = 0.0;
+        let mut t1 = ray.t_max;
+        for i in 0..3 {
+            let inv_ray_dir = 1.0 / ray.direction[i];
+            let mut t_near = (self.min[i] - ray.origin[i]) * inv_ray_dir;
+            let mut t_far = (self.max[i] - ray.origin[i]) * inv_ray_dir;
+            if t_near > t_far {
+                mem::swap(&mut t_near, &mut t_far);
+            }
+
+            // Round the far distance up so rays grazing an edge still hit
+            t_far *= 1.0 + 2.0 * pbrt::gamma(3);
+
+            // Written so that NaN slabs (origin on a slab plane) are ignored
+            t0 = if t_near > t0 { t_near } else { t0 };
+            t1 = if t_far < t1 { t_far } else { t1 };
+            if t0 > t1 {
+                return None;
+            }
+        }
+        return Some((t0, t1));
+    }
+
+    // Slab test against a ray with precomputed reciprocal direction. The far
+    // distances are rounded up so hits on box edges are never missed.
+    pub fn intersect_p_inv(&self, ray: &Ray, inv_dir: &Vector3<f64>, dir_is_neg: &[u8; 3]) -> bool {
+        let gamma3 = 1.0 + 2.0 * pbrt::gamma(3);
+
+        let mut t_min = (self[dir_is_neg[0]].x - ray.origin.x) * inv_dir.x;
+        let mut t_max = (self[1 - dir_is_neg[0]].x - ray.origin.x) * inv_dir.x * gamma3;
+        let ty_min = (self[dir_is_neg[1]].y - ray.origin.y) * inv_dir.y;
+        let ty_max = (self[1 - dir_is_neg[1]].y - ray.origin.y) * inv_dir.y * gamma3;
+
+        if t_min > ty_max || ty_min > t_max {
+            return false;
+        }
+        if ty_min > t_min {
+            t_min = ty_min;
+        }
+        if ty_max < t_max {
+            t_max = ty_max;
+        }
+
+        let tz_min = (self[dir_is_neg[2]].z - ray.origin.z) * inv_dir.z;
+        let tz_max = (self[1 - dir_is_neg[2]].z - ray.origin.z) * inv_dir.z * gamma3;
+        if t_min > tz_max || tz_min > t_max {
+            return false;
+        }
+        if tz_min > t_min {
+            t_min = tz_min;
+        }
+        if tz_max < t_max {
+            t_max = tz_max;
+        }
+
+        return t_min < ray.t_max && t_max > 0.0;
+    }
+}
+
 impl<T: Float> ops::Index<u8> for Bounds3<T> {
     type Output = Point3<T>;
 
@@ -149,3 +305,123 @@ impl<T: Float> cmp::PartialEq for Bounds3<T> {
 }
 
 impl<T: Float> cmp::Eq for Bounds3<T> {}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Bounds3f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::ray::Ray;
+
+    fn unit_box() -> Bounds3f {
+        return Bounds3f::new(Point3f::new(0.0, 0.0, 0.0), Point3f::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn new_orders_corners() {
+        let b = Bounds3f::new(Point3f::new(1.0, -2.0, 3.0), Point3f::new(-1.0, 2.0, -3.0));
+        assert_eq!(b.min, Point3f::new(-1.0, -2.0, -3.0));
+        assert_eq!(b.max, Point3f::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn union_point() {
+        let b = unit_box().union_point(&Point3f::new(2.0, -1.0, 0.5));
+        assert_eq!(b.min, Point3f::new(0.0, -1.0, 0.0));
+        assert_eq!(b.max, Point3f::new(2.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn union_box() {
+        let other = Bounds3f::new(Point3f::new(-1.0, 0.5, 0.5), Point3f::new(0.5, 3.0, 0.75));
+        let b = unit_box().union(&other);
+        assert_eq!(b.min, Point3f::new(-1.0, 0.0, 0.0));
+        assert_eq!(b.max, Point3f::new(1.0, 3.0, 1.0));
+    }
+
+    #[test]
+    fn intersect() {
+        let other = Bounds3f::new(Point3f::new(0.5, -1.0, 0.25), Point3f::new(2.0, 0.5, 0.75));
+        let b = unit_box().intersect(&other);
+        assert_eq!(b.min, Point3f::new(0.5, 0.0, 0.25));
+        assert_eq!(b.max, Point3f::new(1.0, 0.5, 0.75));
+    }
+
+    #[test]
+    fn overlaps() {
+        let touching = Bounds3f::new(Point3f::new(1.0, 1.0, 1.0), Point3f::new(2.0, 2.0, 2.0));
+        let apart = Bounds3f::new(Point3f::new(0.0, 0.0, 1.5), Point3f::new(1.0, 1.0, 2.0));
+        assert!(unit_box().overlaps(&touching));
+        assert!(!unit_box().overlaps(&apart));
+    }
+
+    #[test]
+    fn inside() {
+        let b = unit_box();
+        assert!(b.inside(&Point3f::new(0.5, 0.5, 0.5)));
+        assert!(b.inside(&Point3f::new(1.0, 1.0, 1.0)));
+        assert!(!b.inside(&Point3f::new(0.5, 0.5, 1.5)));
+    }
+
+    #[test]
+    fn inside_exclusive() {
+        let b = unit_box();
+        assert!(b.inside_exclusive(&Point3f::new(0.0, 0.0, 0.0)));
+        assert!(!b.inside_exclusive(&Point3f::new(0.5, 0.5, 1.0)));
+    }
+
+    #[test]
+    fn expand() {
+        let b = unit_box().expand(0.5);
+        assert_eq!(b.min, Point3f::new(-0.5, -0.5, -0.5));
+        assert_eq!(b.max, Point3f::new(1.5, 1.5, 1.5));
+    }
+
+    #[test]
+    fn bounding_sphere() {
+        let (center, radius) = unit_box().bounding_sphere();
+        assert_eq!(center, Point3f::new(0.5, 0.5, 0.5));
+        assert!((radius - 0.75f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn maximum_extent_considers_z() {
+        let b = Bounds3f::new(Point3f::new(0.0, 0.0, 0.0), Point3f::new(1.0, 2.0, 3.0));
+        assert_eq!(b.maximum_extent(), 2);
+        let b = Bounds3f::new(Point3f::new(0.0, 0.0, 0.0), Point3f::new(3.0, 2.0, 1.0));
+        assert_eq!(b.maximum_extent(), 0);
+    }
+
+    #[test]
+    fn offset_considers_z() {
+        let b = Bounds3f::new(Point3f::new(0.0, 0.0, 0.0), Point3f::new(2.0, 4.0, 8.0));
+        assert_eq!(b.offset(Point3f::new(1.0, 1.0, 2.0)), Vector3f::new(0.5, 0.25, 0.25));
+    }
+
+    #[test]
+    fn ray_intersect_p() {
+        let ray = Ray::new(Point3f::new(0.5, 0.5, -1.0), Vector3f::new(0.0, 0.0, 1.0), f64::MAX, 0.0);
+        let (t0, t1) = unit_box().intersect_p(&ray).unwrap();
+        assert!((t0 - 1.0).abs() < 1e-12);
+        assert!((t1 - 2.0).abs() < 1e-12);
+
+        let miss = Ray::new(Point3f::new(2.0, 0.5, -1.0), Vector3f::new(0.0, 0.0, 1.0), f64::MAX, 0.0);
+        assert!(unit_box().intersect_p(&miss).is_none());
+
+        let short = Ray::new(Point3f::new(0.5, 0.5, -1.0), Vector3f::new(0.0, 0.0, 1.0), 0.5, 0.0);
+        assert!(unit_box().intersect_p(&short).is_none());
+    }
+
+    #[test]
+    fn ray_intersect_p_inv() {
+        let b = unit_box();
+        let d = Vector3f::new(1.0, 1.0, -1.0);
+        let ray = Ray::new(Point3f::new(-1.0, -1.0, 2.0), d, f64::MAX, 0.0);
+        let inv_dir = Vector3f::new(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+        assert!(b.intersect_p_inv(&ray, &inv_dir, &[0, 0, 1]));
+
+        let behind = Ray::new(Point3f::new(2.0, 2.0, -1.0), d, f64::MAX, 0.0);
+        assert!(!b.intersect_p_inv(&behind, &inv_dir, &[0, 0, 1]));
+    }
+}
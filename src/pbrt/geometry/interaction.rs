@@ -0,0 +1,84 @@
+use pbrt::geometry::Normal3f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::normal::Normal3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Interaction {
+    pub p: Point3f,
+    pub n: Normal3f,
+    pub wo: Vector3f,
+    pub time: f64,
+}
+
+impl Interaction {
+    pub fn new(p: Point3f, n: Normal3f, wo: Vector3f, time: f64) -> Interaction {
+        Interaction { p, n, wo, time }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Shading {
+    pub n: Normal3f,
+    pub dpdu: Vector3f,
+    pub dpdv: Vector3f,
+    pub dndu: Normal3f,
+    pub dndv: Normal3f,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceInteraction {
+    pub p: Point3f,
+    pub n: Normal3f,
+    pub uv: Point2f,
+    pub dpdu: Vector3f,
+    pub dpdv: Vector3f,
+    pub dndu: Normal3f,
+    pub dndv: Normal3f,
+    pub wo: Vector3f,
+    pub time: f64,
+    pub shading: Shading,
+}
+
+impl SurfaceInteraction {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        p: Point3f,
+        uv: Point2f,
+        wo: Vector3f,
+        dpdu: Vector3f,
+        dpdv: Vector3f,
+        dndu: Normal3f,
+        dndv: Normal3f,
+        time: f64,
+    ) -> SurfaceInteraction {
+        let mut n = Normal3::from(dpdu.cross(&dpdv));
+        n.normalize();
+
+        let shading = Shading { n, dpdu, dpdv, dndu, dndv };
+        SurfaceInteraction { p, n, uv, dpdu, dpdv, dndu, dndv, wo, time, shading }
+    }
+
+    // Installs perturbed shading geometry (e.g. from interpolated vertex normals).
+    // When `orientation_is_authoritative` the geometric normal is flipped to agree
+    // with the shading normal, otherwise the shading normal follows the geometric one.
+    pub fn set_shading_geometry(
+        &mut self,
+        dpdus: Vector3f,
+        dpdvs: Vector3f,
+        dndus: Normal3f,
+        dndvs: Normal3f,
+        orientation_is_authoritative: bool,
+    ) {
+        let mut n = Normal3::from(dpdus.cross(&dpdvs));
+        n.normalize();
+        if orientation_is_authoritative {
+            self.n = self.n.face_forward(&n);
+        } else {
+            n = n.face_forward(&self.n);
+        }
+
+        self.shading = Shading { n, dpdu: dpdus, dpdv: dpdvs, dndu: dndus, dndv: dndvs };
+    }
+}
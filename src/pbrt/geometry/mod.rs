@@ -1,16 +1,28 @@
+pub mod animated_transform;
 pub mod bounds;
+pub mod interaction;
 pub mod normal;
 pub mod point;
+pub mod quaternion;
 pub mod ray;
+pub mod shape;
 pub mod sphere;
+pub mod transform;
+pub mod triangle;
 pub mod vector;
 
 
+pub type Bounds2f = bounds::Bounds2<f64>;
+pub type Bounds2i = bounds::Bounds2<i64>;
+pub type Bounds3f = bounds::Bounds3<f64>;
+
 pub type Point2f = point::Point2<f64>;
 pub type Point2i = point::Point2<i64>;
 pub type Point3f = point::Point3<f64>;
 pub type Point3i = point::Point3<i64>;
 
+pub type Normal3f = normal::Normal3<f64>;
+
 pub type Vector2f = vector::Vector2<f64>;
 pub type Vector2i = vector::Vector2<i64>;
 pub type Vector3f = vector::Vector3<f64>;
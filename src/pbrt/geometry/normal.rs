@@ -3,6 +3,8 @@ use std::ops;
 
 use num_traits::{Float, One, Zero};
 
+use pbrt::geometry::vector::Vector3;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Normal3<T: Float> {
     pub x: T,
@@ -37,6 +39,28 @@ impl<T: Float> Normal3<T> {
     pub fn dot(&self, other: &Normal3<T>) -> T {
         return self.x * other.x + self.y * other.y + self.z * other.z;
     }
+
+    // Flips this normal so that it lies in the same hemisphere as `other`.
+    pub fn face_forward(&self, other: &Normal3<T>) -> Normal3<T> {
+        if self.dot(other) < T::zero() {
+            return -*self;
+        }
+        return *self;
+    }
+}
+
+impl<T: Float> From<Vector3<T>> for Normal3<T> {
+    fn from(v: Vector3<T>) -> Normal3<T> {
+        return Normal3::new(v.x, v.y, v.z);
+    }
+}
+
+impl<T: Float> ops::Neg for Normal3<T> {
+    type Output = Normal3<T>;
+
+    fn neg(self) -> Normal3<T> {
+        return Normal3::new(-self.x, -self.y, -self.z);
+    }
 }
 
 impl<T: Float> ops::Add<Normal3<T>> for Normal3<T> {
@@ -1,22 +1,24 @@
 use std::cmp;
 use std::ops;
 
-use num_traits::{Float, One, Zero};
+use num_traits::{Float, Num, One, Zero};
 
 use pbrt::geometry::vector::Vector2;
 use pbrt::geometry::vector::Vector3;
 
 #[derive(Clone, Copy, Debug)]
-pub struct Point2<T: Float> {
+pub struct Point2<T> {
     pub x: T,
     pub y: T,
 }
 
-impl<T: Float> Point2<T> {
+impl<T> Point2<T> {
     pub fn new(x: T, y: T) -> Point2<T> {
         Point2 { x, y }
     }
+}
 
+impl<T: Float> Point2<T> {
     pub fn length_squared(&self) -> T {
         return self.x * self.x + self.y * self.y;
     }
@@ -27,7 +29,7 @@ impl<T: Float> Point2<T> {
 }
 
 
-impl<T: Float> ops::Add<Point2<T>> for Point2<T> {
+impl<T: Num + Copy> ops::Add<Point2<T>> for Point2<T> {
     type Output = Point2<T>;
 
     fn add(self, rhs: Point2<T>) -> Point2<T> {
@@ -38,7 +40,7 @@ impl<T: Float> ops::Add<Point2<T>> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::AddAssign<Point2<T>> for Point2<T> {
+impl<T: Num + Copy> ops::AddAssign<Point2<T>> for Point2<T> {
     fn add_assign(&mut self, other: Point2<T>) {
         *self = Point2 {
             x: self.x + other.x,
@@ -47,7 +49,7 @@ impl<T: Float> ops::AddAssign<Point2<T>> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::Sub<Point2<T>> for Point2<T> {
+impl<T: Num + Copy> ops::Sub<Point2<T>> for Point2<T> {
     type Output = Vector2<T>;
 
     fn sub(self, rhs: Point2<T>) -> Vector2<T> {
@@ -58,7 +60,7 @@ impl<T: Float> ops::Sub<Point2<T>> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::SubAssign<Point2<T>> for Point2<T> {
+impl<T: Num + Copy> ops::SubAssign<Point2<T>> for Point2<T> {
     fn sub_assign(&mut self, rhs: Point2<T>) {
         *self = Point2::new(
             self.x - rhs.x,
@@ -67,7 +69,7 @@ impl<T: Float> ops::SubAssign<Point2<T>> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::Mul<T> for Point2<T> {
+impl<T: Num + Copy> ops::Mul<T> for Point2<T> {
     type Output = Point2<T>;
 
     fn mul(self, rhs: T) -> Point2<T> {
@@ -78,7 +80,7 @@ impl<T: Float> ops::Mul<T> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::MulAssign<T> for Point2<T> {
+impl<T: Num + Copy> ops::MulAssign<T> for Point2<T> {
     fn mul_assign(&mut self, rhs: T) {
         *self = Point2::new(
             self.x * rhs,
@@ -87,7 +89,7 @@ impl<T: Float> ops::MulAssign<T> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::Div<T> for Point2<T> {
+impl<T: Num + Copy> ops::Div<T> for Point2<T> {
     type Output = Point2<T>;
 
     fn div(self, rhs: T) -> Point2<T> {
@@ -102,7 +104,7 @@ impl<T: Float> ops::Div<T> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::DivAssign<T> for Point2<T> {
+impl<T: Num + Copy> ops::DivAssign<T> for Point2<T> {
     fn div_assign(&mut self, rhs: T) {
         if rhs == Zero::zero() {
             // TODO: raise error?
@@ -116,7 +118,7 @@ impl<T: Float> ops::DivAssign<T> for Point2<T> {
     }
 }
 
-impl<T: Float> ops::Index<u8> for Point2<T> {
+impl<T> ops::Index<u8> for Point2<T> {
     type Output = T;
 
     fn index(&self, index: u8) -> &T {
@@ -128,7 +130,7 @@ impl<T: Float> ops::Index<u8> for Point2<T> {
     }
 }
 
-impl<T: Float> cmp::PartialEq<Point2<T>> for Point2<T> {
+impl<T: PartialEq> cmp::PartialEq<Point2<T>> for Point2<T> {
     fn eq(&self, other: &Point2<T>) -> bool {
         return self.x == other.x && self.y == other.y;
     }
@@ -171,6 +173,20 @@ impl<T: Float> Point3<T> {
     pub fn dot(&self, other: &Point3<T>) -> T {
         return self.x * other.x + self.y * other.y + self.z * other.z;
     }
+
+    pub fn min(&self, other: &Point3<T>) -> Point3<T> {
+        return Point3::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z));
+    }
+
+    pub fn max(&self, other: &Point3<T>) -> Point3<T> {
+        return Point3::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z));
+    }
+}
+
+impl<T: Float> From<Vector3<T>> for Point3<T> {
+    fn from(v: Vector3<T>) -> Point3<T> {
+        return Point3::new(v.x, v.y, v.z);
+    }
 }
 
 impl<T: Float> ops::Add<Point3<T>> for Point3<T> {
@@ -317,10 +333,10 @@ impl<T: Float> ops::AddAssign<Vector3<T>> for Point3<T> {
 }
 
 impl<T: Float> ops::Sub<Vector3<T>> for Point3<T> {
-    type Output = Vector3<T>;
+    type Output = Point3<T>;
 
-    fn sub(self, rhs: Vector3<T>) -> Vector3<T> {
-        return Vector3::new(
+    fn sub(self, rhs: Vector3<T>) -> Point3<T> {
+        return Point3::new(
             self.x - rhs.x,
             self.y - rhs.y,
             self.z - rhs.z,
@@ -0,0 +1,152 @@
+use std::ops;
+
+use pbrt;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::transform::Matrix4x4;
+use pbrt::geometry::transform::Transform;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub v: Vector3f,
+    pub w: f64,
+}
+
+impl Quaternion {
+    pub fn new(v: Vector3f, w: f64) -> Quaternion {
+        Quaternion { v, w }
+    }
+
+    pub fn identity() -> Quaternion {
+        return Quaternion::new(Vector3f::new(0.0, 0.0, 0.0), 1.0);
+    }
+
+    // Extracts the rotation from the upper 3x3 of a pure rotation transform.
+    pub fn from_transform(t: &Transform) -> Quaternion {
+        let m = &t.m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt();
+            let w = s / 2.0;
+            let s = 0.5 / s;
+            return Quaternion::new(
+                Vector3f::new((m[2][1] - m[1][2]) * s, (m[0][2] - m[2][0]) * s, (m[1][0] - m[0][1]) * s),
+                w,
+            );
+        }
+
+        // Compute the largest of x, y, z first for numerical stability
+        let nxt = [1, 2, 0];
+        let mut i = 0;
+        if m[1][1] > m[0][0] {
+            i = 1;
+        }
+        if m[2][2] > m[i][i] {
+            i = 2;
+        }
+        let j = nxt[i];
+        let k = nxt[j];
+        let mut s = ((m[i][i] - (m[j][j] + m[k][k])) + 1.0).sqrt();
+        let mut q = [0.0; 3];
+        q[i] = s * 0.5;
+        if s != 0.0 {
+            s = 0.5 / s;
+        }
+        let w = (m[k][j] - m[j][k]) * s;
+        q[j] = (m[j][i] + m[i][j]) * s;
+        q[k] = (m[k][i] + m[i][k]) * s;
+        return Quaternion::new(Vector3f::new(q[0], q[1], q[2]), w);
+    }
+
+    pub fn to_transform(self) -> Transform {
+        let (x, y, z) = (self.v.x, self.v.y, self.v.z);
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (x * self.w, y * self.w, z * self.w);
+
+        let m = Matrix4x4::new(
+            1.0 - 2.0 * (yy + zz), 2.0 * (xy - wz), 2.0 * (xz + wy), 0.0,
+            2.0 * (xy + wz), 1.0 - 2.0 * (xx + zz), 2.0 * (yz - wx), 0.0,
+            2.0 * (xz - wy), 2.0 * (yz + wx), 1.0 - 2.0 * (xx + yy), 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        return Transform::from_matrices(m, m.transpose());
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        return self.v.dot(&other.v) + self.w * other.w;
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        return Quaternion::new(-self.v, self.w);
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        return *self / self.dot(self).sqrt();
+    }
+
+    // Spherical linear interpolation along the great arc from q1 to q2.
+    pub fn slerp(t: f64, q1: &Quaternion, q2: &Quaternion) -> Quaternion {
+        let cos_theta = q1.dot(q2);
+        if cos_theta > 0.9995 {
+            return (*q1 * (1.0 - t) + *q2 * t).normalize();
+        }
+
+        let theta = pbrt::clamp(cos_theta, -1.0, 1.0).acos();
+        let thetap = theta * t;
+        let qperp = (*q2 - *q1 * cos_theta).normalize();
+        return *q1 * thetap.cos() + qperp * thetap.sin();
+    }
+}
+
+impl ops::Add<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, rhs: Quaternion) -> Quaternion {
+        return Quaternion::new(self.v + rhs.v, self.w + rhs.w);
+    }
+}
+
+impl ops::Sub<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn sub(self, rhs: Quaternion) -> Quaternion {
+        return Quaternion::new(self.v - rhs.v, self.w - rhs.w);
+    }
+}
+
+// Hamilton product: rotating by (a * b) rotates by b, then by a.
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        return Quaternion::new(
+            rhs.v * self.w + self.v * rhs.w + self.v.cross(&rhs.v),
+            self.w * rhs.w - self.v.dot(&rhs.v),
+        );
+    }
+}
+
+impl ops::Mul<f64> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: f64) -> Quaternion {
+        return Quaternion::new(self.v * rhs, self.w * rhs);
+    }
+}
+
+impl ops::Div<f64> for Quaternion {
+    type Output = Quaternion;
+
+    fn div(self, rhs: f64) -> Quaternion {
+        return Quaternion::new(self.v / rhs, self.w / rhs);
+    }
+}
+
+impl ops::Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Quaternion {
+        return Quaternion::new(-self.v, -self.w);
+    }
+}
@@ -31,6 +31,21 @@ pub struct RayDifferential {
 }
 
 impl RayDifferential {
+    // A differential ray with no offset rays yet.
+    pub fn from_ray(r: &Ray) -> RayDifferential {
+        RayDifferential {
+            origin: r.origin,
+            direction: r.direction,
+            t_max: r.t_max,
+            time: r.time,
+            has_differentials: false,
+            rx_origin: r.origin,
+            ry_origin: r.origin,
+            rx_direction: r.direction,
+            ry_direction: r.direction,
+        }
+    }
+
     pub fn scale_differentials(&mut self, s: f64) {
         self.rx_origin = self.origin + (self.rx_origin - self.origin) * s;
         self.ry_origin = self.origin + (self.ry_origin - self.origin) * s;
@@ -0,0 +1,70 @@
+use pbrt::geometry::Bounds3f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::animated_transform::AnimatedTransform;
+use pbrt::geometry::interaction::Interaction;
+use pbrt::geometry::interaction::SurfaceInteraction;
+use pbrt::geometry::ray::Ray;
+
+pub trait Shape {
+    fn world_bound(&self) -> Bounds3f;
+
+    // Returns the parametric distance along the ray and the surface record of
+    // the nearest hit in (0, ray.t_max).
+    fn intersect(&self, ray: &Ray) -> Option<(f64, SurfaceInteraction)>;
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        return self.intersect(ray).is_some();
+    }
+
+    fn area(&self) -> f64;
+
+    // Samples a point uniformly by area on the surface of the shape.
+    fn sample(&self, u: &Point2f) -> Interaction;
+
+    fn pdf(&self, _it: &Interaction) -> f64 {
+        return 1.0 / self.area();
+    }
+}
+
+
+// A shape placed in the world by an animated transform, e.g. for motion
+// blur. The wrapped shape is defined in its own space and each ray is
+// intersected against the transform interpolated at the ray's time.
+pub struct TransformedShape {
+    shape: Box<dyn Shape>,
+    shape_to_world: AnimatedTransform,
+}
+
+impl TransformedShape {
+    pub fn new(shape: Box<dyn Shape>, shape_to_world: AnimatedTransform) -> TransformedShape {
+        TransformedShape { shape, shape_to_world }
+    }
+}
+
+impl Shape for TransformedShape {
+    fn world_bound(&self) -> Bounds3f {
+        return self.shape_to_world.motion_bounds(&self.shape.world_bound());
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(f64, SurfaceInteraction)> {
+        let shape_to_world = self.shape_to_world.interpolate(ray.time);
+        let r = shape_to_world.inverse().transform_ray(ray);
+        let (t_hit, isect) = self.shape.intersect(&r)?;
+        return Some((t_hit, shape_to_world.transform_surface_interaction(&isect)));
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        let r = self.shape_to_world.interpolate(ray.time).inverse().transform_ray(ray);
+        return self.shape.intersect_p(&r);
+    }
+
+    // Area of the shape in its own space; animated scaling is not accounted for.
+    fn area(&self) -> f64 {
+        return self.shape.area();
+    }
+
+    fn sample(&self, u: &Point2f) -> Interaction {
+        let it = self.shape.sample(u);
+        return self.shape_to_world.interpolate(it.time).transform_interaction(&it);
+    }
+}
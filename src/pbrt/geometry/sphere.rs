@@ -1,44 +1,287 @@
 use std::f64;
 
+use pbrt;
+use pbrt::geometry::Bounds3f;
+use pbrt::geometry::Normal3f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
 use pbrt::geometry::Vector3f;
+use pbrt::geometry::interaction::Interaction;
+use pbrt::geometry::interaction::SurfaceInteraction;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::shape::Shape;
+use pbrt::geometry::transform::Transform;
 
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct Sphere {
-    center: Vector3f,
+    object_to_world: Transform,
+    world_to_object: Transform,
     radius: f64,
+    z_min: f64,
+    z_max: f64,
+    theta_z_min: f64,
+    theta_z_max: f64,
+    phi_max: f64,
 }
 
 impl Sphere {
-    pub fn new(center: Vector3f, radius: f64) -> Sphere {
-        Sphere { center, radius }
+    pub fn new(object_to_world: Transform, radius: f64) -> Sphere {
+        return Sphere::partial(object_to_world, radius, -radius, radius, 360.0);
     }
 
-    pub fn center(&self) -> Vector3f {
-        self.center
+    // A sphere clipped to [z_min, z_max] in object space and swept through
+    // phi_max degrees about the z axis.
+    pub fn partial(object_to_world: Transform, radius: f64, z_min: f64, z_max: f64, phi_max: f64) -> Sphere {
+        let z_lo = pbrt::clamp(z_min.min(z_max), -radius, radius);
+        let z_hi = pbrt::clamp(z_min.max(z_max), -radius, radius);
+        Sphere {
+            object_to_world,
+            world_to_object: object_to_world.inverse(),
+            radius,
+            z_min: z_lo,
+            z_max: z_hi,
+            theta_z_min: pbrt::clamp(z_lo / radius, -1.0, 1.0).acos(),
+            theta_z_max: pbrt::clamp(z_hi / radius, -1.0, 1.0).acos(),
+            phi_max: pbrt::radians(pbrt::clamp(phi_max, 0.0, 360.0)),
+        }
+    }
+
+    pub fn object_to_world(&self) -> &Transform {
+        &self.object_to_world
     }
 
     pub fn radius(&self) -> f64 {
         self.radius
     }
 
-    pub fn intersects(&self, origin: &Vector3f, direction: &Vector3f) -> (f64, f64, bool) {
-        let l = self.center() - *origin;
-        let tca = l.dot(direction);
+    // Whether an object-space hit point lies outside the clipped part of the sphere.
+    fn is_clipped(&self, p: &Point3f, phi: f64) -> bool {
+        return (self.z_min > -self.radius && p.z < self.z_min)
+            || (self.z_max < self.radius && p.z > self.z_max)
+            || phi > self.phi_max;
+    }
+
+    // Refines an object-space point onto the surface and returns it with its phi.
+    fn surface_point(&self, ray: &Ray, t: f64) -> (Point3f, f64) {
+        let mut p = ray.origin + ray.direction * t;
+        p *= self.radius / p.length();
+        if p.x == 0.0 && p.y == 0.0 {
+            p.x = 1e-5 * self.radius;
+        }
+
+        let mut phi = p.y.atan2(p.x);
+        if phi < 0.0 {
+            phi += 2.0 * f64::consts::PI;
+        }
+        return (p, phi);
+    }
+
+    // Finds the nearest unclipped hit of an object-space ray.
+    fn intersect_object(&self, ray: &Ray) -> Option<(f64, Point3f, f64)> {
+        let o = ray.origin;
+        let d = ray.direction;
+
+        let a = d.length_squared();
+        let b = 2.0 * (d.x * o.x + d.y * o.y + d.z * o.z);
+        let c = o.length_squared() - self.radius * self.radius;
 
-        if tca < 0.0 {
-            return (f64::MAX, f64::MAX, false);
+        let (t0, t1) = pbrt::quadratic(a, b, c)?;
+        if t0 >= ray.t_max || t1 <= 0.0 {
+            return None;
         }
 
-        let d2 = l.length_squared() - tca * tca;
-        if d2 > self.radius * self.radius {
-            return (f64::MAX, f64::MAX, false);
+        let mut t_shape_hit = t0;
+        if t_shape_hit <= 0.0 {
+            t_shape_hit = t1;
+            if t_shape_hit >= ray.t_max {
+                return None;
+            }
         }
 
-        let thc = (self.radius * self.radius).sqrt() - d2;
+        let (mut p_hit, mut phi) = self.surface_point(ray, t_shape_hit);
+        if self.is_clipped(&p_hit, phi) {
+            if t_shape_hit == t1 || t1 >= ray.t_max {
+                return None;
+            }
+            t_shape_hit = t1;
+            let (p, p_phi) = self.surface_point(ray, t_shape_hit);
+            if self.is_clipped(&p, p_phi) {
+                return None;
+            }
+            p_hit = p;
+            phi = p_phi;
+        }
+
+        return Some((t_shape_hit, p_hit, phi));
+    }
+
+    pub fn inside(&self, p: &Point3f) -> bool {
+        let local = Vector3f::from(self.world_to_object.transform_point(p));
+        if local.length_squared() > self.radius * self.radius {
+            return false;
+        }
+        if local.z < self.z_min || local.z > self.z_max {
+            return false;
+        }
+
+        let mut phi = local.y.atan2(local.x);
+        if phi < 0.0 {
+            phi += 2.0 * f64::consts::PI;
+        }
+        return phi <= self.phi_max;
+    }
+}
+
+impl Shape for Sphere {
+    fn world_bound(&self) -> Bounds3f {
+        let object_bound = Bounds3f::new(
+            Point3f::new(-self.radius, -self.radius, self.z_min),
+            Point3f::new(self.radius, self.radius, self.z_max),
+        );
+        return self.object_to_world.transform_bounds(&object_bound);
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(f64, SurfaceInteraction)> {
+        let object_ray = self.world_to_object.transform_ray(ray);
+        let (t_shape_hit, p_hit, phi) = self.intersect_object(&object_ray)?;
+
+        // Parametric representation of the hit
+        let u = phi / self.phi_max;
+        let cos_theta = pbrt::clamp(p_hit.z / self.radius, -1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let theta = cos_theta.acos();
+        let theta_range = self.theta_z_max - self.theta_z_min;
+        let v = (theta - self.theta_z_min) / theta_range;
+
+        let z_radius = (p_hit.x * p_hit.x + p_hit.y * p_hit.y).sqrt();
+        let cos_phi = p_hit.x / z_radius;
+        let sin_phi = p_hit.y / z_radius;
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let dpdv = Vector3f::new(p_hit.z * cos_phi, p_hit.z * sin_phi, -self.radius * sin_theta) * theta_range;
+
+        // Weingarten equations for the normal derivatives
+        let d2pduu = Vector3f::new(p_hit.x, p_hit.y, 0.0) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::new(-sin_phi, cos_phi, 0.0) * (theta_range * p_hit.z * self.phi_max);
+        let d2pdvv = Vector3f::new(p_hit.x, p_hit.y, p_hit.z) * (-theta_range * theta_range);
 
-        let t0 = tca - thc;
-        let t1 = tca + thc;
-        return (t0, t1, true);
+        let e1 = dpdu.dot(&dpdu);
+        let f1 = dpdu.dot(&dpdv);
+        let g1 = dpdv.dot(&dpdv);
+        let mut n = dpdu.cross(&dpdv);
+        n.normalize();
+        let e = n.dot(&d2pduu);
+        let f = n.dot(&d2pduv);
+        let g = n.dot(&d2pdvv);
+
+        let inv_egf2 = 1.0 / (e1 * g1 - f1 * f1);
+        let dndu = Normal3f::from(dpdu * ((f * f1 - e * g1) * inv_egf2) + dpdv * ((e * f1 - f * e1) * inv_egf2));
+        let dndv = Normal3f::from(dpdu * ((g * f1 - f * g1) * inv_egf2) + dpdv * ((f * f1 - g * e1) * inv_egf2));
+
+        let isect = SurfaceInteraction::new(
+            p_hit,
+            Point2f::new(u, v),
+            -object_ray.direction,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            ray.time,
+        );
+        return Some((t_shape_hit, self.object_to_world.transform_surface_interaction(&isect)));
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        let object_ray = self.world_to_object.transform_ray(ray);
+        return self.intersect_object(&object_ray).is_some();
+    }
+
+    fn area(&self) -> f64 {
+        return self.phi_max * self.radius * (self.z_max - self.z_min);
+    }
+
+    // Uniform in z and phi, which is uniform by area on a sphere (Archimedes'
+    // hat-box theorem), so it matches `area` for partial spheres too.
+    fn sample(&self, u: &Point2f) -> Interaction {
+        let z = pbrt::lerp(u.x, self.z_min, self.z_max);
+        let phi = u.y * self.phi_max;
+        let r_xy = (self.radius * self.radius - z * z).max(0.0).sqrt();
+        let p_obj = Point3f::new(r_xy * phi.cos(), r_xy * phi.sin(), z);
+        let mut n = Normal3f::new(p_obj.x, p_obj.y, p_obj.z);
+        n.normalize();
+
+        let it = Interaction::new(p_obj, n, Vector3f::new(0.0, 0.0, 0.0), 0.0);
+        return self.object_to_world.transform_interaction(&it);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f64;
+
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::ray::Ray;
+    use pbrt::geometry::shape::Shape;
+    use pbrt::geometry::sphere::Sphere;
+    use pbrt::geometry::transform::Transform;
+    use pbrt::rng::Rng;
+
+    // A unit sphere cut to |z| <= 0.5 and the half with y >= 0, moved up by 2.
+    fn band() -> Sphere {
+        return Sphere::partial(Transform::translate(&Vector3f::new(0.0, 0.0, 2.0)), 1.0, -0.5, 0.5, 180.0);
+    }
+
+    #[test]
+    fn partial_spheres_clip_hits() {
+        let sphere = band();
+        let hit = |origin: Point3f, direction: Vector3f| {
+            return sphere.intersect(&Ray::new(origin, direction, f64::INFINITY, 0.0)).map(|(t, _)| t);
+        };
+        let near = 5.0 - (1.0f64 - 0.01).sqrt();
+
+        // The near side is kept where phi is at most 180 degrees
+        assert!((hit(Point3f::new(5.0, 0.1, 2.0), Vector3f::new(-1.0, 0.0, 0.0)).unwrap() - near).abs() < 1e-9);
+        assert!((hit(Point3f::new(-5.0, 0.1, 2.0), Vector3f::new(1.0, 0.0, 0.0)).unwrap() - near).abs() < 1e-9);
+        assert!(hit(Point3f::new(-5.0, -0.1, 2.0), Vector3f::new(1.0, 0.0, 0.0)).is_none());
+
+        // A clipped near side lets the ray through to the far side
+        let t = hit(Point3f::new(0.0, -5.0, 2.0), Vector3f::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((t - 6.0).abs() < 1e-9);
+
+        // The caps are open, from outside and from inside
+        assert!(hit(Point3f::new(0.0, 0.2, -3.0), Vector3f::new(0.0, 0.0, 1.0)).is_none());
+        assert!(hit(Point3f::new(0.0, 0.0, 2.0), Vector3f::new(0.0, 0.0, -1.0)).is_none());
+        assert!((hit(Point3f::new(0.0, 0.0, 2.0), Vector3f::new(0.0, 1.0, 0.0)).unwrap() - 1.0).abs() < 1e-9);
+
+        let p = Point3f::new(0.0, 0.2, 2.0);
+        assert!(sphere.intersect_p(&Ray::new(p, Vector3f::new(0.0, 1.0, 0.0), f64::INFINITY, 0.0)));
+        assert!(!sphere.intersect_p(&Ray::new(p, Vector3f::new(0.0, 1.0, 0.0), 0.5, 0.0)));
+    }
+
+    #[test]
+    fn samples_cover_the_partial_area_uniformly() {
+        let sphere = band();
+        assert!((sphere.area() - f64::consts::PI).abs() < 1e-12);
+        assert!((sphere.pdf(&sphere.sample(&Point2f::new(0.5, 0.5))) - 1.0 / f64::consts::PI).abs() < 1e-12);
+
+        // Sampled points lie on the kept surface; slabs of equal height have
+        // equal area, so they should get equal shares
+        let mut rng = Rng::new(7);
+        let mut slabs = [0; 4];
+        let n = 40000;
+        for _ in 0..n {
+            let it = sphere.sample(&Point2f::new(rng.uniform_f64(), rng.uniform_f64()));
+            let p = it.p - Vector3f::new(0.0, 0.0, 2.0);
+            assert!((Vector3f::from(p).length() - 1.0).abs() < 1e-9);
+            assert!(p.z >= -0.5 && p.z <= 0.5 && p.y >= -1e-9);
+            assert!((Vector3f::from(it.n) - Vector3f::from(p)).length() < 1e-9);
+            slabs[((p.z + 0.5) * 4.0).min(3.0) as usize] += 1;
+        }
+        for &count in slabs.iter() {
+            assert!((count as f64 / n as f64 - 0.25).abs() < 0.01, "{:?}", slabs);
+        }
     }
 }
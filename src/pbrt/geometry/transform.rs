@@ -0,0 +1,497 @@
+use std::ops;
+
+use pbrt;
+use pbrt::geometry::Bounds3f;
+use pbrt::geometry::Normal3f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::interaction::Interaction;
+use pbrt::geometry::interaction::Shading;
+use pbrt::geometry::interaction::SurfaceInteraction;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::ray::RayDifferential;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4x4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4x4 {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        t00: f64, t01: f64, t02: f64, t03: f64,
+        t10: f64, t11: f64, t12: f64, t13: f64,
+        t20: f64, t21: f64, t22: f64, t23: f64,
+        t30: f64, t31: f64, t32: f64, t33: f64,
+    ) -> Matrix4x4 {
+        Matrix4x4 {
+            m: [
+                [t00, t01, t02, t03],
+                [t10, t11, t12, t13],
+                [t20, t21, t22, t23],
+                [t30, t31, t32, t33],
+            ],
+        }
+    }
+
+    pub fn identity() -> Matrix4x4 {
+        return Matrix4x4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+    }
+
+    pub fn transpose(&self) -> Matrix4x4 {
+        let mut r = [[0.0; 4]; 4];
+        for (i, row) in r.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        return Matrix4x4 { m: r };
+    }
+
+    // Gauss-Jordan elimination with full pivoting. Returns None for singular matrices.
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let mut indxc = [0usize; 4];
+        let mut indxr = [0usize; 4];
+        let mut ipiv = [0usize; 4];
+        let mut minv = self.m;
+
+        for i in 0..4 {
+            let mut irow = 0;
+            let mut icol = 0;
+            let mut big = 0.0;
+
+            // Choose the pivot
+            for j in 0..4 {
+                if ipiv[j] != 1 {
+                    for k in 0..4 {
+                        if ipiv[k] == 0 {
+                            if minv[j][k].abs() >= big {
+                                big = minv[j][k].abs();
+                                irow = j;
+                                icol = k;
+                            }
+                        } else if ipiv[k] > 1 {
+                            return None;
+                        }
+                    }
+                }
+            }
+            ipiv[icol] += 1;
+
+            // Swap rows irow and icol for the pivot
+            if irow != icol {
+                minv.swap(irow, icol);
+            }
+            indxr[i] = irow;
+            indxc[i] = icol;
+            if minv[icol][icol] == 0.0 {
+                return None;
+            }
+
+            // Set m[icol][icol] to one by scaling row icol appropriately
+            let pivinv = 1.0 / minv[icol][icol];
+            minv[icol][icol] = 1.0;
+            for v in minv[icol].iter_mut() {
+                *v *= pivinv;
+            }
+
+            // Subtract this row from others to zero out their columns
+            let pivot_row = minv[icol];
+            for (j, row) in minv.iter_mut().enumerate() {
+                if j != icol {
+                    let save = row[icol];
+                    row[icol] = 0.0;
+                    for (v, p) in row.iter_mut().zip(pivot_row.iter()) {
+                        *v -= p * save;
+                    }
+                }
+            }
+        }
+
+        // Swap columns to reflect the permutation
+        for j in (0..4).rev() {
+            if indxr[j] != indxc[j] {
+                for row in minv.iter_mut() {
+                    row.swap(indxr[j], indxc[j]);
+                }
+            }
+        }
+
+        return Some(Matrix4x4 { m: minv });
+    }
+}
+
+impl ops::Mul<Matrix4x4> for Matrix4x4 {
+    type Output = Matrix4x4;
+
+    fn mul(self, rhs: Matrix4x4) -> Matrix4x4 {
+        let mut r = [[0.0; 4]; 4];
+        for (i, row) in r.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[i][0] * rhs.m[0][j]
+                    + self.m[i][1] * rhs.m[1][j]
+                    + self.m[i][2] * rhs.m[2][j]
+                    + self.m[i][3] * rhs.m[3][j];
+            }
+        }
+        return Matrix4x4 { m: r };
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub m: Matrix4x4,
+    pub m_inv: Matrix4x4,
+}
+
+impl Transform {
+    // Panics if `m` is singular; use `from_matrices` when the inverse is known.
+    pub fn new(m: Matrix4x4) -> Transform {
+        let m_inv = m.inverse().expect("singular matrix in Transform::new");
+        Transform { m, m_inv }
+    }
+
+    pub fn from_matrices(m: Matrix4x4, m_inv: Matrix4x4) -> Transform {
+        Transform { m, m_inv }
+    }
+
+    pub fn identity() -> Transform {
+        return Transform::from_matrices(Matrix4x4::identity(), Matrix4x4::identity());
+    }
+
+    pub fn translate(delta: &Vector3f) -> Transform {
+        let m = Matrix4x4::new(
+            1.0, 0.0, 0.0, delta.x,
+            0.0, 1.0, 0.0, delta.y,
+            0.0, 0.0, 1.0, delta.z,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let m_inv = Matrix4x4::new(
+            1.0, 0.0, 0.0, -delta.x,
+            0.0, 1.0, 0.0, -delta.y,
+            0.0, 0.0, 1.0, -delta.z,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        return Transform::from_matrices(m, m_inv);
+    }
+
+    pub fn scale(x: f64, y: f64, z: f64) -> Transform {
+        let m = Matrix4x4::new(
+            x, 0.0, 0.0, 0.0,
+            0.0, y, 0.0, 0.0,
+            0.0, 0.0, z, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let m_inv = Matrix4x4::new(
+            1.0 / x, 0.0, 0.0, 0.0,
+            0.0, 1.0 / y, 0.0, 0.0,
+            0.0, 0.0, 1.0 / z, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        return Transform::from_matrices(m, m_inv);
+    }
+
+    pub fn rotate_x(theta: f64) -> Transform {
+        let (sin_theta, cos_theta) = pbrt::radians(theta).sin_cos();
+        let m = Matrix4x4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, cos_theta, -sin_theta, 0.0,
+            0.0, sin_theta, cos_theta, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        return Transform::from_matrices(m, m.transpose());
+    }
+
+    pub fn rotate_y(theta: f64) -> Transform {
+        let (sin_theta, cos_theta) = pbrt::radians(theta).sin_cos();
+        let m = Matrix4x4::new(
+            cos_theta, 0.0, sin_theta, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            -sin_theta, 0.0, cos_theta, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        return Transform::from_matrices(m, m.transpose());
+    }
+
+    pub fn rotate_z(theta: f64) -> Transform {
+        let (sin_theta, cos_theta) = pbrt::radians(theta).sin_cos();
+        let m = Matrix4x4::new(
+            cos_theta, -sin_theta, 0.0, 0.0,
+            sin_theta, cos_theta, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        return Transform::from_matrices(m, m.transpose());
+    }
+
+    // Rotation of `theta` degrees about an arbitrary axis.
+    pub fn rotate(theta: f64, axis: &Vector3f) -> Transform {
+        let mut a = *axis;
+        a.normalize();
+        let (sin_theta, cos_theta) = pbrt::radians(theta).sin_cos();
+
+        let mut m = Matrix4x4::identity();
+        m.m[0][0] = a.x * a.x + (1.0 - a.x * a.x) * cos_theta;
+        m.m[0][1] = a.x * a.y * (1.0 - cos_theta) - a.z * sin_theta;
+        m.m[0][2] = a.x * a.z * (1.0 - cos_theta) + a.y * sin_theta;
+
+        m.m[1][0] = a.x * a.y * (1.0 - cos_theta) + a.z * sin_theta;
+        m.m[1][1] = a.y * a.y + (1.0 - a.y * a.y) * cos_theta;
+        m.m[1][2] = a.y * a.z * (1.0 - cos_theta) - a.x * sin_theta;
+
+        m.m[2][0] = a.x * a.z * (1.0 - cos_theta) - a.y * sin_theta;
+        m.m[2][1] = a.y * a.z * (1.0 - cos_theta) + a.x * sin_theta;
+        m.m[2][2] = a.z * a.z + (1.0 - a.z * a.z) * cos_theta;
+
+        return Transform::from_matrices(m, m.transpose());
+    }
+
+    // World-to-camera transform for a camera at `pos` looking at `look`.
+    // Returns None if `up` is parallel to the viewing direction.
+    pub fn look_at(pos: &Point3f, look: &Point3f, up: &Vector3f) -> Option<Transform> {
+        let mut dir = *look - *pos;
+        dir.normalize();
+        let mut up = *up;
+        up.normalize();
+        let mut right = up.cross(&dir);
+        if right.length() == 0.0 {
+            return None;
+        }
+        right.normalize();
+        let new_up = dir.cross(&right);
+
+        let camera_to_world = Matrix4x4::new(
+            right.x, new_up.x, dir.x, pos.x,
+            right.y, new_up.y, dir.y, pos.y,
+            right.z, new_up.z, dir.z, pos.z,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let world_to_camera = camera_to_world.inverse()?;
+        return Some(Transform::from_matrices(world_to_camera, camera_to_world));
+    }
+
+    // Parallel projection keeping x and y, mapping [near, far] to [0, 1].
+    pub fn orthographic(near: f64, far: f64) -> Transform {
+        return Transform::scale(1.0, 1.0, 1.0 / (far - near)) * Transform::translate(&Vector3f::new(0.0, 0.0, -near));
+    }
+
+    // Projects camera space onto the z=1 plane, mapping [near, far] to [0, 1].
+    pub fn perspective(fov: f64, near: f64, far: f64) -> Transform {
+        let persp = Matrix4x4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, far / (far - near), -far * near / (far - near),
+            0.0, 0.0, 1.0, 0.0,
+        );
+        let inv_tan_ang = 1.0 / (pbrt::radians(fov) / 2.0).tan();
+        return Transform::scale(inv_tan_ang, inv_tan_ang, 1.0) * Transform::new(persp);
+    }
+
+    pub fn inverse(&self) -> Transform {
+        return Transform::from_matrices(self.m_inv, self.m);
+    }
+
+    pub fn transpose(&self) -> Transform {
+        return Transform::from_matrices(self.m.transpose(), self.m_inv.transpose());
+    }
+
+    pub fn is_identity(&self) -> bool {
+        return self.m == Matrix4x4::identity();
+    }
+
+    pub fn has_scale(&self) -> bool {
+        let not_one = |v: Vector3f| {
+            let l2 = self.transform_vector(&v).length_squared();
+            return !(0.999..=1.001).contains(&l2);
+        };
+        return not_one(Vector3f::new(1.0, 0.0, 0.0))
+            || not_one(Vector3f::new(0.0, 1.0, 0.0))
+            || not_one(Vector3f::new(0.0, 0.0, 1.0));
+    }
+
+    // Whether the transform flips a right-handed coordinate system into a left-handed one.
+    pub fn swaps_handedness(&self) -> bool {
+        let m = &self.m.m;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        return det < 0.0;
+    }
+
+    pub fn transform_point(&self, p: &Point3f) -> Point3f {
+        let m = &self.m.m;
+        let xp = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let yp = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let zp = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let wp = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if wp == 1.0 {
+            return Point3f::new(xp, yp, zp);
+        }
+        return Point3f::new(xp, yp, zp) / wp;
+    }
+
+    pub fn transform_vector(&self, v: &Vector3f) -> Vector3f {
+        let m = &self.m.m;
+        return Vector3f::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        );
+    }
+
+    // Normals transform by the inverse transpose so they stay perpendicular to surfaces.
+    pub fn transform_normal(&self, n: &Normal3f) -> Normal3f {
+        let m_inv = &self.m_inv.m;
+        return Normal3f::new(
+            m_inv[0][0] * n.x + m_inv[1][0] * n.y + m_inv[2][0] * n.z,
+            m_inv[0][1] * n.x + m_inv[1][1] * n.y + m_inv[2][1] * n.z,
+            m_inv[0][2] * n.x + m_inv[1][2] * n.y + m_inv[2][2] * n.z,
+        );
+    }
+
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        return Ray::new(self.transform_point(&r.origin), self.transform_vector(&r.direction), r.t_max, r.time);
+    }
+
+    pub fn transform_ray_differential(&self, r: &RayDifferential) -> RayDifferential {
+        return RayDifferential {
+            origin: self.transform_point(&r.origin),
+            direction: self.transform_vector(&r.direction),
+            t_max: r.t_max,
+            time: r.time,
+            has_differentials: r.has_differentials,
+            rx_origin: self.transform_point(&r.rx_origin),
+            ry_origin: self.transform_point(&r.ry_origin),
+            rx_direction: self.transform_vector(&r.rx_direction),
+            ry_direction: self.transform_vector(&r.ry_direction),
+        };
+    }
+
+    pub fn transform_bounds(&self, b: &Bounds3f) -> Bounds3f {
+        let mut ret = Bounds3f::from_point(self.transform_point(&b.corner(0)));
+        for corner in 1..8 {
+            ret = ret.union_point(&self.transform_point(&b.corner(corner)));
+        }
+        return ret;
+    }
+
+    pub fn transform_interaction(&self, it: &Interaction) -> Interaction {
+        let mut n = self.transform_normal(&it.n);
+        n.normalize();
+        return Interaction::new(self.transform_point(&it.p), n, self.transform_vector(&it.wo), it.time);
+    }
+
+    pub fn transform_surface_interaction(&self, si: &SurfaceInteraction) -> SurfaceInteraction {
+        let mut ret = *si;
+        ret.p = self.transform_point(&si.p);
+        ret.n = self.transform_normal(&si.n);
+        ret.n.normalize();
+        ret.wo = self.transform_vector(&si.wo);
+        if ret.wo.length_squared() > 0.0 {
+            ret.wo.normalize();
+        }
+        ret.dpdu = self.transform_vector(&si.dpdu);
+        ret.dpdv = self.transform_vector(&si.dpdv);
+        ret.dndu = self.transform_normal(&si.dndu);
+        ret.dndv = self.transform_normal(&si.dndv);
+
+        let mut shading_n = self.transform_normal(&si.shading.n);
+        shading_n.normalize();
+        ret.shading = Shading {
+            n: shading_n.face_forward(&ret.n),
+            dpdu: self.transform_vector(&si.shading.dpdu),
+            dpdv: self.transform_vector(&si.shading.dpdv),
+            dndu: self.transform_normal(&si.shading.dndu),
+            dndv: self.transform_normal(&si.shading.dndv),
+        };
+        return ret;
+    }
+}
+
+impl ops::Mul<Transform> for Transform {
+    type Output = Transform;
+
+    // Composition: (a * b) applies b first, then a.
+    fn mul(self, rhs: Transform) -> Transform {
+        return Transform::from_matrices(self.m * rhs.m, rhs.m_inv * self.m_inv);
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        return Transform::identity();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Normal3f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::transform::Matrix4x4;
+    use pbrt::geometry::transform::Transform;
+
+    fn assert_matrix_near(a: &Matrix4x4, b: &Matrix4x4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-9, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_of_composition() {
+        let t = Transform::translate(&Vector3f::new(1.0, -2.0, 3.0))
+            * Transform::rotate(30.0, &Vector3f::new(1.0, 1.0, 0.0))
+            * Transform::scale(2.0, 3.0, 4.0);
+        let inv = t.m.inverse().unwrap();
+        assert_matrix_near(&inv, &t.m_inv);
+        assert_matrix_near(&(t.m * inv), &Matrix4x4::identity());
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let mut m = Matrix4x4::identity();
+        m.m[2][2] = 0.0;
+        assert!(m.inverse().is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let t = Transform::scale(1.0, 4.0, 1.0);
+        let tangent = t.transform_vector(&Vector3f::new(1.0, 1.0, 0.0));
+        let n = t.transform_normal(&Normal3f::new(1.0, -1.0, 0.0));
+        assert!(tangent.dot(&Vector3f::from(n)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rotate_matches_axis_rotations() {
+        let p = Point3f::new(1.0, 2.0, 3.0);
+        let a = Transform::rotate(40.0, &Vector3f::new(0.0, 1.0, 0.0)).transform_point(&p);
+        let b = Transform::rotate_y(40.0).transform_point(&p);
+        assert!((a - b).length() < 1e-12);
+    }
+
+    #[test]
+    fn swaps_handedness() {
+        assert!(Transform::scale(-1.0, 1.0, 1.0).swaps_handedness());
+        assert!(!Transform::rotate_z(90.0).swaps_handedness());
+    }
+
+    #[test]
+    fn look_at_maps_eye_to_origin() {
+        let eye = Point3f::new(0.0, 0.0, 5.0);
+        let t = Transform::look_at(&eye, &Point3f::new(0.0, 0.0, 0.0), &Vector3f::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((t.transform_point(&eye) - Point3f::new(0.0, 0.0, 0.0)).length() < 1e-12);
+        let forward = t.transform_vector(&Vector3f::new(0.0, 0.0, -1.0));
+        assert!((forward - Vector3f::new(0.0, 0.0, 1.0)).length() < 1e-12);
+    }
+}
@@ -0,0 +1,436 @@
+use std::sync::Arc;
+
+use pbrt;
+use pbrt::geometry::Bounds3f;
+use pbrt::geometry::Normal3f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::Vector3f;
+use pbrt::geometry::interaction::Interaction;
+use pbrt::geometry::interaction::SurfaceInteraction;
+use pbrt::geometry::ray::Ray;
+use pbrt::geometry::shape::Shape;
+use pbrt::geometry::transform::Transform;
+use pbrt::sampling;
+
+
+// Vertex data shared by every triangle of a mesh, stored in world space.
+// Optional attributes are either absent or hold one entry per vertex.
+#[derive(Clone, Debug)]
+pub struct TriangleMesh {
+    pub n_triangles: usize,
+    pub transform_swaps_handedness: bool,
+    pub vertex_indices: Vec<usize>,
+    pub p: Vec<Point3f>,
+    pub n: Option<Vec<Normal3f>>,
+    pub s: Option<Vec<Vector3f>>,
+    pub uv: Option<Vec<Point2f>>,
+}
+
+impl TriangleMesh {
+    // Fails if the indices do not make whole triangles of existing vertices,
+    // or an attribute does not have one entry per vertex.
+    pub fn new(
+        object_to_world: &Transform,
+        vertex_indices: Vec<usize>,
+        p: Vec<Point3f>,
+        n: Option<Vec<Normal3f>>,
+        s: Option<Vec<Vector3f>>,
+        uv: Option<Vec<Point2f>>,
+    ) -> Result<TriangleMesh, String> {
+        if !vertex_indices.len().is_multiple_of(3) {
+            return Err(format!("{} vertex indices do not make whole triangles", vertex_indices.len()));
+        }
+        if let Some(&bad) = vertex_indices.iter().find(|&&i| i >= p.len()) {
+            return Err(format!("vertex index {} out of range (have {})", bad, p.len()));
+        }
+        let lengths = [
+            ("normals", n.as_ref().map(|n| n.len())),
+            ("tangents", s.as_ref().map(|s| s.len())),
+            ("uvs", uv.as_ref().map(|uv| uv.len())),
+        ];
+        for &(what, len) in lengths.iter() {
+            if let Some(len) = len.filter(|&len| len != p.len()) {
+                return Err(format!("{} {} for {} vertices", len, what, p.len()));
+            }
+        }
+
+        return Ok(TriangleMesh {
+            n_triangles: vertex_indices.len() / 3,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+            vertex_indices,
+            p: p.iter().map(|p| object_to_world.transform_point(p)).collect(),
+            n: n.map(|n| n.iter().map(|n| object_to_world.transform_normal(n)).collect()),
+            s: s.map(|s| s.iter().map(|s| object_to_world.transform_vector(s)).collect()),
+            uv,
+        });
+    }
+}
+
+pub fn create_triangles(mesh: &Arc<TriangleMesh>) -> Vec<Triangle> {
+    return (0..mesh.n_triangles).map(|i| Triangle::new(mesh.clone(), i)).collect();
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    v: usize,
+}
+
+impl Triangle {
+    pub fn new(mesh: Arc<TriangleMesh>, triangle: usize) -> Triangle {
+        Triangle { mesh, v: 3 * triangle }
+    }
+
+    pub fn mesh(&self) -> &Arc<TriangleMesh> {
+        &self.mesh
+    }
+
+    fn indices(&self) -> (usize, usize, usize) {
+        let vi = &self.mesh.vertex_indices;
+        return (vi[self.v], vi[self.v + 1], vi[self.v + 2]);
+    }
+
+    fn positions(&self) -> (Point3f, Point3f, Point3f) {
+        let (i0, i1, i2) = self.indices();
+        return (self.mesh.p[i0], self.mesh.p[i1], self.mesh.p[i2]);
+    }
+
+    fn uvs(&self) -> (Point2f, Point2f, Point2f) {
+        match self.mesh.uv {
+            Some(ref uv) => {
+                let (i0, i1, i2) = self.indices();
+                (uv[i0], uv[i1], uv[i2])
+            }
+            None => (Point2f::new(0.0, 0.0), Point2f::new(1.0, 0.0), Point2f::new(1.0, 1.0)),
+        }
+    }
+
+    // Watertight ray/triangle test. Returns the hit distance and barycentrics.
+    fn intersect_barycentric(&self, ray: &Ray) -> Option<(f64, f64, f64, f64)> {
+        let (p0, p1, p2) = self.positions();
+
+        // Translate vertices based on the ray origin
+        let mut p0t = p0 - ray.origin;
+        let mut p1t = p1 - ray.origin;
+        let mut p2t = p2 - ray.origin;
+
+        // Permute components so the ray direction's largest axis is z
+        let kz = ray.direction.abs().max_dimension();
+        let kx = if kz == 2 { 0 } else { kz + 1 };
+        let ky = if kx == 2 { 0 } else { kx + 1 };
+        let d = ray.direction.permute(kx, ky, kz);
+        p0t = p0t.permute(kx, ky, kz);
+        p1t = p1t.permute(kx, ky, kz);
+        p2t = p2t.permute(kx, ky, kz);
+
+        // Shear so the ray direction becomes +z
+        let sx = -d.x / d.z;
+        let sy = -d.y / d.z;
+        let sz = 1.0 / d.z;
+        p0t.x += sx * p0t.z;
+        p0t.y += sy * p0t.z;
+        p1t.x += sx * p1t.z;
+        p1t.y += sy * p1t.z;
+        p2t.x += sx * p2t.z;
+        p2t.y += sy * p2t.z;
+
+        // Edge function coefficients
+        let e0 = pbrt::difference_of_products(p1t.x, p2t.y, p1t.y, p2t.x);
+        let e1 = pbrt::difference_of_products(p2t.x, p0t.y, p2t.y, p0t.x);
+        let e2 = pbrt::difference_of_products(p0t.x, p1t.y, p0t.y, p1t.x);
+
+        if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+            return None;
+        }
+        let det = e0 + e1 + e2;
+        if det == 0.0 {
+            return None;
+        }
+
+        // Scaled hit distance, compared against the ray range without dividing
+        p0t.z *= sz;
+        p1t.z *= sz;
+        p2t.z *= sz;
+        let t_scaled = e0 * p0t.z + e1 * p1t.z + e2 * p2t.z;
+        if det < 0.0 && (t_scaled >= 0.0 || t_scaled < ray.t_max * det) {
+            return None;
+        }
+        if det > 0.0 && (t_scaled <= 0.0 || t_scaled > ray.t_max * det) {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let b0 = e0 * inv_det;
+        let b1 = e1 * inv_det;
+        let b2 = e2 * inv_det;
+        let t = t_scaled * inv_det;
+
+        // Make sure t is conservatively greater than zero
+        let max_zt = Vector3f::new(p0t.z, p1t.z, p2t.z).abs().max_component();
+        let delta_z = pbrt::gamma(3) * max_zt;
+        let max_xt = Vector3f::new(p0t.x, p1t.x, p2t.x).abs().max_component();
+        let max_yt = Vector3f::new(p0t.y, p1t.y, p2t.y).abs().max_component();
+        let delta_x = pbrt::gamma(5) * (max_xt + max_zt);
+        let delta_y = pbrt::gamma(5) * (max_yt + max_zt);
+        let delta_e = 2.0 * (pbrt::gamma(2) * max_xt * max_yt + delta_y * max_xt + delta_x * max_yt);
+        let max_e = Vector3f::new(e0, e1, e2).abs().max_component();
+        let delta_t = 3.0 * (pbrt::gamma(3) * max_e * max_zt + delta_e * max_zt + delta_z * max_e) * inv_det.abs();
+        if t <= delta_t {
+            return None;
+        }
+
+        return Some((t, b0, b1, b2));
+    }
+}
+
+impl Shape for Triangle {
+    fn world_bound(&self) -> Bounds3f {
+        let (p0, p1, p2) = self.positions();
+        return Bounds3f::new(p0, p1).union_point(&p2);
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(f64, SurfaceInteraction)> {
+        let (t, b0, b1, b2) = self.intersect_barycentric(ray)?;
+        let (p0, p1, p2) = self.positions();
+        let (uv0, uv1, uv2) = self.uvs();
+
+        // Triangle partial derivatives from the uv parameterization
+        let duv02 = uv0 - uv2;
+        let duv12 = uv1 - uv2;
+        let dp02 = p0 - p2;
+        let dp12 = p1 - p2;
+        let determinant = pbrt::difference_of_products(duv02.x, duv12.y, duv02.y, duv12.x);
+        let degenerate_uv = determinant.abs() < 1e-8;
+
+        let mut dpdu = Vector3f::new(0.0, 0.0, 0.0);
+        let mut dpdv = Vector3f::new(0.0, 0.0, 0.0);
+        if !degenerate_uv {
+            let inv_det = 1.0 / determinant;
+            dpdu = (dp02 * duv12.y - dp12 * duv02.y) * inv_det;
+            dpdv = (dp12 * duv02.x - dp02 * duv12.x) * inv_det;
+        }
+        if degenerate_uv || dpdu.cross(&dpdv).length_squared() == 0.0 {
+            let mut ng = (p2 - p0).cross(&(p1 - p0));
+            if ng.length_squared() == 0.0 {
+                return None;
+            }
+            ng.normalize();
+            let (u, v) = ng.coordinate_system();
+            dpdu = u;
+            dpdv = v;
+        }
+
+        let p_hit = p0 * b0 + p1 * b1 + p2 * b2;
+        let uv_hit = Point2f::new(
+            b0 * uv0.x + b1 * uv1.x + b2 * uv2.x,
+            b0 * uv0.y + b1 * uv1.y + b2 * uv2.y,
+        );
+
+        let zero = Normal3f::new(0.0, 0.0, 0.0);
+        let mut isect = SurfaceInteraction::new(p_hit, uv_hit, -ray.direction, dpdu, dpdv, zero, zero, ray.time);
+
+        // The geometric normal follows the vertex winding order
+        let mut n = Normal3f::from(dp02.cross(&dp12));
+        n.normalize();
+        if self.mesh.n.is_none() && self.mesh.transform_swaps_handedness {
+            n = -n;
+        }
+        isect.n = n;
+        isect.shading.n = n;
+
+        if let Some(ref normals) = self.mesh.n {
+            let (i0, i1, i2) = self.indices();
+            let (n0, n1, n2) = (normals[i0], normals[i1], normals[i2]);
+
+            // Interpolated shading normal
+            let mut ns = n0 * b0 + n1 * b1 + n2 * b2;
+            if ns.length_squared() > 0.0 {
+                ns.normalize();
+            } else {
+                ns = isect.n;
+            }
+
+            // Shading tangent, either from the mesh or the parametric derivative
+            let mut ss = match self.mesh.s {
+                Some(ref s) => s[i0] * b0 + s[i1] * b1 + s[i2] * b2,
+                None => isect.dpdu,
+            };
+            if ss.length_squared() == 0.0 {
+                ss = isect.dpdu;
+            }
+
+            // Bitangent, then re-orthogonalize the tangent
+            let ns_v = Vector3f::from(ns);
+            let mut ts = ss.cross(&ns_v);
+            if ts.length_squared() > 0.0 {
+                ts.normalize();
+                ss = ts.cross(&ns_v);
+            } else {
+                let (s, t) = ns_v.coordinate_system();
+                ss = s;
+                ts = t;
+            }
+
+            // Normal derivatives from the vertex normals
+            let (dndu, dndv) = if degenerate_uv {
+                let dn = Vector3f::from(n2 - n0).cross(&Vector3f::from(n1 - n0));
+                if dn.length_squared() == 0.0 {
+                    (zero, zero)
+                } else {
+                    let (dnu, dnv) = dn.coordinate_system();
+                    (Normal3f::from(dnu), Normal3f::from(dnv))
+                }
+            } else {
+                let inv_det = 1.0 / determinant;
+                let dn1 = n0 - n2;
+                let dn2 = n1 - n2;
+                (
+                    (dn1 * duv12.y - dn2 * duv02.y) * inv_det,
+                    (dn2 * duv02.x - dn1 * duv12.x) * inv_det,
+                )
+            };
+
+            isect.set_shading_geometry(ss, ts, dndu, dndv, true);
+        }
+
+        return Some((t, isect));
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        return self.intersect_barycentric(ray).is_some();
+    }
+
+    fn area(&self) -> f64 {
+        let (p0, p1, p2) = self.positions();
+        return 0.5 * (p1 - p0).cross(&(p2 - p0)).length();
+    }
+
+    fn sample(&self, u: &Point2f) -> Interaction {
+        let b = sampling::uniform_sample_triangle(u);
+        let (p0, p1, p2) = self.positions();
+        let p = p0 * b.x + p1 * b.y + p2 * (1.0 - b.x - b.y);
+
+        let mut n = Normal3f::from((p1 - p0).cross(&(p2 - p0)));
+        n.normalize();
+        if let Some(ref normals) = self.mesh.n {
+            let (i0, i1, i2) = self.indices();
+            let ns = normals[i0] * b.x + normals[i1] * b.y + normals[i2] * (1.0 - b.x - b.y);
+            n = n.face_forward(&ns);
+        } else if self.mesh.transform_swaps_handedness {
+            n = -n;
+        }
+
+        return Interaction::new(p, n, Vector3f::new(0.0, 0.0, 0.0), 0.0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::Vector3f;
+    use pbrt::geometry::ray::Ray;
+    use pbrt::geometry::shape::Shape;
+    use pbrt::geometry::transform::Transform;
+    use pbrt::geometry::triangle;
+    use pbrt::geometry::triangle::Triangle;
+    use pbrt::geometry::triangle::TriangleMesh;
+    use pbrt::rng::Rng;
+
+    // The unit square at z = 0 as two triangles sharing the diagonal from
+    // (1, 0) to (0, 1).
+    fn square(uv: Option<Vec<Point2f>>) -> Vec<Triangle> {
+        let p = vec![
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+            Point3f::new(1.0, 1.0, 0.0),
+        ];
+        let mesh = TriangleMesh::new(&Transform::identity(), vec![0, 1, 2, 1, 3, 2], p, None, None, uv).unwrap();
+        return triangle::create_triangles(&Arc::new(mesh));
+    }
+
+    fn down_from(x: f64, y: f64) -> Ray {
+        return Ray::new(Point3f::new(x, y, 1.0), Vector3f::new(0.0, 0.0, -1.0), f64::INFINITY, 0.0);
+    }
+
+    #[test]
+    fn hits_report_distance_barycentrics_and_uv() {
+        let uv = vec![Point2f::new(0.0, 0.0), Point2f::new(2.0, 0.0), Point2f::new(0.0, 4.0), Point2f::new(2.0, 4.0)];
+        let triangles = square(Some(uv));
+
+        let (t, b0, b1, b2) = triangles[0].intersect_barycentric(&down_from(0.25, 0.5)).unwrap();
+        assert_eq!((t, b0, b1, b2), (1.0, 0.25, 0.25, 0.5));
+
+        let (t, isect) = triangles[0].intersect(&down_from(0.25, 0.5)).unwrap();
+        assert_eq!(t, 1.0);
+        assert!(isect.p == Point3f::new(0.25, 0.5, 0.0));
+        assert!(isect.uv == Point2f::new(0.5, 2.0));
+        // Counter-clockwise winding seen from +z
+        assert_eq!(isect.n.z, 1.0);
+        assert!(isect.dpdu == Vector3f::new(0.5, 0.0, 0.0) && isect.dpdv == Vector3f::new(0.0, 0.25, 0.0));
+
+        // Past the hypotenuse, behind the origin and beyond t_max
+        assert!(triangles[0].intersect(&down_from(0.75, 0.5)).is_none());
+        let mut away = down_from(0.25, 0.25);
+        away.direction = -away.direction;
+        assert!(triangles[0].intersect(&away).is_none());
+        let mut short = down_from(0.25, 0.25);
+        short.t_max = 0.5;
+        assert!(triangles[0].intersect(&short).is_none());
+    }
+
+    #[test]
+    fn edges_and_vertices_are_watertight() {
+        let triangles = square(None);
+
+        // Exactly on the shared edge, and on the corners
+        for i in 0..=16 {
+            let x = i as f64 / 16.0;
+            let ray = down_from(x, 1.0 - x);
+            assert!(triangles.iter().any(|tri| tri.intersect(&ray).is_some()), "ray through ({}, {}) fell through", x, 1.0 - x);
+        }
+        assert!(triangles[0].intersect(&down_from(0.0, 0.0)).is_some());
+        assert!(triangles[1].intersect(&down_from(1.0, 1.0)).is_some());
+
+        // Oblique rays aimed at points on the shared edge
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let x = rng.uniform_f64();
+            let target = Point3f::new(x, 1.0 - x, 0.0);
+            let origin = Point3f::new(rng.uniform_f64() * 4.0 - 2.0, rng.uniform_f64() * 4.0 - 2.0, 1.0 + rng.uniform_f64());
+            let ray = Ray::new(origin, target - origin, f64::INFINITY, 0.0);
+            assert!(triangles.iter().any(|tri| tri.intersect_p(&ray)));
+        }
+    }
+
+    #[test]
+    fn intersect_p_agrees_with_intersect() {
+        let triangles = square(None);
+        let mut rng = Rng::new(11);
+        for _ in 0..1000 {
+            let origin = Point3f::new(rng.uniform_f64() * 3.0 - 1.0, rng.uniform_f64() * 3.0 - 1.0, rng.uniform_f64() * 2.0 - 1.0);
+            let target = Point3f::new(rng.uniform_f64() * 3.0 - 1.0, rng.uniform_f64() * 3.0 - 1.0, 0.0);
+            let ray = Ray::new(origin, target - origin, rng.uniform_f64() * 2.0, 0.0);
+            for tri in triangles.iter() {
+                assert_eq!(tri.intersect_p(&ray), tri.intersect(&ray).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_meshes_are_rejected() {
+        let p = vec![Point3f::new(0.0, 0.0, 0.0), Point3f::new(1.0, 0.0, 0.0), Point3f::new(0.0, 1.0, 0.0)];
+        let identity = Transform::identity();
+        assert!(TriangleMesh::new(&identity, vec![0, 1, 2], p.clone(), None, None, None).is_ok());
+        assert!(TriangleMesh::new(&identity, vec![0, 1, 2, 0], p.clone(), None, None, None).is_err());
+        assert!(TriangleMesh::new(&identity, vec![0, 1, 3], p.clone(), None, None, None).is_err());
+        let uv = vec![Point2f::new(0.0, 0.0); 2];
+        assert!(TriangleMesh::new(&identity, vec![0, 1, 2], p, None, None, Some(uv)).is_err());
+    }
+}
@@ -1,20 +1,25 @@
 use std::cmp;
 use std::ops;
 
-use num_traits::{Float, One, Zero};
+use num_traits::{Float, Num, One, Zero};
+
+use pbrt::geometry::normal::Normal3;
+use pbrt::geometry::point::Point3;
 
 
 #[derive(Clone, Copy, Debug)]
-pub struct Vector2<T: Float> {
+pub struct Vector2<T> {
     pub x: T,
     pub y: T,
 }
 
-impl<T: Float> Vector2<T> {
+impl<T> Vector2<T> {
     pub fn new(x: T, y: T) -> Vector2<T> {
         Vector2 { x, y }
     }
+}
 
+impl<T: Float> Vector2<T> {
     pub fn length_squared(&self) -> T {
         return self.x * self.x + self.y * self.y;
     }
@@ -25,7 +30,7 @@ impl<T: Float> Vector2<T> {
 }
 
 
-impl<T: Float> ops::Add<Vector2<T>> for Vector2<T> {
+impl<T: Num + Copy> ops::Add<Vector2<T>> for Vector2<T> {
     type Output = Vector2<T>;
 
     fn add(self, rhs: Vector2<T>) -> Vector2<T> {
@@ -36,7 +41,7 @@ impl<T: Float> ops::Add<Vector2<T>> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::AddAssign<Vector2<T>> for Vector2<T> {
+impl<T: Num + Copy> ops::AddAssign<Vector2<T>> for Vector2<T> {
     fn add_assign(&mut self, other: Vector2<T>) {
         *self = Vector2 {
             x: self.x + other.x,
@@ -45,7 +50,7 @@ impl<T: Float> ops::AddAssign<Vector2<T>> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::Sub<Vector2<T>> for Vector2<T> {
+impl<T: Num + Copy> ops::Sub<Vector2<T>> for Vector2<T> {
     type Output = Vector2<T>;
 
     fn sub(self, rhs: Vector2<T>) -> Vector2<T> {
@@ -56,7 +61,7 @@ impl<T: Float> ops::Sub<Vector2<T>> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::SubAssign<Vector2<T>> for Vector2<T> {
+impl<T: Num + Copy> ops::SubAssign<Vector2<T>> for Vector2<T> {
     fn sub_assign(&mut self, rhs: Vector2<T>) {
         *self = Vector2::new(
             self.x - rhs.x,
@@ -65,7 +70,7 @@ impl<T: Float> ops::SubAssign<Vector2<T>> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::Mul<T> for Vector2<T> {
+impl<T: Num + Copy> ops::Mul<T> for Vector2<T> {
     type Output = Vector2<T>;
 
     fn mul(self, rhs: T) -> Vector2<T> {
@@ -76,7 +81,7 @@ impl<T: Float> ops::Mul<T> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::MulAssign<T> for Vector2<T> {
+impl<T: Num + Copy> ops::MulAssign<T> for Vector2<T> {
     fn mul_assign(&mut self, rhs: T) {
         *self = Vector2::new(
             self.x * rhs,
@@ -85,7 +90,7 @@ impl<T: Float> ops::MulAssign<T> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::Div<T> for Vector2<T> {
+impl<T: Num + Copy> ops::Div<T> for Vector2<T> {
     type Output = Vector2<T>;
 
     fn div(self, rhs: T) -> Vector2<T> {
@@ -100,7 +105,7 @@ impl<T: Float> ops::Div<T> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::DivAssign<T> for Vector2<T> {
+impl<T: Num + Copy> ops::DivAssign<T> for Vector2<T> {
     fn div_assign(&mut self, rhs: T) {
         if rhs == Zero::zero() {
             // TODO: raise error?
@@ -114,7 +119,7 @@ impl<T: Float> ops::DivAssign<T> for Vector2<T> {
     }
 }
 
-impl<T: Float> ops::Index<u8> for Vector2<T> {
+impl<T> ops::Index<u8> for Vector2<T> {
     type Output = T;
 
     fn index(&self, index: u8) -> &T {
@@ -126,7 +131,7 @@ impl<T: Float> ops::Index<u8> for Vector2<T> {
     }
 }
 
-impl<T: Float> cmp::PartialEq<Vector2<T>> for Vector2<T> {
+impl<T: PartialEq> cmp::PartialEq<Vector2<T>> for Vector2<T> {
     fn eq(&self, other: &Vector2<T>) -> bool {
         return self.x == other.x && self.y == other.y;
     }
@@ -169,6 +174,67 @@ impl<T: Float> Vector3<T> {
     pub fn dot(&self, other: &Vector3<T>) -> T {
         return self.x * other.x + self.y * other.y + self.z * other.z;
     }
+
+    pub fn cross(&self, other: &Vector3<T>) -> Vector3<T> {
+        return Vector3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        );
+    }
+
+    pub fn abs(&self) -> Vector3<T> {
+        return Vector3::new(self.x.abs(), self.y.abs(), self.z.abs());
+    }
+
+    pub fn max_component(&self) -> T {
+        return self.x.max(self.y.max(self.z));
+    }
+
+    pub fn max_dimension(&self) -> u8 {
+        if self.x > self.y {
+            if self.x > self.z { 0 } else { 2 }
+        } else if self.y > self.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn permute(&self, x: u8, y: u8, z: u8) -> Vector3<T> {
+        return Vector3::new(self[x], self[y], self[z]);
+    }
+
+    // Builds two vectors that form an orthonormal basis with this (normalized) vector.
+    pub fn coordinate_system(&self) -> (Vector3<T>, Vector3<T>) {
+        let v2 = if self.x.abs() > self.y.abs() {
+            Vector3::new(-self.z, T::zero(), self.x) / (self.x * self.x + self.z * self.z).sqrt()
+        } else {
+            Vector3::new(T::zero(), self.z, -self.y) / (self.y * self.y + self.z * self.z).sqrt()
+        };
+        let v3 = self.cross(&v2);
+        return (v2, v3);
+    }
+}
+
+impl<T: Float> From<Point3<T>> for Vector3<T> {
+    fn from(p: Point3<T>) -> Vector3<T> {
+        return Vector3::new(p.x, p.y, p.z);
+    }
+}
+
+impl<T: Float> From<Normal3<T>> for Vector3<T> {
+    fn from(n: Normal3<T>) -> Vector3<T> {
+        return Vector3::new(n.x, n.y, n.z);
+    }
+}
+
+impl<T: Float> ops::Neg for Vector3<T> {
+    type Output = Vector3<T>;
+
+    fn neg(self) -> Vector3<T> {
+        return Vector3::new(-self.x, -self.y, -self.z);
+    }
 }
 
 impl<T: Float> ops::Add<Vector3<T>> for Vector3<T> {
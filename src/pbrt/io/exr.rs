@@ -0,0 +1,1333 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use flate2;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use pbrt::io::FileError;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelType {
+    UInt,
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn from_code(code: i32) -> Option<PixelType> {
+        match code {
+            0 => Some(PixelType::UInt),
+            1 => Some(PixelType::Half),
+            2 => Some(PixelType::Float),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> i32 {
+        match *self {
+            PixelType::UInt => 0,
+            PixelType::Half => 1,
+            PixelType::Float => 2,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            PixelType::Half => 2,
+            PixelType::UInt | PixelType::Float => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Rle,
+    // Zlib, one scanline per block
+    Zips,
+    // Zlib, 16 scanlines per block
+    Zip,
+    // Wavelet and Huffman, 32 scanlines per block
+    Piz,
+}
+
+impl Compression {
+    fn from_code(code: u8) -> Option<Compression> {
+        match code {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Rle),
+            2 => Some(Compression::Zips),
+            3 => Some(Compression::Zip),
+            4 => Some(Compression::Piz),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match *self {
+            Compression::None => 0,
+            Compression::Rle => 1,
+            Compression::Zips => 2,
+            Compression::Zip => 3,
+            Compression::Piz => 4,
+        }
+    }
+
+    fn lines_per_block(&self) -> usize {
+        match *self {
+            Compression::None | Compression::Rle | Compression::Zips => 1,
+            Compression::Zip => 16,
+            Compression::Piz => 32,
+        }
+    }
+}
+
+
+// One named image plane, stored as f32 in memory whatever its file type.
+#[derive(Clone, Debug)]
+pub struct ExrChannel {
+    pub name: String,
+    pub pixel_type: PixelType,
+    pub data: Vec<f32>,
+}
+
+// A scanline image with any number of named channels, e.g. "R", "G", "B"
+// plus AOVs such as "Z" or "normal.X".
+#[derive(Clone, Debug)]
+pub struct ExrImage {
+    pub width: usize,
+    pub height: usize,
+    pub channels: Vec<ExrChannel>,
+}
+
+impl ExrImage {
+    pub fn new(width: usize, height: usize) -> ExrImage {
+        ExrImage { width, height, channels: Vec::new() }
+    }
+
+    // `data` holds width * height values in scanline order.
+    pub fn add_channel(&mut self, name: &str, pixel_type: PixelType, data: Vec<f32>) -> Result<(), String> {
+        if data.len() != self.width * self.height {
+            return Err(format!("channel {} has {} values for a {}x{} image", name, data.len(), self.width, self.height));
+        }
+        self.channels.push(ExrChannel { name: name.to_string(), pixel_type, data });
+        return Ok(());
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&ExrChannel> {
+        return self.channels.iter().find(|c| c.name == name);
+    }
+}
+
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const TILED_FLAG: u32 = 0x200;
+const NON_IMAGE_FLAG: u32 = 0x800;
+const MULTI_PART_FLAG: u32 = 0x1000;
+// Deflate's best case: a 258 byte match in two bits
+const MAX_COMPRESSION_RATIO: usize = 1032;
+
+pub fn save_exr<P: AsRef<Path>>(path: P, image: &ExrImage, compression: Compression) -> Result<(), FileError> {
+    let path = path.as_ref();
+    let err = |e: ::std::io::Error| FileError::new(path, e.to_string());
+    let mut writer = BufWriter::new(File::create(path).map_err(&err)?);
+    write_exr(&mut writer, image, compression).map_err(&err)?;
+    return writer.flush().map_err(&err);
+}
+
+pub fn write_exr<W: Write>(writer: &mut W, image: &ExrImage, compression: Compression) -> ::std::io::Result<()> {
+    // Channels are stored sorted by name
+    let mut channels: Vec<&ExrChannel> = image.channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for c in channels.iter() {
+        chlist.extend_from_slice(c.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&c.pixel_type.code().to_le_bytes());
+        // pLinear and reserved bytes, then x and y sampling
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+
+    let mut window = Vec::new();
+    for v in [0, 0, image.width as i32 - 1, image.height as i32 - 1].iter() {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    let mut center = Vec::new();
+    center.extend_from_slice(&0f32.to_le_bytes());
+    center.extend_from_slice(&0f32.to_le_bytes());
+
+    write_attribute(&mut header, "channels", "chlist", &chlist);
+    write_attribute(&mut header, "compression", "compression", &[compression.code()]);
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &center);
+    write_attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let lines_per_block = compression.lines_per_block();
+    let mut chunks = Vec::new();
+    for y0 in (0..image.height).step_by(lines_per_block) {
+        let y1 = (y0 + lines_per_block).min(image.height);
+        let raw = pack_block(image.width, y0, y1, &channels);
+        let compressed = compress(compression, &raw, image.width, y1 - y0, &channels)?;
+
+        // Blocks that do not shrink are stored as is
+        let data = if compressed.len() < raw.len() { compressed } else { raw };
+        let mut chunk = Vec::with_capacity(data.len() + 8);
+        chunk.extend_from_slice(&(y0 as i32).to_le_bytes());
+        chunk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        chunk.extend_from_slice(&data);
+        chunks.push(chunk);
+    }
+
+    writer.write_all(&header)?;
+    let mut offset = (header.len() + 8 * chunks.len()) as u64;
+    for chunk in chunks.iter() {
+        writer.write_all(&offset.to_le_bytes())?;
+        offset += chunk.len() as u64;
+    }
+    for chunk in chunks.iter() {
+        writer.write_all(chunk)?;
+    }
+    return Ok(());
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(type_name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// Interleaves scanlines y0..y1 as the file stores them: each line holds
+// every channel's values in turn.
+fn pack_block(width: usize, y0: usize, y1: usize, channels: &[&ExrChannel]) -> Vec<u8> {
+    let mut raw = Vec::new();
+    for y in y0..y1 {
+        for c in channels.iter() {
+            for &v in c.data[y * width..(y + 1) * width].iter() {
+                match c.pixel_type {
+                    PixelType::UInt => raw.extend_from_slice(&(v.max(0.0) as u32).to_le_bytes()),
+                    PixelType::Half => raw.extend_from_slice(&f32_to_half(v).to_le_bytes()),
+                    PixelType::Float => raw.extend_from_slice(&v.to_le_bytes()),
+                }
+            }
+        }
+    }
+    return raw;
+}
+
+fn compress(compression: Compression, raw: &[u8], width: usize, lines: usize, channels: &[&ExrChannel]) -> ::std::io::Result<Vec<u8>> {
+    return match compression {
+        Compression::None => Ok(raw.to_vec()),
+        Compression::Rle => Ok(rle_compress(&predict(raw))),
+        Compression::Zips | Compression::Zip => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&predict(raw))?;
+            encoder.finish()
+        }
+        Compression::Piz => {
+            let types: Vec<PixelType> = channels.iter().map(|c| c.pixel_type).collect();
+            Ok(piz_compress(raw, width, lines, &types))
+        }
+    };
+}
+
+
+pub fn load_exr<P: AsRef<Path>>(path: P) -> Result<ExrImage, FileError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| FileError::new(path, e.to_string()))?;
+    return read_exr(file, path);
+}
+
+// Reads single-part scanline files with any of the compressions above.
+pub fn read_exr<R: Read>(mut reader: R, path: &Path) -> Result<ExrImage, FileError> {
+    let err = |message: String| FileError::new(path, message);
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|e| err(e.to_string()))?;
+
+    let mut input = ByteReader { bytes: &bytes, pos: 0 };
+    if input.take(4).map_err(&err)? != MAGIC {
+        return Err(err("not an OpenEXR file".to_string()));
+    }
+    let version = input.u32().map_err(&err)?;
+    if version & 0xff != 2 {
+        return Err(err(format!("unsupported version {}", version & 0xff)));
+    }
+    if version & (TILED_FLAG | NON_IMAGE_FLAG | MULTI_PART_FLAG) != 0 {
+        return Err(err("only single-part scanline files are supported".to_string()));
+    }
+
+    let mut channels: Option<Vec<(String, PixelType)>> = None;
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = input.c_string().map_err(&err)?;
+        if name.is_empty() {
+            break;
+        }
+        let _type_name = input.c_string().map_err(&err)?;
+        let size = input.i32().map_err(&err)?;
+        let value = input.take(size.max(0) as usize).map_err(&err)?;
+        let mut value = ByteReader { bytes: value, pos: 0 };
+
+        match &name[..] {
+            "channels" => {
+                let mut list = Vec::new();
+                loop {
+                    let channel_name = value.c_string().map_err(&err)?;
+                    if channel_name.is_empty() {
+                        break;
+                    }
+                    let code = value.i32().map_err(&err)?;
+                    let pixel_type = PixelType::from_code(code).ok_or_else(|| err(format!("unknown pixel type {}", code)))?;
+                    value.take(4).map_err(&err)?;
+                    let (x_sampling, y_sampling) = (value.i32().map_err(&err)?, value.i32().map_err(&err)?);
+                    if x_sampling != 1 || y_sampling != 1 {
+                        return Err(err(format!("subsampled channel {} is not supported", channel_name)));
+                    }
+                    list.push((channel_name, pixel_type));
+                }
+                channels = Some(list);
+            }
+            "compression" => {
+                let code = value.u8().map_err(&err)?;
+                compression = Some(Compression::from_code(code).ok_or_else(|| err(format!("unsupported compression {}", code)))?);
+            }
+            "dataWindow" => {
+                let mut w = [0i32; 4];
+                for v in w.iter_mut() {
+                    *v = value.i32().map_err(&err)?;
+                }
+                data_window = Some(w);
+            }
+            _ => {}
+        }
+    }
+
+    let channels = channels.ok_or_else(|| err("missing channels attribute".to_string()))?;
+    let compression = compression.ok_or_else(|| err("missing compression attribute".to_string()))?;
+    let [x_min, y_min, x_max, y_max] = data_window.ok_or_else(|| err("missing dataWindow attribute".to_string()))?;
+    if x_max < x_min || y_max < y_min {
+        return Err(err("empty data window".to_string()));
+    }
+    // Widen before subtracting: a hostile window can span all of i32
+    let width = (x_max as i64 - x_min as i64 + 1) as usize;
+    let height = (y_max as i64 - y_min as i64 + 1) as usize;
+    let types: Vec<PixelType> = channels.iter().map(|c| c.1).collect();
+    let bytes_per_line = width
+        .checked_mul(types.iter().map(|t| t.size()).sum::<usize>())
+        .ok_or_else(|| err("data window is too large".to_string()))?;
+
+    // Check the claimed size against the file before allocating for it.
+    // Every block needs an 8 byte offset, and no codec here packs more than
+    // deflate's 1032:1.
+    let lines_per_block = compression.lines_per_block();
+    let n_chunks = height.div_ceil(lines_per_block);
+    let fits = match (bytes_per_line.checked_mul(height), width.checked_mul(height)) {
+        (Some(raw_size), Some(_)) => n_chunks.saturating_mul(8) <= bytes.len() && raw_size / MAX_COMPRESSION_RATIO <= bytes.len(),
+        _ => false,
+    };
+    if !fits {
+        return Err(err(format!("{}x{} data window is too large for a {} byte file", width, height, bytes.len())));
+    }
+
+    let mut image = ExrImage::new(width, height);
+    for &(ref name, pixel_type) in channels.iter() {
+        image.add_channel(name, pixel_type, vec![0.0; width * height]).map_err(&err)?;
+    }
+    let mut offsets = Vec::with_capacity(n_chunks);
+    for _ in 0..n_chunks {
+        offsets.push(input.u64().map_err(&err)? as usize);
+    }
+
+    for offset in offsets {
+        let mut chunk = ByteReader { bytes: &bytes, pos: offset };
+        let y0 = chunk.i32().map_err(&err)?;
+        let size = chunk.i32().map_err(&err)?;
+        let data = chunk.take(size.max(0) as usize).map_err(&err)?;
+
+        if y0 < y_min || y0 > y_max {
+            return Err(err(format!("block at scanline {} is outside the data window", y0)));
+        }
+        let y0 = (y0 - y_min) as usize;
+        let lines = lines_per_block.min(height - y0);
+        let expected = lines * bytes_per_line;
+
+        let raw = if data.len() == expected {
+            data.to_vec()
+        } else {
+            decompress(compression, data, width, lines, &types, expected).map_err(&err)?
+        };
+        if raw.len() != expected {
+            return Err(err(format!("block at scanline {} has {} bytes, expected {}", y0, raw.len(), expected)));
+        }
+
+        let mut pixels = ByteReader { bytes: &raw, pos: 0 };
+        for y in y0..y0 + lines {
+            for c in image.channels.iter_mut() {
+                for x in 0..width {
+                    c.data[y * width + x] = match c.pixel_type {
+                        PixelType::UInt => pixels.u32().map_err(&err)? as f32,
+                        PixelType::Half => half_to_f32(pixels.u16().map_err(&err)?),
+                        PixelType::Float => f32::from_bits(pixels.u32().map_err(&err)?),
+                    };
+                }
+            }
+        }
+    }
+
+    return Ok(image);
+}
+
+fn decompress(compression: Compression, data: &[u8], width: usize, lines: usize, types: &[PixelType], expected: usize) -> Result<Vec<u8>, String> {
+    return match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Rle => Ok(unpredict(&rle_uncompress(data, expected)?)),
+        Compression::Zips | Compression::Zip => {
+            // Stop one byte past the expected size; the caller reports the mismatch
+            let mut out = Vec::with_capacity(expected);
+            ZlibDecoder::new(data).take(expected as u64 + 1).read_to_end(&mut out).map_err(|e| e.to_string())?;
+            Ok(unpredict(&out))
+        }
+        Compression::Piz => piz_uncompress(data, width, lines, types),
+    };
+}
+
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.bytes.len().saturating_sub(self.pos) {
+            return Err("unexpected end of file".to_string());
+        }
+        let s = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        return Ok(s);
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        return Ok(u16::from_le_bytes([b[0], b[1]]));
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        return Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        return Ok(self.u32()? as i32);
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let b = self.take(8)?;
+        return Ok(u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]));
+    }
+
+    fn c_string(&mut self) -> Result<String, String> {
+        let rest = &self.bytes[self.pos.min(self.bytes.len())..];
+        let end = rest.iter().position(|&b| b == 0).ok_or_else(|| "unterminated string".to_string())?;
+        self.pos += end + 1;
+        return Ok(String::from_utf8_lossy(&rest[..end]).into_owned());
+    }
+}
+
+
+// IEEE 754 binary16, rounding to nearest even.
+fn f32_to_half(value: f32) -> u16 {
+    let x = value.to_bits();
+    let sign = ((x >> 16) & 0x8000) as u16;
+    let exp = ((x >> 23) & 0xff) as i32;
+    let mant = x & 0x007f_ffff;
+
+    if exp == 0xff {
+        // Keep NaNs NaN even if their payload is in the low bits
+        let nan = if mant != 0 { 0x0200 | (mant >> 13) as u16 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let round = |m: u32, shift: u32| {
+        let half_m = m >> shift;
+        let round_bit = 1u32 << (shift - 1);
+        let sticky = m & (round_bit - 1);
+        if m & round_bit != 0 && (sticky != 0 || half_m & 1 != 0) {
+            return half_m + 1;
+        }
+        return half_m;
+    };
+
+    if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        // Subnormal; rounding up may carry into the smallest normal
+        return sign | round(mant | 0x0080_0000, (14 - e) as u32) as u16;
+    }
+    return sign | round(((e as u32) << 23) | mant, 13) as u16;
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let mant = (h & 0x03ff) as u32;
+
+    let bits = if exp == 0 {
+        if mant == 0 {
+            sign
+        } else {
+            let mut e = 127 - 15 + 1;
+            let mut m = mant;
+            while m & 0x0400 == 0 {
+                m <<= 1;
+                e -= 1;
+            }
+            sign | (e << 23) | ((m & 0x03ff) << 13)
+        }
+    } else if exp == 0x1f {
+        sign | 0x7f80_0000 | (mant << 13)
+    } else {
+        sign | ((exp + 127 - 15) << 23) | (mant << 13)
+    };
+    return f32::from_bits(bits);
+}
+
+
+// ZIP and RLE first split the bytes into two halves (even then odd
+// positions) and delta encode them, which helps with multi-byte values.
+fn predict(raw: &[u8]) -> Vec<u8> {
+    let half = raw.len().div_ceil(2);
+    let mut t = vec![0u8; raw.len()];
+    for (i, &b) in raw.iter().enumerate() {
+        if i % 2 == 0 {
+            t[i / 2] = b;
+        } else {
+            t[half + i / 2] = b;
+        }
+    }
+    for i in (1..t.len()).rev() {
+        t[i] = t[i].wrapping_sub(t[i - 1]).wrapping_add(128);
+    }
+    return t;
+}
+
+fn unpredict(data: &[u8]) -> Vec<u8> {
+    let mut t = data.to_vec();
+    for i in 1..t.len() {
+        t[i] = t[i - 1].wrapping_add(t[i]).wrapping_sub(128);
+    }
+
+    let half = t.len().div_ceil(2);
+    let mut raw = vec![0u8; t.len()];
+    for (i, b) in raw.iter_mut().enumerate() {
+        *b = if i % 2 == 0 { t[i / 2] } else { t[half + i / 2] };
+    }
+    return raw;
+}
+
+// Runs of 3 or more bytes become (count - 1, byte); other stretches become
+// (-length, bytes...).
+fn rle_compress(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN_LENGTH: usize = 3;
+    const MAX_RUN_LENGTH: usize = 127;
+
+    let mut out = Vec::new();
+    let mut run_start = 0;
+    let mut run_end = 1;
+    while run_start < data.len() {
+        while run_end < data.len() && data[run_start] == data[run_end] && run_end - run_start - 1 < MAX_RUN_LENGTH {
+            run_end += 1;
+        }
+        if run_end - run_start >= MIN_RUN_LENGTH {
+            out.push((run_end - run_start - 1) as u8);
+            out.push(data[run_start]);
+            run_start = run_end;
+        } else {
+            while run_end < data.len()
+                && ((run_end + 1 >= data.len() || data[run_end] != data[run_end + 1])
+                    || (run_end + 2 >= data.len() || data[run_end + 1] != data[run_end + 2]))
+                && run_end - run_start < MAX_RUN_LENGTH
+            {
+                run_end += 1;
+            }
+            out.push((run_start as isize - run_end as isize) as i8 as u8);
+            out.extend_from_slice(&data[run_start..run_end]);
+            run_start = run_end;
+        }
+        run_end += 1;
+    }
+    return out;
+}
+
+fn rle_uncompress(data: &[u8], max_length: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(max_length);
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let n = -(count as isize) as usize;
+            if i + n > data.len() {
+                return Err("truncated RLE data".to_string());
+            }
+            out.extend_from_slice(&data[i..i + n]);
+            i += n;
+        } else {
+            let b = *data.get(i).ok_or_else(|| "truncated RLE data".to_string())?;
+            out.extend(std::iter::repeat_n(b, count as usize + 1));
+            i += 1;
+        }
+        if out.len() > max_length {
+            return Err("RLE data overflows block".to_string());
+        }
+    }
+    return Ok(out);
+}
+
+
+const USHORT_RANGE: usize = 1 << 16;
+const BITMAP_SIZE: usize = USHORT_RANGE >> 3;
+
+// PIZ works on 16-bit words, with each channel's words gathered into its own
+// plane: nx words per line for half, 2 * nx for 32-bit types.
+fn piz_planes(raw: &[u8], width: usize, lines: usize, types: &[PixelType]) -> Vec<u16> {
+    let mut words = vec![0u16; raw.len() / 2];
+    let mut starts = Vec::with_capacity(types.len());
+    let mut start = 0;
+    for t in types.iter() {
+        starts.push(start);
+        start += width * lines * t.size() / 2;
+    }
+
+    let mut pos = 0;
+    for y in 0..lines {
+        for (c, t) in types.iter().enumerate() {
+            let n = width * t.size() / 2;
+            for i in 0..n {
+                words[starts[c] + y * n + i] = u16::from_le_bytes([raw[pos], raw[pos + 1]]);
+                pos += 2;
+            }
+        }
+    }
+    return words;
+}
+
+fn piz_scanlines(words: &[u16], width: usize, lines: usize, types: &[PixelType]) -> Vec<u8> {
+    let mut starts = Vec::with_capacity(types.len());
+    let mut start = 0;
+    for t in types.iter() {
+        starts.push(start);
+        start += width * lines * t.size() / 2;
+    }
+
+    let mut raw = Vec::with_capacity(words.len() * 2);
+    for y in 0..lines {
+        for (c, t) in types.iter().enumerate() {
+            let n = width * t.size() / 2;
+            for &w in words[starts[c] + y * n..starts[c] + (y + 1) * n].iter() {
+                raw.extend_from_slice(&w.to_le_bytes());
+            }
+        }
+    }
+    return raw;
+}
+
+fn piz_compress(raw: &[u8], width: usize, lines: usize, types: &[PixelType]) -> Vec<u8> {
+    let mut words = piz_planes(raw, width, lines, types);
+
+    // Remap the values that occur onto a dense range; zero is always assumed
+    let mut bitmap = vec![0u8; BITMAP_SIZE];
+    for &w in words.iter() {
+        bitmap[(w >> 3) as usize] |= 1 << (w & 7);
+    }
+    bitmap[0] &= !1;
+    let min_non_zero = bitmap.iter().position(|&b| b != 0).unwrap_or(BITMAP_SIZE - 1);
+    let max_non_zero = bitmap.iter().rposition(|&b| b != 0).unwrap_or(0);
+
+    let mut lut = vec![0u16; USHORT_RANGE];
+    let mut k = 0u32;
+    for (i, l) in lut.iter_mut().enumerate() {
+        if i == 0 || bitmap[i >> 3] & (1 << (i & 7)) != 0 {
+            *l = k as u16;
+            k += 1;
+        }
+    }
+    let max_value = (k - 1) as u16;
+    for w in words.iter_mut() {
+        *w = lut[*w as usize];
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&(min_non_zero as u16).to_le_bytes());
+    out.extend_from_slice(&(max_non_zero as u16).to_le_bytes());
+    if min_non_zero <= max_non_zero {
+        out.extend_from_slice(&bitmap[min_non_zero..=max_non_zero]);
+    }
+
+    let mut start = 0;
+    for t in types.iter() {
+        let size = t.size() / 2;
+        let plane = &mut words[start..start + width * lines * size];
+        for j in 0..size {
+            wav2_encode(&mut plane[j..], width, size, lines, width * size, max_value);
+        }
+        start += width * lines * size;
+    }
+
+    let huffman = huf_compress(&words);
+    out.extend_from_slice(&(huffman.len() as i32).to_le_bytes());
+    out.extend_from_slice(&huffman);
+    return out;
+}
+
+fn piz_uncompress(data: &[u8], width: usize, lines: usize, types: &[PixelType]) -> Result<Vec<u8>, String> {
+    let mut input = ByteReader { bytes: data, pos: 0 };
+    let min_non_zero = input.u16()? as usize;
+    let max_non_zero = input.u16()? as usize;
+    if max_non_zero >= BITMAP_SIZE {
+        return Err("invalid PIZ bitmap".to_string());
+    }
+    let mut bitmap = vec![0u8; BITMAP_SIZE];
+    if min_non_zero <= max_non_zero {
+        bitmap[min_non_zero..=max_non_zero].copy_from_slice(input.take(max_non_zero - min_non_zero + 1)?);
+    }
+
+    let mut lut = vec![0u16; USHORT_RANGE];
+    let mut k = 0;
+    for i in 0..USHORT_RANGE {
+        if i == 0 || bitmap[i >> 3] & (1 << (i & 7)) != 0 {
+            lut[k] = i as u16;
+            k += 1;
+        }
+    }
+    let max_value = (k - 1) as u16;
+
+    let n_words: usize = types.iter().map(|t| width * lines * t.size() / 2).sum();
+    let length = input.i32()?;
+    let mut words = huf_uncompress(input.take(length.max(0) as usize)?, n_words)?;
+
+    let mut start = 0;
+    for t in types.iter() {
+        let size = t.size() / 2;
+        let plane = &mut words[start..start + width * lines * size];
+        for j in 0..size {
+            wav2_decode(&mut plane[j..], width, size, lines, width * size, max_value);
+        }
+        start += width * lines * size;
+    }
+
+    for w in words.iter_mut() {
+        *w = lut[*w as usize];
+    }
+    return Ok(piz_scanlines(&words, width, lines, types));
+}
+
+
+// Haar-like wavelet on 16-bit words. With values below 2^14 the plain
+// average and difference fit; otherwise they are taken modulo 2^16.
+fn wenc14(a: u16, b: u16) -> (u16, u16) {
+    let (a, b) = (a as i16 as i32, b as i16 as i32);
+    let m = (a + b) >> 1;
+    let d = a - b;
+    return (m as u16, d as u16);
+}
+
+fn wdec14(l: u16, h: u16) -> (u16, u16) {
+    let (l, h) = (l as i16 as i32, h as i16 as i32);
+    let a = l + (h & 1) + (h >> 1);
+    let b = a - h;
+    return (a as u16, b as u16);
+}
+
+const A_OFFSET: i32 = 1 << 15;
+const M_OFFSET: i32 = 1 << 15;
+const MOD_MASK: i32 = (1 << 16) - 1;
+
+fn wenc16(a: u16, b: u16) -> (u16, u16) {
+    let ao = (a as i32 + A_OFFSET) & MOD_MASK;
+    let mut m = (ao + b as i32) >> 1;
+    let d = ao - b as i32;
+    if d < 0 {
+        m = (m + M_OFFSET) & MOD_MASK;
+    }
+    return (m as u16, (d & MOD_MASK) as u16);
+}
+
+fn wdec16(l: u16, h: u16) -> (u16, u16) {
+    let (m, d) = (l as i32, h as i32);
+    let b = (m - (d >> 1)) & MOD_MASK;
+    let a = (d + b - A_OFFSET) & MOD_MASK;
+    return (a as u16, b as u16);
+}
+
+// 2D transform of an nx by ny array with element stride ox and line stride
+// oy, one level per power of two of the smaller dimension.
+fn wav2_encode(buf: &mut [u16], nx: usize, ox: usize, ny: usize, oy: usize, mx: u16) {
+    let enc: fn(u16, u16) -> (u16, u16) = if mx < (1 << 14) { wenc14 } else { wenc16 };
+    let n = nx.min(ny);
+    let mut p = 1;
+    let mut p2 = 2;
+    while p2 <= n {
+        let (ox1, ox2, oy1, oy2) = (ox * p, ox * p2, oy * p, oy * p2);
+        let mut py = 0;
+        while py + oy * p2 <= oy * ny {
+            let mut px = py;
+            while px + ox * p2 <= py + ox * nx {
+                let (p01, p10) = (px + ox1, px + oy1);
+                let p11 = p10 + ox1;
+                let (i00, i01) = enc(buf[px], buf[p01]);
+                let (i10, i11) = enc(buf[p10], buf[p11]);
+                let (a, b) = enc(i00, i10);
+                buf[px] = a;
+                buf[p10] = b;
+                let (a, b) = enc(i01, i11);
+                buf[p01] = a;
+                buf[p11] = b;
+                px += ox2;
+            }
+            // Odd column
+            if nx & p != 0 {
+                let p10 = px + oy1;
+                let (a, b) = enc(buf[px], buf[p10]);
+                buf[px] = a;
+                buf[p10] = b;
+            }
+            py += oy2;
+        }
+        // Odd line
+        if ny & p != 0 {
+            let mut px = py;
+            while px + ox * p2 <= py + ox * nx {
+                let p01 = px + ox1;
+                let (a, b) = enc(buf[px], buf[p01]);
+                buf[px] = a;
+                buf[p01] = b;
+                px += ox2;
+            }
+        }
+        p = p2;
+        p2 <<= 1;
+    }
+}
+
+fn wav2_decode(buf: &mut [u16], nx: usize, ox: usize, ny: usize, oy: usize, mx: u16) {
+    let dec: fn(u16, u16) -> (u16, u16) = if mx < (1 << 14) { wdec14 } else { wdec16 };
+    let n = nx.min(ny);
+    let mut p = 1;
+    while p <= n {
+        p <<= 1;
+    }
+    p >>= 1;
+    let mut p2 = p;
+    p >>= 1;
+
+    while p >= 1 {
+        let (ox1, ox2, oy1, oy2) = (ox * p, ox * p2, oy * p, oy * p2);
+        let mut py = 0;
+        while py + oy * p2 <= oy * ny {
+            let mut px = py;
+            while px + ox * p2 <= py + ox * nx {
+                let (p01, p10) = (px + ox1, px + oy1);
+                let p11 = p10 + ox1;
+                let (i00, i10) = dec(buf[px], buf[p10]);
+                let (i01, i11) = dec(buf[p01], buf[p11]);
+                let (a, b) = dec(i00, i01);
+                buf[px] = a;
+                buf[p01] = b;
+                let (a, b) = dec(i10, i11);
+                buf[p10] = a;
+                buf[p11] = b;
+                px += ox2;
+            }
+            if nx & p != 0 {
+                let p10 = px + oy1;
+                let (a, b) = dec(buf[px], buf[p10]);
+                buf[px] = a;
+                buf[p10] = b;
+            }
+            py += oy2;
+        }
+        if ny & p != 0 {
+            let mut px = py;
+            while px + ox * p2 <= py + ox * nx {
+                let p01 = px + ox1;
+                let (a, b) = dec(buf[px], buf[p01]);
+                buf[px] = a;
+                buf[p01] = b;
+                px += ox2;
+            }
+        }
+        p2 = p;
+        p >>= 1;
+    }
+}
+
+
+// Huffman coding of 16-bit words as used by PIZ. Code tables hold
+// (code << 6) | length for each symbol; symbol HUF_ENCSIZE - 1 is room for
+// the run-length pseudo-symbol that follows the largest value used.
+const HUF_ENCSIZE: usize = (1 << 16) + 1;
+const SHORT_ZEROCODE_RUN: u64 = 59;
+const LONG_ZEROCODE_RUN: u64 = 63;
+const SHORTEST_LONG_RUN: u64 = 2 + LONG_ZEROCODE_RUN - SHORT_ZEROCODE_RUN;
+const LONGEST_LONG_RUN: u64 = 255 + SHORTEST_LONG_RUN;
+
+fn huf_length(code: u64) -> u32 {
+    return (code & 63) as u32;
+}
+
+fn huf_code(code: u64) -> u64 {
+    return code >> 6;
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    c: u64,
+    lc: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, n_bits: u32, bits: u64) {
+        self.c = if n_bits >= 64 { 0 } else { self.c << n_bits } | bits;
+        self.lc += n_bits;
+        while self.lc >= 8 {
+            self.lc -= 8;
+            self.out.push((self.c >> self.lc) as u8);
+        }
+    }
+
+    fn write_code(&mut self, code: u64) {
+        self.write(huf_length(code), huf_code(code));
+    }
+
+    // Pads the final partial byte with zeros, returning the bits written.
+    fn finish(mut self) -> (Vec<u8>, usize) {
+        let n_bits = self.out.len() * 8 + self.lc as usize;
+        if self.lc > 0 {
+            self.out.push((self.c << (8 - self.lc)) as u8);
+        }
+        return (self.out, n_bits);
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, n_bits: u32) -> Result<u64, String> {
+        let mut v = 0u64;
+        for _ in 0..n_bits {
+            let byte = *self.bytes.get(self.pos / 8).ok_or_else(|| "truncated Huffman data".to_string())?;
+            v = (v << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u64;
+            self.pos += 1;
+        }
+        return Ok(v);
+    }
+}
+
+// Turns code lengths into canonical codes, longest codes first.
+fn huf_canonical_code_table(hcode: &mut [u64]) {
+    let mut n = [0u64; 59];
+    for &l in hcode.iter() {
+        n[l as usize] += 1;
+    }
+    let mut c = 0;
+    for i in (1..59).rev() {
+        let nc = (c + n[i]) >> 1;
+        n[i] = c;
+        c = nc;
+    }
+    for code in hcode.iter_mut() {
+        let l = *code;
+        if l > 0 {
+            *code = l | (n[l as usize] << 6);
+            n[l as usize] += 1;
+        }
+    }
+}
+
+// Replaces the frequencies in `frq` with codes, returning the smallest and
+// largest symbols coded (the largest being the run-length symbol).
+fn huf_build_enc_table(frq: &mut [u64]) -> (usize, usize) {
+    let im = frq.iter().position(|&f| f != 0).unwrap_or(0);
+    let mut i_max = frq.iter().rposition(|&f| f != 0).unwrap_or(0);
+
+    let mut hlink: Vec<usize> = (0..HUF_ENCSIZE).collect();
+    let mut heap = BinaryHeap::new();
+    for (i, &f) in frq.iter().enumerate().skip(im) {
+        if f != 0 {
+            heap.push(Reverse((f, i)));
+        }
+    }
+    i_max += 1;
+    frq[i_max] = 1;
+    heap.push(Reverse((1, i_max)));
+
+    // Merge the two least frequent subtrees until one is left, lengthening
+    // every code in both by one bit
+    let mut scode = vec![0u64; HUF_ENCSIZE];
+    while heap.len() > 1 {
+        let Reverse((_, mm)) = heap.pop().unwrap();
+        let Reverse((_, m)) = heap.pop().unwrap();
+        frq[m] += frq[mm];
+        heap.push(Reverse((frq[m], m)));
+
+        let mut j = m;
+        loop {
+            scode[j] += 1;
+            if hlink[j] == j {
+                hlink[j] = mm;
+                break;
+            }
+            j = hlink[j];
+        }
+        let mut j = mm;
+        loop {
+            scode[j] += 1;
+            if hlink[j] == j {
+                break;
+            }
+            j = hlink[j];
+        }
+    }
+
+    huf_canonical_code_table(&mut scode);
+    frq.copy_from_slice(&scode);
+    return (im, i_max);
+}
+
+// Code lengths as 6-bit fields, with runs of unused symbols collapsed.
+fn huf_pack_enc_table(hcode: &[u64], im: usize, i_max: usize) -> Vec<u8> {
+    let mut w = BitWriter { out: Vec::new(), c: 0, lc: 0 };
+    let mut i = im;
+    while i <= i_max {
+        let l = huf_length(hcode[i]) as u64;
+        if l == 0 {
+            let mut zerun = 1;
+            while i < i_max && zerun < LONGEST_LONG_RUN && huf_length(hcode[i + 1]) == 0 {
+                i += 1;
+                zerun += 1;
+            }
+            if zerun >= 2 {
+                if zerun >= SHORTEST_LONG_RUN {
+                    w.write(6, LONG_ZEROCODE_RUN);
+                    w.write(8, zerun - SHORTEST_LONG_RUN);
+                } else {
+                    w.write(6, SHORT_ZEROCODE_RUN + zerun - 2);
+                }
+                i += 1;
+                continue;
+            }
+        }
+        w.write(6, l);
+        i += 1;
+    }
+    return w.finish().0;
+}
+
+fn huf_unpack_enc_table(r: &mut BitReader, im: usize, i_max: usize) -> Result<Vec<u64>, String> {
+    let mut hcode = vec![0u64; HUF_ENCSIZE];
+    let mut i = im;
+    while i <= i_max {
+        let l = r.read(6)?;
+        let zerun = if l == LONG_ZEROCODE_RUN {
+            r.read(8)? + SHORTEST_LONG_RUN
+        } else if l >= SHORT_ZEROCODE_RUN {
+            l - SHORT_ZEROCODE_RUN + 2
+        } else {
+            hcode[i] = l;
+            i += 1;
+            continue;
+        };
+        if i + zerun as usize > i_max + 1 {
+            return Err("Huffman table too long".to_string());
+        }
+        i += zerun as usize;
+    }
+    huf_canonical_code_table(&mut hcode);
+    return Ok(hcode);
+}
+
+fn huf_compress(raw: &[u16]) -> Vec<u8> {
+    if raw.is_empty() {
+        return Vec::new();
+    }
+
+    let mut hcode = vec![0u64; HUF_ENCSIZE];
+    for &v in raw.iter() {
+        hcode[v as usize] += 1;
+    }
+    let (im, i_max) = huf_build_enc_table(&mut hcode);
+    let table = huf_pack_enc_table(&hcode, im, i_max);
+
+    // Repeats of a symbol are sent as symbol, run code, 8-bit count when
+    // that is shorter
+    let rlc = hcode[i_max];
+    let mut w = BitWriter { out: Vec::new(), c: 0, lc: 0 };
+    let send = |w: &mut BitWriter, s: u16, run: u32| {
+        let code = hcode[s as usize];
+        if huf_length(code) + huf_length(rlc) + 8 < huf_length(code) * run {
+            w.write_code(code);
+            w.write_code(rlc);
+            w.write(8, run as u64);
+        } else {
+            for _ in 0..=run {
+                w.write_code(code);
+            }
+        }
+    };
+    let mut s = raw[0];
+    let mut cs = 0;
+    for &v in raw[1..].iter() {
+        if v == s && cs < 255 {
+            cs += 1;
+        } else {
+            send(&mut w, s, cs);
+            cs = 0;
+        }
+        s = v;
+    }
+    send(&mut w, s, cs);
+    let (data, n_bits) = w.finish();
+
+    let mut out = Vec::with_capacity(20 + table.len() + data.len());
+    out.extend_from_slice(&(im as u32).to_le_bytes());
+    out.extend_from_slice(&(i_max as u32).to_le_bytes());
+    out.extend_from_slice(&(table.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(n_bits as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&table);
+    out.extend_from_slice(&data);
+    return out;
+}
+
+fn huf_uncompress(compressed: &[u8], n_raw: usize) -> Result<Vec<u16>, String> {
+    if compressed.is_empty() {
+        if n_raw != 0 {
+            return Err("missing Huffman data".to_string());
+        }
+        return Ok(Vec::new());
+    }
+
+    let mut input = ByteReader { bytes: compressed, pos: 0 };
+    let im = input.u32()? as usize;
+    let i_max = input.u32()? as usize;
+    let _table_length = input.u32()?;
+    let n_bits = input.u32()? as usize;
+    input.u32()?;
+    if im >= HUF_ENCSIZE || i_max >= HUF_ENCSIZE {
+        return Err("invalid Huffman table range".to_string());
+    }
+
+    let mut r = BitReader { bytes: &compressed[20..], pos: 0 };
+    let hcode = huf_unpack_enc_table(&mut r, im, i_max)?;
+    let data = &compressed[20 + r.pos.div_ceil(8)..];
+    if n_bits > data.len() * 8 {
+        return Err("truncated Huffman data".to_string());
+    }
+
+    // Canonical codes of each length are consecutive, so a code can be
+    // recognized from its length and offset from the first code of that length
+    let mut first = [0u64; 59];
+    let mut symbols: Vec<Vec<u32>> = vec![Vec::new(); 59];
+    for (i, &code) in hcode.iter().enumerate() {
+        let l = huf_length(code) as usize;
+        if l > 0 {
+            if symbols[l].is_empty() {
+                first[l] = huf_code(code);
+            }
+            symbols[l].push(i as u32);
+        }
+    }
+
+    let mut out: Vec<u16> = Vec::with_capacity(n_raw);
+    let mut r = BitReader { bytes: data, pos: 0 };
+    let mut code = 0u64;
+    let mut length = 0;
+    while r.pos < n_bits {
+        code = (code << 1) | r.read(1)?;
+        length += 1;
+        if length > 58 {
+            return Err("invalid Huffman code".to_string());
+        }
+        if symbols[length].is_empty() || code < first[length] || code - first[length] >= symbols[length].len() as u64 {
+            continue;
+        }
+
+        let symbol = symbols[length][(code - first[length]) as usize] as usize;
+        code = 0;
+        length = 0;
+        if symbol == i_max {
+            if r.pos + 8 > n_bits {
+                return Err("truncated Huffman run".to_string());
+            }
+            let run = r.read(8)? as usize;
+            let last = *out.last().ok_or_else(|| "Huffman run with no preceding value".to_string())?;
+            if out.len() + run > n_raw {
+                return Err("too much Huffman data".to_string());
+            }
+            out.extend(std::iter::repeat_n(last, run));
+        } else {
+            if out.len() >= n_raw {
+                return Err("too much Huffman data".to_string());
+            }
+            out.push(symbol as u16);
+        }
+    }
+
+    if out.len() != n_raw {
+        return Err("not enough Huffman data".to_string());
+    }
+    return Ok(out);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pbrt::io::exr::Compression;
+    use pbrt::io::exr::ExrImage;
+    use pbrt::io::exr::PixelType;
+    use pbrt::io::exr::f32_to_half;
+    use pbrt::io::exr::half_to_f32;
+    use pbrt::io::exr::read_exr;
+    use pbrt::io::exr::write_exr;
+
+    #[test]
+    fn round_trips_named_channels() {
+        let (width, height) = (37, 45);
+        let plane = |c: usize| -> Vec<f32> {
+            (0..width * height).map(|i| ((i % width / 4 + i / width / 3 + c) % 11) as f32 * 0.25 - 1.0).collect()
+        };
+        let mut image = ExrImage::new(width, height);
+        image.add_channel("R", PixelType::Half, plane(0)).unwrap();
+        image.add_channel("G", PixelType::Half, plane(1)).unwrap();
+        image.add_channel("B", PixelType::Half, plane(2)).unwrap();
+        image.add_channel("depth.Z", PixelType::Float, (0..width * height).map(|i| (i as f32).sqrt() * 0.1).collect()).unwrap();
+        assert!(image.add_channel("A", PixelType::Half, vec![1.0; width]).is_err());
+
+        for compression in [Compression::None, Compression::Rle, Compression::Zips, Compression::Zip, Compression::Piz].iter() {
+            let mut bytes = Vec::new();
+            write_exr(&mut bytes, &image, *compression).unwrap();
+            let read = read_exr(&bytes[..], Path::new("memory.exr")).unwrap();
+
+            assert_eq!(read.width, width);
+            assert_eq!(read.height, height);
+            assert_eq!(read.channels.len(), 4);
+            for c in image.channels.iter() {
+                let r = read.channel(&c.name).unwrap();
+                assert_eq!(r.pixel_type, c.pixel_type);
+                // Values chosen to be exact in half precision
+                assert_eq!(r.data, c.data);
+            }
+        }
+    }
+
+    // 7x19 RGB files written by the independent `exr` crate, with
+    // value(x, y, c) = ((7x + 13y + 5c) % 37) / 8 - 2, which is exact in half
+    #[test]
+    fn decodes_reference_files() {
+        let files: [(&str, &[u8], PixelType); 4] = [
+            ("zip_half.exr", include_bytes!("testdata/zip_half.exr"), PixelType::Half),
+            ("zip_float.exr", include_bytes!("testdata/zip_float.exr"), PixelType::Float),
+            ("piz_half.exr", include_bytes!("testdata/piz_half.exr"), PixelType::Half),
+            ("piz_float.exr", include_bytes!("testdata/piz_float.exr"), PixelType::Float),
+        ];
+        for &(name, bytes, pixel_type) in files.iter() {
+            let image = read_exr(bytes, Path::new(name)).unwrap();
+            assert_eq!((image.width, image.height), (7, 19), "{}", name);
+            for (c, channel_name) in ["R", "G", "B"].iter().enumerate() {
+                let channel = image.channel(channel_name).unwrap();
+                assert_eq!(channel.pixel_type, pixel_type, "{}", name);
+                for y in 0..19 {
+                    for x in 0..7 {
+                        let expected = ((x * 7 + y * 13 + c * 5) % 37) as f32 / 8.0 - 2.0;
+                        assert_eq!(channel.data[y * 7 + x], expected, "{} {} at ({}, {})", name, channel_name, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn converts_to_half() {
+        // Subnormals, including ties to even at the bottom of the range
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(3.0 * 2f32.powi(-25)), 0x0002);
+        assert_eq!(f32_to_half(1023.0 * 2f32.powi(-24)), 0x03ff);
+        assert_eq!(f32_to_half(2f32.powi(-14)), 0x0400);
+        assert_eq!(f32_to_half(-2f32.powi(-30)), 0x8000);
+
+        // Ties between normals go to the even mantissa
+        assert_eq!(f32_to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(f32_to_half(1.0 + 2f32.powi(-11) + 2f32.powi(-20)), 0x3c01);
+
+        // Overflow rounds to infinity from halfway past the largest half
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(65519.0), 0x7bff);
+        assert_eq!(f32_to_half(65520.0), 0x7c00);
+        assert_eq!(f32_to_half(-1e10), 0xfc00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+
+        // NaNs stay NaN, even when the payload would be shifted out
+        for &bits in [0x7fc0_0000u32, 0x7f80_0001, 0xff80_1000].iter() {
+            let h = f32_to_half(f32::from_bits(bits));
+            assert_eq!(h & 0x7c00, 0x7c00);
+            assert!(h & 0x03ff != 0);
+            assert!(half_to_f32(h).is_nan());
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_data_windows() {
+        let mut image = ExrImage::new(4, 4);
+        image.add_channel("Y", PixelType::Float, vec![0.5; 16]).unwrap();
+        let mut bytes = Vec::new();
+        write_exr(&mut bytes, &image, Compression::Zip).unwrap();
+
+        let key = b"dataWindow\0box2i\0";
+        let at = bytes.windows(key.len()).position(|w| w == &key[..]).unwrap() + key.len() + 4;
+        for window in [[i32::MIN, 0, i32::MAX, 3], [0, 0, 999_999, 999_999], [0, i32::MIN, 3, i32::MAX]].iter() {
+            let mut hostile = bytes.clone();
+            for (i, v) in window.iter().enumerate() {
+                hostile[at + 4 * i..at + 4 * i + 4].copy_from_slice(&v.to_le_bytes());
+            }
+            assert!(read_exr(&hostile[..], Path::new("hostile.exr")).is_err());
+        }
+    }
+}
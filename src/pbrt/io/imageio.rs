@@ -0,0 +1,95 @@
+use std::error;
+use std::fs::File;
+use std::path::Path;
+
+use image;
+
+use pbrt;
+use pbrt::Spectrum;
+use pbrt::geometry::Point2i;
+use pbrt::io::exr;
+use pbrt::io::exr::ExrImage;
+use pbrt::io::exr::PixelType;
+use pbrt::io::pfm;
+use pbrt::io::rgbe;
+use pbrt::tonemap::ToneMapping;
+
+
+fn extension(path: &Path) -> String {
+    return path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+}
+
+// Loads an RGB image for use as a texture or environment map, picking the
+// format from the extension. Values are linear; 8-bit images are assumed to
+// be sRGB encoded. Pixels are returned top row first.
+pub fn read_image<P: AsRef<Path>>(path: P) -> Result<(Point2i, Vec<Spectrum>), Box<dyn error::Error>> {
+    let path = path.as_ref();
+    match &extension(path)[..] {
+        "exr" => return Ok(rgb_from_exr(&exr::load_exr(path)?)),
+        "pfm" => return Ok(pfm::load_pfm(path)?),
+        "hdr" => return Ok(rgbe::load_rgbe(path)?),
+        _ => {}
+    }
+
+    let img = image::open(path)?.to_rgb8();
+    let rgb = img.pixels().map(|p| {
+        let linear = |v: u8| pbrt::inverse_gamma_correct(v as f64 / 255.0);
+        Spectrum::from_rgb(linear(p[0]), linear(p[1]), linear(p[2]))
+    }).collect();
+    return Ok((Point2i::new(img.width() as i64, img.height() as i64), rgb));
+}
+
+// The R, G and B channels of `image`, or its Y channel repeated for
+// luminance-only files.
+fn rgb_from_exr(image: &ExrImage) -> (Point2i, Vec<Spectrum>) {
+    let resolution = Point2i::new(image.width as i64, image.height as i64);
+    let n = image.width * image.height;
+    let zeros = vec![0.0; n];
+    let plane = |name: &str| image.channel(name).map(|c| &c.data).unwrap_or(&zeros);
+
+    let rgb = if image.channel("R").is_none() && image.channel("Y").is_some() {
+        plane("Y").iter().map(|&y| Spectrum::new(y as f64)).collect()
+    } else {
+        let (r, g, b) = (plane("R"), plane("G"), plane("B"));
+        (0..n).map(|i| Spectrum::from_rgb(r[i] as f64, g[i] as f64, b[i] as f64)).collect()
+    };
+    return (resolution, rgb);
+}
+
+// `rgb`, given top row first, as half-float R, G and B channels.
+pub fn exr_from_rgb(rgb: &[Spectrum], resolution: &Point2i) -> Result<ExrImage, String> {
+    let mut image = ExrImage::new(resolution.x as usize, resolution.y as usize);
+    for (c, name) in ["R", "G", "B"].iter().enumerate() {
+        image.add_channel(name, PixelType::Half, rgb.iter().map(|s| s.samples[c] as f32).collect())?;
+    }
+    return Ok(image);
+}
+
+// Writes linear `rgb`, given top row first, picking the format from the
+// extension: OpenEXR, PFM or Radiance HDR keep the floating point values,
+// anything else is written as 8-bit PNG through `tone_mapping`.
+pub fn write_image<P: AsRef<Path>>(
+    path: P,
+    rgb: &[Spectrum],
+    resolution: &Point2i,
+    tone_mapping: &ToneMapping,
+) -> Result<(), Box<dyn error::Error>> {
+    let path = path.as_ref();
+    match &extension(path)[..] {
+        "exr" => {
+            exr::save_exr(path, &exr_from_rgb(rgb, resolution)?, exr::Compression::Zip)?;
+        }
+        "pfm" => pfm::save_pfm(path, rgb, resolution)?,
+        "hdr" => rgbe::save_rgbe(path, rgb, resolution)?,
+        _ => {
+            let width = resolution.x as usize;
+            let imgbuf = image::ImageBuffer::from_fn(resolution.x as u32, resolution.y as u32, |x, y| {
+                image::Rgb(tone_mapping.quantize(&rgb[y as usize * width + x as usize], x, y))
+            });
+
+            let mut fout = File::create(path)?;
+            image::DynamicImage::ImageRgb8(imgbuf).write_to(&mut fout, image::ImageFormat::Png)?;
+        }
+    }
+    return Ok(());
+}
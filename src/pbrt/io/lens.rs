@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use pbrt::io::FileError;
+
+
+// One row of a lens prescription, in the units of the file (millimeters).
+// A curvature radius of zero marks the aperture stop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensElement {
+    pub curvature_radius: f64,
+    pub thickness: f64,
+    pub eta: f64,
+    pub aperture_diameter: f64,
+}
+
+pub fn load_lens<P: AsRef<Path>>(path: P) -> Result<Vec<LensElement>, FileError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| FileError::new(path, e.to_string()))?;
+    return read_lens(BufReader::new(file), path);
+}
+
+// Reads the pbrt lens format: one interface per line, ordered from the scene
+// side to the film side, as "radius thickness eta aperture". Everything after
+// a '#' is a comment.
+pub fn read_lens<R: BufRead>(reader: R, path: &Path) -> Result<Vec<LensElement>, FileError> {
+    let mut elements = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.map_err(|e| FileError::at_line(path, line_number, e.to_string()))?;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => &line[..],
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let values = line.split_whitespace()
+            .map(|t| t.parse::<f64>().map_err(|_| FileError::at_line(path, line_number, format!("invalid number '{}'", t))))
+            .collect::<Result<Vec<f64>, FileError>>()?;
+        if values.len() != 4 {
+            return Err(FileError::at_line(path, line_number, format!("expected 4 values, found {}", values.len())));
+        }
+        elements.push(LensElement {
+            curvature_radius: values[0],
+            thickness: values[1],
+            eta: values[2],
+            aperture_diameter: values[3],
+        });
+    }
+
+    if elements.is_empty() {
+        return Err(FileError::new(path, "no lens elements".to_string()));
+    }
+    return Ok(elements);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pbrt::io::lens;
+
+    #[test]
+    fn reports_error_lines() {
+        let path = Path::new("broken.dat");
+        let e = lens::read_lens("# radius thickness eta aperture\n35.98 4.6 1.67 20\n0 1 x 10\n".as_bytes(), path).unwrap_err();
+        assert_eq!(e.to_string(), "broken.dat:3: invalid number 'x'");
+
+        // Errors about the file as a whole have no line
+        let e = lens::read_lens("# empty\n".as_bytes(), path).unwrap_err();
+        assert_eq!(e.to_string(), "broken.dat: no lens elements");
+    }
+}
@@ -0,0 +1,42 @@
+pub mod exr;
+pub mod imageio;
+pub mod lens;
+pub mod obj;
+pub mod pfm;
+pub mod ply;
+pub mod rgbe;
+
+use std::error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+
+// A file that could not be read or written, and why. Text formats also
+// report the 1-based line of the offending statement.
+#[derive(Clone, Debug)]
+pub struct FileError {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(file: &Path, message: String) -> FileError {
+        FileError { file: file.to_path_buf(), line: None, message }
+    }
+
+    pub fn at_line(file: &Path, line: usize, message: String) -> FileError {
+        FileError { file: file.to_path_buf(), line: Some(line), message }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        };
+    }
+}
+
+impl error::Error for FileError {}
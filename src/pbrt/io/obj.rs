@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use pbrt::Spectrum;
+use pbrt::geometry::Normal3f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::transform::Transform;
+use pbrt::geometry::triangle::TriangleMesh;
+use pbrt::io::FileError;
+
+
+#[derive(Clone, Debug)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: Spectrum,
+    pub diffuse: Spectrum,
+    pub specular: Spectrum,
+    pub emission: Spectrum,
+    pub shininess: f64,
+    pub ior: f64,
+    pub dissolve: f64,
+    pub illum: i32,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub bump_map: Option<PathBuf>,
+    pub alpha_map: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    pub fn new(name: &str) -> ObjMaterial {
+        ObjMaterial {
+            name: name.to_string(),
+            ambient: Spectrum::new(0.0),
+            diffuse: Spectrum::new(0.8),
+            specular: Spectrum::new(0.0),
+            emission: Spectrum::new(0.0),
+            shininess: 0.0,
+            ior: 1.0,
+            dissolve: 1.0,
+            illum: 1,
+            diffuse_map: None,
+            specular_map: None,
+            bump_map: None,
+            alpha_map: None,
+        }
+    }
+}
+
+// A run of faces sharing the same object, groups and material.
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    pub object: String,
+    pub groups: Vec<String>,
+    pub material: Option<String>,
+    pub mesh: Arc<TriangleMesh>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjScene {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, ObjMaterial>,
+    // Problems that were skipped over, such as missing materials.
+    pub warnings: Vec<FileError>,
+}
+
+impl ObjScene {
+    pub fn material(&self, mesh: &ObjMesh) -> Option<&ObjMaterial> {
+        return mesh.material.as_ref().and_then(|name| self.materials.get(name));
+    }
+}
+
+
+pub fn load_obj<P: AsRef<Path>>(path: P, object_to_world: &Transform) -> Result<ObjScene, FileError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| FileError::new(path, e.to_string()))?;
+    return parse_obj(BufReader::new(file), path, object_to_world);
+}
+
+// Parses OBJ statements from `reader`. `path` is used for error reporting and
+// to resolve `mtllib` references relative to the OBJ file.
+pub fn parse_obj<R: BufRead>(reader: R, path: &Path, object_to_world: &Transform) -> Result<ObjScene, FileError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions: Vec<Point3f> = Vec::new();
+    let mut normals: Vec<Normal3f> = Vec::new();
+    let mut uvs: Vec<Point2f> = Vec::new();
+
+    let mut materials: HashMap<String, ObjMaterial> = HashMap::new();
+    let mut meshes: Vec<ObjMesh> = Vec::new();
+    let mut warnings: Vec<FileError> = Vec::new();
+    let mut builder = MeshBuilder::new(String::new(), vec![String::from("default")], None);
+
+    for (i, line) in reader.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.map_err(|e| FileError::at_line(path, line_number, e.to_string()))?;
+        let err = |message: String| FileError::at_line(path, line_number, message);
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(&args, 3, 3).map_err(&err)?;
+                positions.push(Point3f::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let v = parse_floats(&args, 3, 3).map_err(&err)?;
+                normals.push(Normal3f::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parse_floats(&args, 1, 2).map_err(&err)?;
+                uvs.push(Point2f::new(v[0], if v.len() > 1 { v[1] } else { 0.0 }));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(format!("face needs at least 3 vertices, found {}", args.len())));
+                }
+                let mut face = Vec::with_capacity(args.len());
+                for arg in args.iter() {
+                    face.push(parse_face_vertex(arg, positions.len(), uvs.len(), normals.len()).map_err(&err)?);
+                }
+                builder.add_face(&face, &positions, &uvs, &normals);
+            }
+            "o" => {
+                let object = args.join(" ");
+                let groups = builder.groups.clone();
+                let material = builder.material.clone();
+                builder.flush_into(object_to_world, &mut meshes).map_err(&err)?;
+                builder = MeshBuilder::new(object, groups, material);
+            }
+            "g" => {
+                let groups = if args.is_empty() {
+                    vec![String::from("default")]
+                } else {
+                    args.iter().map(|g| g.to_string()).collect()
+                };
+                let object = builder.object.clone();
+                let material = builder.material.clone();
+                builder.flush_into(object_to_world, &mut meshes).map_err(&err)?;
+                builder = MeshBuilder::new(object, groups, material);
+            }
+            "usemtl" => {
+                if args.is_empty() {
+                    return Err(err(String::from("usemtl needs a material name")));
+                }
+                // The faces that follow an unknown material keep no material
+                let mut material = Some(args.join(" "));
+                if let Some(name) = material.clone().filter(|name| !materials.contains_key(name)) {
+                    warnings.push(err(format!("unknown material '{}'", name)));
+                    material = None;
+                }
+                let object = builder.object.clone();
+                let groups = builder.groups.clone();
+                builder.flush_into(object_to_world, &mut meshes).map_err(&err)?;
+                builder = MeshBuilder::new(object, groups, material);
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(err(String::from("mtllib needs a file name")));
+                }
+                for name in args.iter() {
+                    let mtl_path = base_dir.join(name);
+                    let file = match File::open(&mtl_path) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            warnings.push(err(format!("cannot open material library {}: {}", mtl_path.display(), e)));
+                            continue;
+                        }
+                    };
+                    for material in parse_mtl(BufReader::new(file), &mtl_path)? {
+                        materials.insert(material.name.clone(), material);
+                    }
+                }
+            }
+            // Smoothing groups, merging groups, points, lines and free-form
+            // geometry do not affect triangle meshes.
+            _ => {}
+        }
+    }
+    builder.flush_into(object_to_world, &mut meshes).map_err(|e| FileError::new(path, e))?;
+
+    return Ok(ObjScene { meshes, materials, warnings });
+}
+
+pub fn parse_mtl<R: BufRead>(reader: R, path: &Path) -> Result<Vec<ObjMaterial>, FileError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.map_err(|e| FileError::at_line(path, line_number, e.to_string()))?;
+        let err = |message: String| FileError::at_line(path, line_number, message);
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(err(String::from("newmtl needs a material name")));
+            }
+            materials.push(ObjMaterial::new(&args.join(" ")));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(m) => m,
+            None => return Err(err(format!("'{}' before any newmtl", keyword))),
+        };
+        match keyword {
+            "Ka" => material.ambient = parse_color(&args).map_err(&err)?,
+            "Kd" => material.diffuse = parse_color(&args).map_err(&err)?,
+            "Ks" => material.specular = parse_color(&args).map_err(&err)?,
+            "Ke" => material.emission = parse_color(&args).map_err(&err)?,
+            "Ns" => material.shininess = parse_floats(&args, 1, 1).map_err(&err)?[0],
+            "Ni" => material.ior = parse_floats(&args, 1, 1).map_err(&err)?[0],
+            "d" => material.dissolve = parse_floats(&args, 1, 1).map_err(&err)?[0],
+            "Tr" => material.dissolve = 1.0 - parse_floats(&args, 1, 1).map_err(&err)?[0],
+            "illum" => {
+                material.illum = args.first()
+                    .and_then(|a| a.parse().ok())
+                    .ok_or_else(|| err(String::from("illum needs an integer model")))?;
+            }
+            "map_Kd" => material.diffuse_map = Some(parse_map(&args, base_dir).map_err(&err)?),
+            "map_Ks" => material.specular_map = Some(parse_map(&args, base_dir).map_err(&err)?),
+            "map_Bump" | "map_bump" | "bump" => material.bump_map = Some(parse_map(&args, base_dir).map_err(&err)?),
+            "map_d" => material.alpha_map = Some(parse_map(&args, base_dir).map_err(&err)?),
+            _ => {}
+        }
+    }
+
+    return Ok(materials);
+}
+
+
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+// Collects faces into a single mesh, merging OBJ's separate position, uv and
+// normal indices into unified vertices.
+struct MeshBuilder {
+    object: String,
+    groups: Vec<String>,
+    material: Option<String>,
+    lookup: HashMap<FaceVertex, usize>,
+    indices: Vec<usize>,
+    p: Vec<Point3f>,
+    n: Vec<Normal3f>,
+    uv: Vec<Point2f>,
+    all_have_normals: bool,
+    all_have_uvs: bool,
+}
+
+impl MeshBuilder {
+    fn new(object: String, groups: Vec<String>, material: Option<String>) -> MeshBuilder {
+        MeshBuilder {
+            object,
+            groups,
+            material,
+            lookup: HashMap::new(),
+            indices: Vec::new(),
+            p: Vec::new(),
+            n: Vec::new(),
+            uv: Vec::new(),
+            all_have_normals: true,
+            all_have_uvs: true,
+        }
+    }
+
+    fn vertex(&mut self, v: FaceVertex, positions: &[Point3f], uvs: &[Point2f], normals: &[Normal3f]) -> usize {
+        if let Some(&index) = self.lookup.get(&v) {
+            return index;
+        }
+
+        let index = self.p.len();
+        self.p.push(positions[v.0]);
+        match v.1 {
+            Some(t) => self.uv.push(uvs[t]),
+            None => {
+                self.all_have_uvs = false;
+                self.uv.push(Point2f::new(0.0, 0.0));
+            }
+        }
+        match v.2 {
+            Some(n) => self.n.push(normals[n]),
+            None => {
+                self.all_have_normals = false;
+                self.n.push(Normal3f::new(0.0, 0.0, 0.0));
+            }
+        }
+        self.lookup.insert(v, index);
+        return index;
+    }
+
+    // Triangulates a convex polygon as a fan around its first vertex.
+    fn add_face(&mut self, face: &[FaceVertex], positions: &[Point3f], uvs: &[Point2f], normals: &[Normal3f]) {
+        let first = self.vertex(face[0], positions, uvs, normals);
+        let mut previous = self.vertex(face[1], positions, uvs, normals);
+        for v in face[2..].iter() {
+            let current = self.vertex(*v, positions, uvs, normals);
+            self.indices.push(first);
+            self.indices.push(previous);
+            self.indices.push(current);
+            previous = current;
+        }
+    }
+
+    fn flush_into(self, object_to_world: &Transform, meshes: &mut Vec<ObjMesh>) -> Result<(), String> {
+        if self.indices.is_empty() {
+            return Ok(());
+        }
+
+        let n = if self.all_have_normals { Some(self.n) } else { None };
+        let uv = if self.all_have_uvs { Some(self.uv) } else { None };
+        meshes.push(ObjMesh {
+            object: self.object,
+            groups: self.groups,
+            material: self.material,
+            mesh: Arc::new(TriangleMesh::new(object_to_world, self.indices, self.p, n, None, uv)?),
+        });
+        return Ok(());
+    }
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min {
+        return Err(format!("expected at least {} values, found {}", min, args.len()));
+    }
+    return args.iter()
+        .take(max)
+        .map(|a| a.parse::<f64>().map_err(|_| format!("invalid number '{}'", a)))
+        .collect();
+}
+
+fn parse_color(args: &[&str]) -> Result<Spectrum, String> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err(format!("unsupported color format '{}'", args[0]));
+    }
+    let v = parse_floats(args, 1, 3)?;
+    if v.len() < 3 {
+        return Ok(Spectrum::new(v[0]));
+    }
+    return Ok(Spectrum::from_rgb(v[0], v[1], v[2]));
+}
+
+// Texture statements may carry options (-bm 0.5 ...); the file name comes last.
+fn parse_map(args: &[&str], base_dir: &Path) -> Result<PathBuf, String> {
+    match args.last() {
+        Some(name) => Ok(base_dir.join(name)),
+        None => Err(String::from("texture map needs a file name")),
+    }
+}
+
+// Resolves a 1-based (or negative, relative) OBJ index into a 0-based one.
+fn resolve_index(token: &str, count: usize, what: &str) -> Result<usize, String> {
+    let index: i64 = token.parse().map_err(|_| format!("invalid {} index '{}'", what, token))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} out of range (have {})", what, index, count));
+    }
+    return Ok(resolved as usize);
+}
+
+fn parse_face_vertex(token: &str, n_positions: usize, n_uvs: usize, n_normals: usize) -> Result<FaceVertex, String> {
+    let mut parts = token.split('/');
+    let p = resolve_index(parts.next().unwrap_or(""), n_positions, "vertex")?;
+    let uv = match parts.next() {
+        Some(t) if !t.is_empty() => Some(resolve_index(t, n_uvs, "texture coordinate")?),
+        _ => None,
+    };
+    let n = match parts.next() {
+        Some(t) if !t.is_empty() => Some(resolve_index(t, n_normals, "normal")?),
+        _ => None,
+    };
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex '{}'", token));
+    }
+    return Ok((p, uv, n));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use pbrt::Spectrum;
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::transform::Transform;
+    use pbrt::io::obj;
+    use pbrt::io::obj::ObjScene;
+
+    fn parse(text: &str, path: &Path) -> ObjScene {
+        return obj::parse_obj(text.as_bytes(), path, &Transform::identity()).unwrap();
+    }
+
+    #[test]
+    fn faces_resolve_indices_and_triangulate() {
+        let scene = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            # A quad in v/vt/vn form, with indices counted back from the end
+            f -4/1/1 -3/2/1 -2/3/1 -1/4/1
+        ", Path::new("quad.obj"));
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0].mesh;
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2, 0, 2, 3]);
+        assert!(mesh.p[2] == Point3f::new(1.0, 1.0, 0.0));
+        assert!(mesh.uv.as_ref().unwrap()[3] == Point2f::new(0.0, 1.0));
+        assert_eq!(mesh.n.as_ref().unwrap().len(), 4);
+
+        // A pentagon becomes a fan of three triangles; vertices without
+        // uvs leave the mesh without any
+        let scene = parse("
+            v 0 0 0
+            v 1 0 0
+            v 2 1 0
+            v 1 2 0
+            v 0 1 0
+            vn 0 0 1
+            f 1//1 2//1 3//1 4//1 5//1
+        ", Path::new("pentagon.obj"));
+        let mesh = &scene.meshes[0].mesh;
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        assert!(mesh.uv.is_none() && mesh.n.is_some());
+
+        assert!(obj::parse_obj("v 0 0 0\nf 1 2 3\n".as_bytes(), Path::new("bad.obj"), &Transform::identity()).is_err());
+        assert!(obj::parse_obj("v 0 0 0\nf 1 1/1/1/1 1\n".as_bytes(), Path::new("bad.obj"), &Transform::identity()).is_err());
+    }
+
+    #[test]
+    fn objects_and_groups_split_meshes() {
+        let scene = parse("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            o first
+            g a b
+            f 1 2 3
+            g
+            f 1 2 3
+            o second
+            f 3 2 1
+        ", Path::new("split.obj"));
+        let names: Vec<(&str, Vec<&str>)> = scene.meshes.iter()
+            .map(|m| (&m.object[..], m.groups.iter().map(|g| &g[..]).collect()))
+            .collect();
+        assert_eq!(names, vec![("first", vec!["a", "b"]), ("first", vec!["default"]), ("second", vec!["default"])]);
+        assert!(scene.meshes.iter().all(|m| m.mesh.n_triangles == 1 && m.mesh.p.len() == 3));
+    }
+
+    #[test]
+    fn parses_materials() {
+        let materials = obj::parse_mtl("
+            # Two materials
+            newmtl red
+            Kd 1 0 0
+            Ns 10
+            Tr 0.25
+            illum 2
+            map_Kd -bm 0.5 textures/red.png
+            newmtl grey
+            Ka 0.5
+        ".as_bytes(), Path::new("scene/scene.mtl")).unwrap();
+        assert_eq!(materials.len(), 2);
+        let red = &materials[0];
+        assert!(red.name == "red" && red.diffuse == Spectrum::from_rgb(1.0, 0.0, 0.0));
+        assert_eq!((red.shininess, red.dissolve, red.illum), (10.0, 0.75, 2));
+        assert_eq!(red.diffuse_map.as_ref().unwrap(), Path::new("scene/textures/red.png"));
+        assert!(materials[1].ambient == Spectrum::new(0.5));
+
+        let e = obj::parse_mtl("\nKd 1 1 1\n".as_bytes(), Path::new("orphan.mtl")).unwrap_err();
+        assert_eq!(e.line, Some(2));
+    }
+
+    #[test]
+    fn missing_materials_are_warnings() {
+        let dir = env::temp_dir().join(format!("obj-materials-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("scene.mtl"), "newmtl white\nKd 1 1 1\n").unwrap();
+
+        let scene = parse("
+            mtllib scene.mtl missing.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            usemtl white
+            f 1 2 3
+            usemtl black
+            f 1 2 3
+        ", &dir.join("scene.obj"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Both faces survive; the second has no material
+        assert_eq!(scene.meshes.len(), 2);
+        assert_eq!(scene.material(&scene.meshes[0]).unwrap().name, "white");
+        assert!(scene.meshes[1].material.is_none());
+        let lines: Vec<Option<usize>> = scene.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, vec![Some(2), Some(8)]);
+    }
+}
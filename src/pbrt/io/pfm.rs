@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use pbrt::Spectrum;
+use pbrt::geometry::Point2i;
+use pbrt::io::FileError;
+
+
+pub fn load_pfm<P: AsRef<Path>>(path: P) -> Result<(Point2i, Vec<Spectrum>), FileError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| FileError::new(path, e.to_string()))?;
+    return read_pfm(file, path);
+}
+
+// Reads a Portable Float Map, either three-channel "PF" or greyscale "Pf".
+// Pixels are returned top row first.
+pub fn read_pfm<R: Read>(mut reader: R, path: &Path) -> Result<(Point2i, Vec<Spectrum>), FileError> {
+    let err = |message: &str| FileError::new(path, message.to_string());
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|e| FileError::new(path, e.to_string()))?;
+
+    // Four whitespace separated header tokens, then a single whitespace byte
+    let mut pos = 0;
+    let mut tokens = Vec::new();
+    while tokens.len() < 4 {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(err("truncated header"));
+        }
+        tokens.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+    }
+    pos += 1;
+
+    let n_channels = match &tokens[0][..] {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(err("not a PFM file")),
+    };
+    let width: usize = tokens[1].parse().map_err(|_| err("bad width"))?;
+    let height: usize = tokens[2].parse().map_err(|_| err("bad height"))?;
+    let scale: f64 = tokens[3].parse().map_err(|_| err("bad scale"))?;
+    // A negative scale marks little-endian data
+    let little_endian = scale < 0.0;
+    let scale = scale.abs();
+
+    let n_values = width.checked_mul(height).and_then(|n| n.checked_mul(n_channels)).ok_or_else(|| err("image is too large"))?;
+    let end = n_values.checked_mul(4).and_then(|n| n.checked_add(pos)).filter(|&end| end <= bytes.len());
+    let end = end.ok_or_else(|| err("truncated pixel data"))?;
+    let values: Vec<f64> = bytes[pos..end].chunks(4).map(|b| {
+        let b = [b[0], b[1], b[2], b[3]];
+        let v = if little_endian { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) };
+        v as f64 * scale
+    }).collect();
+
+    // Rows are stored bottom to top
+    let mut rgb = Vec::with_capacity(width * height);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let v = &values[(y * width + x) * n_channels..];
+            rgb.push(if n_channels == 3 { Spectrum::from_rgb(v[0], v[1], v[2]) } else { Spectrum::new(v[0]) });
+        }
+    }
+    return Ok((Point2i::new(width as i64, height as i64), rgb));
+}
+
+pub fn save_pfm<P: AsRef<Path>>(path: P, rgb: &[Spectrum], resolution: &Point2i) -> Result<(), FileError> {
+    let path = path.as_ref();
+    let err = |e: ::std::io::Error| FileError::new(path, e.to_string());
+    let mut writer = BufWriter::new(File::create(path).map_err(&err)?);
+    write_pfm(&mut writer, rgb, resolution).map_err(&err)?;
+    return writer.flush().map_err(&err);
+}
+
+// Writes `rgb`, given top row first, as a little-endian color PFM.
+pub fn write_pfm<W: Write>(writer: &mut W, rgb: &[Spectrum], resolution: &Point2i) -> ::std::io::Result<()> {
+    let (width, height) = (resolution.x as usize, resolution.y as usize);
+    write!(writer, "PF\n{} {}\n-1\n", width, height)?;
+    for y in (0..height).rev() {
+        for s in rgb[y * width..(y + 1) * width].iter() {
+            for &v in s.samples.iter() {
+                writer.write_all(&(v as f32).to_le_bytes())?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pbrt::Spectrum;
+    use pbrt::geometry::Point2i;
+    use pbrt::io::pfm::read_pfm;
+    use pbrt::io::pfm::write_pfm;
+
+    #[test]
+    fn round_trips_top_row_first() {
+        let resolution = Point2i::new(3, 2);
+        let rgb: Vec<Spectrum> = (0..6).map(|i| Spectrum::from_rgb(i as f64, 0.5 * i as f64, -0.25)).collect();
+        let mut bytes = Vec::new();
+        write_pfm(&mut bytes, &rgb, &resolution).unwrap();
+
+        // Little-endian, with the bottom row stored first
+        let header = b"PF\n3 2\n-1\n";
+        assert_eq!(&bytes[..header.len()], &header[..]);
+        assert_eq!(&bytes[header.len()..header.len() + 4], &3f32.to_le_bytes()[..]);
+
+        let (read_resolution, read) = read_pfm(&bytes[..], Path::new("memory.pfm")).unwrap();
+        assert!(read_resolution == resolution);
+        for (a, b) in rgb.iter().zip(read.iter()) {
+            assert_eq!(a.samples, b.samples);
+        }
+    }
+
+    #[test]
+    fn reads_big_endian_greyscale() {
+        // A positive scale marks big-endian data and scales every value
+        let mut bytes = b"Pf\n2 2\n2.0\n".to_vec();
+        for &v in [1.0f32, 2.0, 3.0, 4.0].iter() {
+            bytes.extend_from_slice(&v.to_be_bytes());
+        }
+        let (resolution, grey) = read_pfm(&bytes[..], Path::new("memory.pfm")).unwrap();
+        assert!(resolution == Point2i::new(2, 2));
+        let values: Vec<f64> = grey.iter().map(|s| s.samples[1]).collect();
+        assert_eq!(values, [6.0, 8.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_images() {
+        for header in ["PF\n2 2\n-1\n", "PF\n4294967296 4294967296\n-1\n", "Pf\n18446744073709551615 1\n-1\n"].iter() {
+            let mut bytes = header.as_bytes().to_vec();
+            bytes.extend_from_slice(&[0; 16]);
+            assert!(read_pfm(&bytes[..], Path::new("memory.pfm")).is_err());
+        }
+    }
+}
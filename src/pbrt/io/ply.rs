@@ -0,0 +1,437 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::SplitWhitespace;
+
+use pbrt::geometry::Normal3f;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point3f;
+use pbrt::geometry::transform::Transform;
+use pbrt::geometry::triangle::TriangleMesh;
+use pbrt::io::FileError;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        return *self != ScalarType::Float32 && *self != ScalarType::Float64;
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+    Scalar { name: String, ty: ScalarType },
+    List { name: String, count_ty: ScalarType, item_ty: ScalarType },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match *self {
+            Property::Scalar { ref name, .. } => name,
+            Property::List { ref name, .. } => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+
+pub fn load_ply<P: AsRef<Path>>(path: P, object_to_world: &Transform) -> Result<TriangleMesh, FileError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| FileError::new(path, e.to_string()))?;
+    return read_ply(BufReader::new(file), path, object_to_world);
+}
+
+// Reads vertex positions, optional normals and uvs, and triangle or polygon
+// faces (fan-triangulated, so quads become two triangles) into a mesh.
+pub fn read_ply<R: BufRead>(mut reader: R, path: &Path, object_to_world: &Transform) -> Result<TriangleMesh, FileError> {
+    let err = |message: String| FileError::new(path, message);
+    let (format, elements) = read_header(&mut reader).map_err(&err)?;
+
+    let mut text = String::new();
+    let mut body = match format {
+        Format::Ascii => {
+            reader.read_to_string(&mut text).map_err(|e| err(e.to_string()))?;
+            Body::Ascii(text.split_whitespace())
+        }
+        Format::BinaryLittleEndian => Body::Binary(Box::new(reader), false),
+        Format::BinaryBigEndian => Body::Binary(Box::new(reader), true),
+    };
+
+    let mut p: Vec<Point3f> = Vec::new();
+    let mut n: Vec<Normal3f> = Vec::new();
+    let mut uv: Vec<Point2f> = Vec::new();
+    let mut has_normals = false;
+    let mut has_uvs = false;
+    let mut indices: Vec<usize> = Vec::new();
+
+    for element in elements.iter() {
+        match element.name.as_str() {
+            "vertex" => {
+                let find = |names: &[&str]| element.properties.iter().position(|prop| names.contains(&prop.name()));
+                let (xi, yi, zi) = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+                    (Some(x), Some(y), Some(z)) => (x, y, z),
+                    _ => return Err(err(String::from("vertex element is missing x, y or z"))),
+                };
+                let normal = match (find(&["nx"]), find(&["ny"]), find(&["nz"])) {
+                    (Some(x), Some(y), Some(z)) => Some((x, y, z)),
+                    _ => None,
+                };
+                let texcoord = match (
+                    find(&["u", "s", "texture_u", "texture_s"]),
+                    find(&["v", "t", "texture_v", "texture_t"]),
+                ) {
+                    (Some(u), Some(v)) => Some((u, v)),
+                    _ => None,
+                };
+                has_normals = normal.is_some();
+                has_uvs = texcoord.is_some();
+
+                let mut values = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    for (j, prop) in element.properties.iter().enumerate() {
+                        match *prop {
+                            Property::Scalar { ty, .. } => values[j] = body.read(ty).map_err(&err)?,
+                            Property::List { count_ty, item_ty, .. } => {
+                                body.skip_list(count_ty, item_ty).map_err(&err)?;
+                            }
+                        }
+                    }
+                    p.push(Point3f::new(values[xi], values[yi], values[zi]));
+                    if let Some((x, y, z)) = normal {
+                        n.push(Normal3f::new(values[x], values[y], values[z]));
+                    }
+                    if let Some((u, v)) = texcoord {
+                        uv.push(Point2f::new(values[u], values[v]));
+                    }
+                }
+            }
+            "face" => {
+                let mut face: Vec<usize> = Vec::new();
+                for _ in 0..element.count {
+                    face.clear();
+                    for prop in element.properties.iter() {
+                        match *prop {
+                            Property::List { ref name, count_ty, item_ty }
+                                if name == "vertex_indices" || name == "vertex_index" => {
+                                let count = body.read_index(count_ty).map_err(&err)?;
+                                for _ in 0..count {
+                                    face.push(body.read_index(item_ty).map_err(&err)?);
+                                }
+                            }
+                            Property::List { count_ty, item_ty, .. } => {
+                                body.skip_list(count_ty, item_ty).map_err(&err)?;
+                            }
+                            Property::Scalar { ty, .. } => {
+                                body.read(ty).map_err(&err)?;
+                            }
+                        }
+                    }
+                    if face.len() < 3 {
+                        return Err(err(format!("face with {} vertices", face.len())));
+                    }
+                    for k in 1..face.len() - 1 {
+                        indices.push(face[0]);
+                        indices.push(face[k]);
+                        indices.push(face[k + 1]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for prop in element.properties.iter() {
+                        match *prop {
+                            Property::Scalar { ty, .. } => {
+                                body.read(ty).map_err(&err)?;
+                            }
+                            Property::List { count_ty, item_ty, .. } => {
+                                body.skip_list(count_ty, item_ty).map_err(&err)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let n = if has_normals { Some(n) } else { None };
+    let uv = if has_uvs { Some(uv) } else { None };
+    return TriangleMesh::new(object_to_world, indices, p, n, None, uv).map_err(err);
+}
+
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<(Format, Vec<Element>), String> {
+    let mut line = String::new();
+    let mut line_number = 0;
+    let mut format: Option<Format> = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    loop {
+        line.clear();
+        line_number += 1;
+        let read = reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Err(String::from("unexpected end of file in header"));
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if line_number == 1 {
+            if tokens.first() != Some(&"ply") {
+                return Err(String::from("not a PLY file (missing 'ply' magic)"));
+            }
+            continue;
+        }
+        let header_err = |message: &str| format!("header line {}: {}", line_number, message);
+
+        match tokens.first().cloned() {
+            Some("format") => {
+                format = match tokens.get(1).cloned() {
+                    Some("ascii") => Some(Format::Ascii),
+                    Some("binary_little_endian") => Some(Format::BinaryLittleEndian),
+                    Some("binary_big_endian") => Some(Format::BinaryBigEndian),
+                    _ => return Err(header_err("unknown format")),
+                };
+            }
+            Some("element") => {
+                if tokens.len() != 3 {
+                    return Err(header_err("expected 'element <name> <count>'"));
+                }
+                let count = tokens[2].parse().map_err(|_| header_err("invalid element count"))?;
+                elements.push(Element { name: tokens[1].to_string(), count, properties: Vec::new() });
+            }
+            Some("property") => {
+                let element = match elements.last_mut() {
+                    Some(e) => e,
+                    None => return Err(header_err("property before any element")),
+                };
+                let property = if tokens.get(1) == Some(&"list") {
+                    if tokens.len() != 5 {
+                        return Err(header_err("expected 'property list <count type> <item type> <name>'"));
+                    }
+                    Property::List {
+                        name: tokens[4].to_string(),
+                        count_ty: ScalarType::parse(tokens[2]).ok_or_else(|| header_err("unknown count type"))?,
+                        item_ty: ScalarType::parse(tokens[3]).ok_or_else(|| header_err("unknown item type"))?,
+                    }
+                } else {
+                    if tokens.len() != 3 {
+                        return Err(header_err("expected 'property <type> <name>'"));
+                    }
+                    Property::Scalar {
+                        name: tokens[2].to_string(),
+                        ty: ScalarType::parse(tokens[1]).ok_or_else(|| header_err("unknown property type"))?,
+                    }
+                };
+                element.properties.push(property);
+            }
+            Some("end_header") => break,
+            Some("comment") | Some("obj_info") | None => {}
+            Some(other) => return Err(header_err(&format!("unknown keyword '{}'", other))),
+        }
+    }
+
+    match format {
+        Some(f) => Ok((f, elements)),
+        None => Err(String::from("header has no format line")),
+    }
+}
+
+enum Body<'a> {
+    Ascii(SplitWhitespace<'a>),
+    // Binary payload and whether it is big endian.
+    Binary(Box<dyn Read + 'a>, bool),
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        match *self {
+            Body::Ascii(ref mut tokens) => {
+                let token = tokens.next().ok_or_else(|| String::from("unexpected end of file"))?;
+                if ty.is_integer() {
+                    return token.parse::<i64>().map(|v| v as f64).map_err(|_| format!("invalid integer '{}'", token));
+                }
+                return token.parse::<f64>().map_err(|_| format!("invalid number '{}'", token));
+            }
+            Body::Binary(ref mut reader, big_endian) => {
+                let mut buf = [0u8; 8];
+                let bytes = &mut buf[..ty.size()];
+                reader.read_exact(bytes).map_err(|_| String::from("unexpected end of file"))?;
+                if big_endian {
+                    bytes.reverse();
+                }
+
+                // Bytes are now little endian
+                let value = match ty {
+                    ScalarType::Int8 => bytes[0] as i8 as f64,
+                    ScalarType::UInt8 => bytes[0] as f64,
+                    ScalarType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    ScalarType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    ScalarType::Int32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    ScalarType::UInt32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    ScalarType::Float32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    ScalarType::Float64 => f64::from_le_bytes(buf),
+                };
+                return Ok(value);
+            }
+        }
+    }
+
+    // A list count or vertex index, which must be a whole, non-negative
+    // number even when stored as a float.
+    fn read_index(&mut self, ty: ScalarType) -> Result<usize, String> {
+        let value = self.read(ty)?;
+        if value < 0.0 || value.fract() != 0.0 || value > u32::MAX as f64 {
+            return Err(format!("invalid index {}", value));
+        }
+        return Ok(value as usize);
+    }
+
+    fn skip_list(&mut self, count_ty: ScalarType, item_ty: ScalarType) -> Result<(), String> {
+        let count = self.read_index(count_ty)?;
+        for _ in 0..count {
+            self.read(item_ty)?;
+        }
+        return Ok(());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pbrt::geometry::Point2f;
+    use pbrt::geometry::Point3f;
+    use pbrt::geometry::transform::Transform;
+    use pbrt::geometry::triangle::TriangleMesh;
+    use pbrt::io::FileError;
+    use pbrt::io::ply;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property float u
+property float v
+element face 2
+property list uchar int vertex_indices
+property uchar flags
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+";
+
+    // A quad and a triangle over a unit square, with a per-face scalar and a
+    // trailing edge element that the reader skips. Values are tagged 'f' for
+    // float, 'i' for int and 'b' for uchar.
+    const BODY: [(char, f64); 33] = [
+        ('f', 0.0), ('f', 0.0), ('f', 0.0), ('f', 0.0), ('f', 0.0),
+        ('f', 1.0), ('f', 0.0), ('f', 0.0), ('f', 1.0), ('f', 0.0),
+        ('f', 1.0), ('f', 1.0), ('f', 0.0), ('f', 1.0), ('f', 1.0),
+        ('f', 0.0), ('f', 1.0), ('f', 0.0), ('f', 0.0), ('f', 0.5),
+        ('b', 4.0), ('i', 0.0), ('i', 1.0), ('i', 2.0), ('i', 3.0), ('b', 7.0),
+        ('b', 3.0), ('i', 1.0), ('i', 3.0), ('i', 2.0), ('b', 0.0),
+        ('i', 0.0), ('i', 2.0),
+    ];
+
+    fn read(format: &str, body: &[u8]) -> Result<TriangleMesh, FileError> {
+        let mut bytes = format!("ply\nformat {} 1.0\ncomment written by hand\n{}", format, HEADER).into_bytes();
+        bytes.extend_from_slice(body);
+        return ply::read_ply(&bytes[..], Path::new("test.ply"), &Transform::identity());
+    }
+
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &(ty, v) in BODY.iter() {
+            let mut encoded = match ty {
+                'f' => (v as f32).to_le_bytes().to_vec(),
+                'i' => (v as i32).to_le_bytes().to_vec(),
+                _ => vec![v as u8],
+            };
+            if big_endian {
+                encoded.reverse();
+            }
+            bytes.extend(encoded);
+        }
+        return bytes;
+    }
+
+    #[test]
+    fn reads_ascii_and_binary_bodies() {
+        let ascii: Vec<String> = BODY.iter().map(|&(_, v)| v.to_string()).collect();
+        let meshes = [
+            read("ascii", ascii.join(" ").as_bytes()).unwrap(),
+            read("binary_little_endian", &binary(false)).unwrap(),
+            read("binary_big_endian", &binary(true)).unwrap(),
+        ];
+        for mesh in meshes.iter() {
+            assert_eq!(mesh.p.len(), 4);
+            assert!(mesh.p[2] == Point3f::new(1.0, 1.0, 0.0));
+            assert!(mesh.uv.as_ref().unwrap()[3] == Point2f::new(0.0, 0.5));
+            assert!(mesh.n.is_none());
+            // The quad is split into a fan
+            assert_eq!(mesh.vertex_indices, vec![0, 1, 2, 0, 2, 3, 1, 3, 2]);
+        }
+
+        // Short bodies are errors
+        assert!(read("binary_little_endian", &binary(false)[..40]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_indices() {
+        let body = |face: &str| format!("0 0 0 0 0  1 0 0 0 0  1 1 0 0 0  0 1 0 0 0\n{}\n3 0 1 2 0\n0 1", face);
+        assert!(read("ascii", body("3 1 3 2 0").as_bytes()).is_ok());
+        // Not whole numbers, negative, and past the last vertex
+        assert!(read("ascii", body("3 1 3 1.7 0").as_bytes()).is_err());
+        assert!(read("ascii", body("3 1 3 -2 0").as_bytes()).is_err());
+        assert!(read("ascii", body("3 1 3 4 0").as_bytes()).is_err());
+        assert!(read("ascii", body("2.5 1 3 2 0").as_bytes()).is_err());
+    }
+}
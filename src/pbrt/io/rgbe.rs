@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use pbrt::Spectrum;
+use pbrt::geometry::Point2i;
+use pbrt::io::FileError;
+
+
+// Shared 8-bit exponent with an 8-bit mantissa per channel.
+fn to_rgbe(s: &Spectrum) -> [u8; 4] {
+    let (r, g, b) = (s.samples[0].max(0.0), s.samples[1].max(0.0), s.samples[2].max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    if e < -128 {
+        return [0, 0, 0, 0];
+    }
+    let e = e.min(127);
+    let scale = 256.0 / 2f64.powi(e);
+    let byte = |c: f64| (c * scale).min(255.0) as u8;
+    return [byte(r), byte(g), byte(b), (e + 128) as u8];
+}
+
+fn from_rgbe(rgbe: &[u8]) -> Spectrum {
+    if rgbe[3] == 0 {
+        return Spectrum::new(0.0);
+    }
+    // Decode to the middle of each mantissa step
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    return Spectrum::from_rgb((rgbe[0] as f64 + 0.5) * f, (rgbe[1] as f64 + 0.5) * f, (rgbe[2] as f64 + 0.5) * f);
+}
+
+
+pub fn load_rgbe<P: AsRef<Path>>(path: P) -> Result<(Point2i, Vec<Spectrum>), FileError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| FileError::new(path, e.to_string()))?;
+    return read_rgbe(file, path);
+}
+
+// Reads a Radiance picture (.hdr) in the standard -Y/+X orientation or its
+// vertical flip. Pixels are returned top row first.
+pub fn read_rgbe<R: Read>(mut reader: R, path: &Path) -> Result<(Point2i, Vec<Spectrum>), FileError> {
+    let err = |message: &str| FileError::new(path, message.to_string());
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|e| FileError::new(path, e.to_string()))?;
+
+    let mut pos = 0;
+    let mut next_line = || -> Option<String> {
+        let rest = &bytes[pos.min(bytes.len())..];
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        pos += end + 1;
+        return Some(String::from_utf8_lossy(&rest[..end]).trim_end().to_string());
+    };
+
+    let magic = next_line().ok_or_else(|| err("truncated header"))?;
+    if !magic.starts_with("#?") {
+        return Err(err("not a Radiance picture"));
+    }
+    // Header variables run until a blank line
+    loop {
+        let line = next_line().ok_or_else(|| err("truncated header"))?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(err(&format!("unsupported {}", line)));
+        }
+    }
+
+    let resolution = next_line().ok_or_else(|| err("missing resolution"))?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    if fields.len() != 4 || fields[2] != "+X" || (fields[0] != "-Y" && fields[0] != "+Y") {
+        return Err(err(&format!("unsupported resolution line \"{}\"", resolution)));
+    }
+    let bottom_up = fields[0] == "+Y";
+    let height: usize = fields[1].parse().map_err(|_| err("bad height"))?;
+    let width: usize = fields[3].parse().map_err(|_| err("bad width"))?;
+    if width == 0 || height == 0 {
+        return Err(err("empty image"));
+    }
+
+    // The smallest a scanline can be encoded, to check the size before
+    // allocating: one two-byte run per 127 pixels of each channel, or flat
+    let data = &bytes[pos..];
+    let min_scanline = if (8..0x8000).contains(&width) { 4 + 8 * width.div_ceil(127) } else { width.saturating_mul(4) };
+    if min_scanline.saturating_mul(height) > data.len() {
+        return Err(err("truncated pixel data"));
+    }
+    let mut pos = 0;
+    let mut take = |n: usize| -> Result<&[u8], FileError> {
+        if pos + n > data.len() {
+            return Err(err("truncated pixel data"));
+        }
+        pos += n;
+        return Ok(&data[pos - n..pos]);
+    };
+
+    let mut rgb = vec![Spectrum::new(0.0); width * height];
+    let mut scanline = vec![0u8; 4 * width];
+    for row in 0..height {
+        let start = take(4)?.to_vec();
+        if (8..0x8000).contains(&width) && start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0 {
+            // Run-length encoded, one channel at a time
+            if ((start[2] as usize) << 8 | start[3] as usize) != width {
+                return Err(err("scanline width mismatch"));
+            }
+            for c in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = take(1)?[0] as usize;
+                    if count > 128 {
+                        let count = count - 128;
+                        let value = take(1)?[0];
+                        if x + count > width {
+                            return Err(err("bad scanline data"));
+                        }
+                        for i in x..x + count {
+                            scanline[4 * i + c] = value;
+                        }
+                        x += count;
+                    } else {
+                        if count == 0 || x + count > width {
+                            return Err(err("bad scanline data"));
+                        }
+                        for (i, &value) in take(count)?.iter().enumerate() {
+                            scanline[4 * (x + i) + c] = value;
+                        }
+                        x += count;
+                    }
+                }
+            }
+        } else {
+            scanline[..4].copy_from_slice(&start);
+            scanline[4..].copy_from_slice(take(4 * (width - 1))?);
+        }
+
+        let y = if bottom_up { height - 1 - row } else { row };
+        for x in 0..width {
+            rgb[y * width + x] = from_rgbe(&scanline[4 * x..4 * x + 4]);
+        }
+    }
+    return Ok((Point2i::new(width as i64, height as i64), rgb));
+}
+
+pub fn save_rgbe<P: AsRef<Path>>(path: P, rgb: &[Spectrum], resolution: &Point2i) -> Result<(), FileError> {
+    let path = path.as_ref();
+    let err = |e: ::std::io::Error| FileError::new(path, e.to_string());
+    let mut writer = BufWriter::new(File::create(path).map_err(&err)?);
+    write_rgbe(&mut writer, rgb, resolution).map_err(&err)?;
+    return writer.flush().map_err(&err);
+}
+
+// Writes `rgb`, given top row first, with run-length encoded scanlines
+// where the width allows it.
+pub fn write_rgbe<W: Write>(writer: &mut W, rgb: &[Spectrum], resolution: &Point2i) -> ::std::io::Result<()> {
+    let (width, height) = (resolution.x as usize, resolution.y as usize);
+    write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+
+    let mut channel = vec![0u8; width];
+    for y in 0..height {
+        let pixels: Vec<[u8; 4]> = rgb[y * width..(y + 1) * width].iter().map(to_rgbe).collect();
+        if !(8..0x8000).contains(&width) {
+            for p in pixels.iter() {
+                writer.write_all(p)?;
+            }
+            continue;
+        }
+
+        writer.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for c in 0..4 {
+            for (v, p) in channel.iter_mut().zip(pixels.iter()) {
+                *v = p[c];
+            }
+            writer.write_all(&rle_channel(&channel))?;
+        }
+    }
+    return Ok(());
+}
+
+// Runs of at least four equal bytes become (128 + count, byte); anything
+// else is copied as (count, bytes...), at most 127 or 128 bytes at a time.
+fn rle_channel(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN_LENGTH: usize = 4;
+    let run_at = |i: usize| data[i..].iter().take(127).take_while(|&&b| b == data[i]).count();
+
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i);
+        if run >= MIN_RUN_LENGTH {
+            out.push(128 + run as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+
+        let start = i;
+        while i < data.len() && i - start < 128 && run_at(i) < MIN_RUN_LENGTH {
+            i += 1;
+        }
+        out.push((i - start) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    return out;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pbrt::Spectrum;
+    use pbrt::geometry::Point2i;
+    use pbrt::io::rgbe::read_rgbe;
+    use pbrt::io::rgbe::write_rgbe;
+
+    #[test]
+    fn round_trips_within_mantissa_precision() {
+        let resolution = Point2i::new(300, 3);
+        let rgb: Vec<Spectrum> = (0..300 * 3).map(|i| {
+            // Flat stretches exercise the runs, ramps the literals
+            let v = if i % 300 < 100 { 0.25 } else { (i % 300) as f64 * 0.731 };
+            Spectrum::from_rgb(v, v * 0.5, (i / 300) as f64 * 100.0)
+        }).collect();
+
+        let mut bytes = Vec::new();
+        write_rgbe(&mut bytes, &rgb, &resolution).unwrap();
+        assert!(bytes.len() < 4 * rgb.len());
+        let (read_resolution, read) = read_rgbe(&bytes[..], Path::new("memory.hdr")).unwrap();
+
+        assert!(read_resolution == resolution);
+        for (a, b) in rgb.iter().zip(read.iter()) {
+            let max = a.samples.iter().cloned().fold(0.0, f64::max);
+            for c in 0..3 {
+                assert!((a.samples[c] - b.samples[c]).abs() <= max / 256.0 + 1e-30);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_truncated_images() {
+        for resolution in ["-Y 1 +X 0", "-Y 0 +X 5", "-Y 100000 +X 100000", "-Y 2 +X 18446744073709551615"].iter() {
+            let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+            bytes.extend_from_slice(&[1; 16]);
+            assert!(read_rgbe(&bytes[..], Path::new("memory.hdr")).is_err());
+        }
+    }
+}
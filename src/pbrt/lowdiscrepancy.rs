@@ -0,0 +1,450 @@
+use std::sync::OnceLock;
+
+use pbrt;
+use pbrt::geometry::Point2f;
+use pbrt::rng::Rng;
+use pbrt::sampling;
+
+
+// Mirrors the base `base` digits of `a` about the radix point, giving the
+// van der Corput sequence in that base.
+pub fn radical_inverse(base: u64, mut a: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed_digits: u64 = 0;
+    let mut inv_base_n = 1.0;
+    while a > 0 {
+        let next = a / base;
+        let digit = a - next * base;
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_n *= inv_base;
+        a = next;
+    }
+    return (reversed_digits as f64 * inv_base_n).min(pbrt::ONE_MINUS_EPSILON);
+}
+
+// Undoes `radical_inverse` for an `n_digits` digit value already scaled to
+// an integer, recovering the index it came from.
+pub fn inverse_radical_inverse(base: u64, mut inverse: u64, n_digits: u32) -> u64 {
+    let mut index = 0;
+    for _ in 0..n_digits {
+        let digit = inverse % base;
+        inverse /= base;
+        index = index * base + digit;
+    }
+    return index;
+}
+
+// `radical_inverse` with every digit passed through `perm`, including the
+// infinite tail of zero digits past the last nonzero one.
+pub fn scrambled_radical_inverse(base: u64, mut a: u64, perm: &[u16]) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed_digits: u64 = 0;
+    let mut inv_base_n = 1.0;
+    while a > 0 {
+        let next = a / base;
+        let digit = a - next * base;
+        reversed_digits = reversed_digits * base + perm[digit as usize] as u64;
+        inv_base_n *= inv_base;
+        a = next;
+    }
+    let tail = inv_base * perm[0] as f64 / (1.0 - inv_base);
+    return (inv_base_n * (reversed_digits as f64 + tail)).min(pbrt::ONE_MINUS_EPSILON);
+}
+
+// `radical_inverse` with Owen scrambling: each digit is permuted by a
+// permutation chosen from the digits before it, so points keep their
+// stratification at every scale.
+pub fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u32) -> f64 {
+    // Stop before the next digit could overflow reversed_digits
+    let limit = u64::MAX / base - base;
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    while 1.0 - inv_base_m < 1.0 && reversed_digits < limit {
+        let next = a / base;
+        let digit = (a - next * base) as u32;
+        let digit_hash = pbrt::mix_bits(hash as u64 ^ reversed_digits) as u32;
+        let digit = permutation_element(digit, base as u32, digit_hash);
+        reversed_digits = reversed_digits * base + digit as u64;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    return (inv_base_m * reversed_digits as f64).min(pbrt::ONE_MINUS_EPSILON);
+}
+
+// Element i of a random permutation of [0, l) selected by `p`, computed
+// without storing the permutation (Kensler's hash-based shuffle).
+pub fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    return (i.wrapping_add(p)) % l;
+}
+
+
+pub const PRIME_TABLE_SIZE: usize = 1000;
+
+// The first PRIME_TABLE_SIZE primes; dimension i of a Halton sequence uses
+// primes()[i] as its base.
+pub fn primes() -> &'static [u64] {
+    static PRIMES: OnceLock<Vec<u64>> = OnceLock::new();
+    return PRIMES.get_or_init(|| {
+        let mut primes: Vec<u64> = Vec::with_capacity(PRIME_TABLE_SIZE);
+        let mut n = 2;
+        while primes.len() < PRIME_TABLE_SIZE {
+            if primes.iter().take_while(|&&p| p * p <= n).all(|&p| n % p != 0) {
+                primes.push(n);
+            }
+            n += 1;
+        }
+        primes
+    });
+}
+
+// A random permutation of the digits of each prime base, stored back to
+// back; dimension i's starts at the sum of the primes before it.
+pub fn compute_radical_inverse_permutations(rng: &mut Rng) -> Vec<u16> {
+    let mut perms = Vec::with_capacity(primes().iter().sum::<u64>() as usize);
+    for &base in primes().iter() {
+        let start = perms.len();
+        perms.extend(0..base as u16);
+        sampling::shuffle(&mut perms[start..], 1, rng);
+    }
+    return perms;
+}
+
+
+// How low-discrepancy samplers randomize their points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RandomizeStrategy {
+    None,
+    // One random permutation of the digits per dimension
+    PermuteDigits,
+    Owen,
+}
+
+
+
+pub const N_SOBOL_DIMENSIONS: usize = 1024;
+// Columns per generator matrix, so indices may use up to this many bits
+pub const SOBOL_MATRIX_SIZE: usize = 52;
+
+// Joe and Kuo's initial direction numbers m_1..m_s (new-joe-kuo-6.21201)
+// for Sobol dimensions 1 through 12; later dimensions draw theirs from a
+// fixed PCG32 stream, which keeps every dimension a (0,1)-sequence but
+// without their tuning of the 2D projections.
+const JOE_KUO_DIRECTIONS: [&[u64]; 12] = [
+    &[1],
+    &[1, 3],
+    &[1, 3, 1],
+    &[1, 1, 1],
+    &[1, 1, 3, 3],
+    &[1, 3, 5, 13],
+    &[1, 1, 5, 5, 17],
+    &[1, 1, 5, 5, 5],
+    &[1, 1, 7, 11, 19],
+    &[1, 1, 5, 1, 1],
+    &[1, 1, 1, 3, 11],
+    &[1, 3, 5, 5, 31],
+];
+
+// Whether `poly` (bit i the coefficient of x^i) of degree `degree` is
+// primitive over GF(2), that is, x has order 2^degree - 1 modulo it.
+fn is_primitive(poly: u32, degree: u32) -> bool {
+    if poly & 1 == 0 {
+        return false;
+    }
+    let period = (1u32 << degree) - 1;
+    let mut v = 1u32;
+    for k in 1..=period {
+        v <<= 1;
+        if v & (1 << degree) != 0 {
+            v ^= poly;
+        }
+        if v == 1 {
+            return k == period;
+        }
+    }
+    return false;
+}
+
+// Primitive polynomials by increasing degree and then value, the order Joe
+// and Kuo assign them to dimensions.
+fn primitive_polynomials(n: usize) -> Vec<(u32, u32)> {
+    let mut polys = Vec::with_capacity(n);
+    let mut degree = 1;
+    while polys.len() < n {
+        for poly in (1u32 << degree)..(2u32 << degree) {
+            if polys.len() < n && is_primitive(poly, degree) {
+                polys.push((poly, degree));
+            }
+        }
+        degree += 1;
+    }
+    return polys;
+}
+
+// The generator matrices of the first N_SOBOL_DIMENSIONS Sobol dimensions,
+// SOBOL_MATRIX_SIZE columns each; column c holds the binary fraction that
+// index bit c contributes, most significant bit first.
+pub fn sobol_matrices() -> &'static [u32] {
+    static MATRICES: OnceLock<Vec<u32>> = OnceLock::new();
+    return MATRICES.get_or_init(|| {
+        let mut matrices = Vec::with_capacity(N_SOBOL_DIMENSIONS * SOBOL_MATRIX_SIZE);
+        let mut rng = Rng::new(0);
+        // Dimension 0 is the van der Corput sequence, m_k = 1
+        let polys = primitive_polynomials(N_SOBOL_DIMENSIONS - 1);
+        for dim in 0..N_SOBOL_DIMENSIONS {
+            let mut m: Vec<u64> = Vec::with_capacity(SOBOL_MATRIX_SIZE);
+            if dim == 0 {
+                m.resize(SOBOL_MATRIX_SIZE, 1);
+            } else {
+                let (poly, s) = polys[dim - 1];
+                let s = s as usize;
+                if dim <= JOE_KUO_DIRECTIONS.len() {
+                    m.extend_from_slice(JOE_KUO_DIRECTIONS[dim - 1]);
+                } else {
+                    // Any odd m_k < 2^k will do
+                    for k in 1..=s {
+                        m.push(((rng.uniform_u32() as u64) & ((1 << (k - 1)) - 1)) << 1 | 1);
+                    }
+                }
+                for k in s..SOBOL_MATRIX_SIZE {
+                    let mut mk = m[k - s] ^ (m[k - s] << s);
+                    for i in 1..s {
+                        if (poly >> (s - i)) & 1 != 0 {
+                            mk ^= m[k - i] << i;
+                        }
+                    }
+                    m.push(mk);
+                }
+            }
+            // m_k / 2^k, truncated to 32 bits
+            matrices.extend(m.iter().enumerate().map(|(c, &mk)| ((mk << (63 - c)) >> 32) as u32));
+        }
+        matrices
+    });
+}
+
+// The bits of dimension `dimension` of Sobol sample `index`, before any
+// scrambling.
+pub fn sobol_sample_bits(mut index: u64, dimension: usize) -> u32 {
+    assert!(index >> SOBOL_MATRIX_SIZE == 0, "Sobol index {} out of range", index);
+    assert!(dimension < N_SOBOL_DIMENSIONS, "Sobol dimension {} past the {} tabulated", dimension, N_SOBOL_DIMENSIONS);
+    let matrix = &sobol_matrices()[dimension * SOBOL_MATRIX_SIZE..(dimension + 1) * SOBOL_MATRIX_SIZE];
+    let mut v = 0;
+    let mut c = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            v ^= matrix[c];
+        }
+        index >>= 1;
+        c += 1;
+    }
+    return v;
+}
+
+// Owen scrambling of a base 2 fraction: each bit is flipped or not by a
+// hash of the bits above it.
+pub fn owen_scramble_bits(mut v: u32, hash: u32) -> u32 {
+    if hash & 1 != 0 {
+        v ^= 1 << 31;
+    }
+    for b in 1..32 {
+        let mask = !0u32 << (32 - b);
+        if (pbrt::mix_bits(((v & mask) ^ hash) as u64) as u32) & (1 << b) != 0 {
+            v ^= 1 << (31 - b);
+        }
+    }
+    return v;
+}
+
+// Sobol sample `index` in dimension `dimension`, randomized with `hash`.
+// Permuting digits in base 2 is an XOR with the hash.
+pub fn sobol_sample(index: u64, dimension: usize, randomize: RandomizeStrategy, hash: u32) -> f64 {
+    let v = sobol_sample_bits(index, dimension);
+    let v = match randomize {
+        RandomizeStrategy::None => v,
+        RandomizeStrategy::PermuteDigits => v ^ hash,
+        RandomizeStrategy::Owen => owen_scramble_bits(v, hash),
+    };
+    return (v as f64 * (1.0 / 4294967296.0)).min(pbrt::ONE_MINUS_EPSILON);
+}
+
+// Fills `p` with the points of generator matrix `c` XORed with `scramble`,
+// stepping in Gray code order so each point costs a single XOR.
+fn gray_code_sample_1d(c: &[u32], scramble: u32, p: &mut [f64]) {
+    let mut v = scramble;
+    for (i, p) in p.iter_mut().enumerate() {
+        *p = (v as f64 * (1.0 / 4294967296.0)).min(pbrt::ONE_MINUS_EPSILON);
+        v ^= c[(i + 1).trailing_zeros() as usize];
+    }
+}
+
+fn gray_code_sample_2d(c0: &[u32], c1: &[u32], scramble: (u32, u32), p: &mut [Point2f]) {
+    let mut v = scramble;
+    for (i, p) in p.iter_mut().enumerate() {
+        p.x = (v.0 as f64 * (1.0 / 4294967296.0)).min(pbrt::ONE_MINUS_EPSILON);
+        p.y = (v.1 as f64 * (1.0 / 4294967296.0)).min(pbrt::ONE_MINUS_EPSILON);
+        let c = (i + 1).trailing_zeros() as usize;
+        v.0 ^= c0[c];
+        v.1 ^= c1[c];
+    }
+}
+
+// Randomly scrambled van der Corput points for `n_pixel_samples` samples of
+// `n_samples_per_pixel_sample` values each. Each sample's values stay
+// stratified and the samples are shuffled as whole blocks.
+pub fn van_der_corput(n_samples_per_pixel_sample: usize, n_pixel_samples: usize, samples: &mut [f64], rng: &mut Rng) {
+    let n = n_samples_per_pixel_sample * n_pixel_samples;
+    gray_code_sample_1d(&sobol_matrices()[..SOBOL_MATRIX_SIZE], rng.uniform_u32(), &mut samples[..n]);
+    for block in samples[..n].chunks_mut(n_samples_per_pixel_sample.max(1)) {
+        sampling::shuffle(block, 1, rng);
+    }
+    sampling::shuffle(&mut samples[..n], n_samples_per_pixel_sample.max(1), rng);
+}
+
+// The 2D counterpart of `van_der_corput`, using the first two Sobol
+// dimensions: a randomly scrambled (0,2)-sequence.
+pub fn sobol_2d(n_samples_per_pixel_sample: usize, n_pixel_samples: usize, samples: &mut [Point2f], rng: &mut Rng) {
+    let n = n_samples_per_pixel_sample * n_pixel_samples;
+    let matrices = sobol_matrices();
+    let scramble = (rng.uniform_u32(), rng.uniform_u32());
+    gray_code_sample_2d(&matrices[..SOBOL_MATRIX_SIZE], &matrices[SOBOL_MATRIX_SIZE..2 * SOBOL_MATRIX_SIZE], scramble, &mut samples[..n]);
+    for block in samples[..n].chunks_mut(n_samples_per_pixel_sample.max(1)) {
+        sampling::shuffle(block, 1, rng);
+    }
+    sampling::shuffle(&mut samples[..n], n_samples_per_pixel_sample.max(1), rng);
+}
+
+
+// The generator g for which the rank-1 lattice of n points
+// (i / n, frac(i * g / n)) is most evenly spread: its closest pair of points,
+// measured on the torus, is as far apart as possible.
+pub fn rank1_lattice_generator(n: u32) -> u32 {
+    let wrap = |v: u64| -> f64 { v.min(n as u64 - v) as f64 / n as f64 };
+    let mut best = (1, 0.0);
+    // g and n - g give mirror images
+    for g in 1..=n / 2 {
+        let min_dist2 = (1..n as u64)
+            .map(|i| wrap(i).powi(2) + wrap(i * g as u64 % n as u64).powi(2))
+            .fold(f64::INFINITY, f64::min);
+        if min_dist2 > best.1 {
+            best = (g, min_dist2);
+        }
+    }
+    return best.0;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::lowdiscrepancy;
+    use pbrt::lowdiscrepancy::RandomizeStrategy;
+    use pbrt::rng::Rng;
+
+    // Whether the first n values of `f` land one in each of n intervals.
+    fn stratified(n: u64, f: &dyn Fn(u64) -> f64) -> bool {
+        let mut counts = vec![0; n as usize];
+        for a in 0..n {
+            counts[(f(a) * n as f64) as usize] += 1;
+        }
+        return counts.iter().all(|&c| c == 1);
+    }
+
+    #[test]
+    fn scrambling_keeps_stratification() {
+        let perms = lowdiscrepancy::compute_radical_inverse_permutations(&mut Rng::new(3));
+        // Base 3's permutation follows base 2's two digits
+        let perm = &perms[2..5];
+        let mut sorted = perm.to_vec();
+        sorted.sort();
+        assert_eq!(sorted, vec![0, 1, 2]);
+        assert!(stratified(27, &|a| lowdiscrepancy::scrambled_radical_inverse(3, a, perm)));
+
+        for &(base, n) in [(2, 16), (3, 27), (5, 25)].iter() {
+            assert!(stratified(n, &|a| lowdiscrepancy::owen_scrambled_radical_inverse(base, a, 0x1234_5678)));
+        }
+    }
+
+    #[test]
+    fn sobol_matches_reference_points() {
+        // The first eight points of the three dimensional Sobol sequence,
+        // as usually tabulated in Gray code order
+        let reference = [
+            [0.0, 0.0, 0.0], [0.5, 0.5, 0.5], [0.75, 0.25, 0.25], [0.25, 0.75, 0.75],
+            [0.375, 0.375, 0.625], [0.875, 0.875, 0.125], [0.625, 0.125, 0.875], [0.125, 0.625, 0.375],
+        ];
+        for (i, p) in reference.iter().enumerate() {
+            let gray = (i ^ (i >> 1)) as u64;
+            for (dim, &v) in p.iter().enumerate() {
+                assert_eq!(lowdiscrepancy::sobol_sample(gray, dim, RandomizeStrategy::None, 0), v);
+            }
+        }
+
+        // Dimension 12, the last from Joe and Kuo's table, has x^5 + x^4 +
+        // x^3 + x^2 + 1 and m = 1, 3, 5, 5, 31: bit k of the index
+        // contributes m_k / 2^k
+        let expected = [0.5, 0.75, 0.625, 0.3125, 0.96875];
+        for (k, &v) in expected.iter().enumerate() {
+            assert_eq!(lowdiscrepancy::sobol_sample(1 << k, 12, RandomizeStrategy::None, 0), v);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn sobol_dimensions_do_not_wrap() {
+        lowdiscrepancy::sobol_sample(1, lowdiscrepancy::N_SOBOL_DIMENSIONS, RandomizeStrategy::None, 0);
+    }
+
+    #[test]
+    fn sobol_points_form_nets() {
+        // Dimension 2 is Joe and Kuo's x^2 + x + 1 with m = 1, 3
+        let d2: Vec<f64> = (0..4).map(|i| lowdiscrepancy::sobol_sample(i, 2, RandomizeStrategy::None, 0)).collect();
+        assert_eq!(d2, vec![0.0, 0.5, 0.75, 0.25]);
+
+        // The first two dimensions put one of every 16 points in each
+        // elementary interval of area 1/16, scrambled or not
+        for randomize in [RandomizeStrategy::None, RandomizeStrategy::PermuteDigits, RandomizeStrategy::Owen].iter() {
+            for &(nx, ny) in [(16, 1), (8, 2), (4, 4), (2, 8), (1, 16)].iter() {
+                let mut counts = [0; 16];
+                for i in 32..48 {
+                    let x = lowdiscrepancy::sobol_sample(i, 0, *randomize, 0x9e37_79b9);
+                    let y = lowdiscrepancy::sobol_sample(i, 1, *randomize, 0x7f4a_7c15);
+                    counts[(x * nx as f64) as usize + nx * (y * ny as f64) as usize] += 1;
+                }
+                assert!(counts.iter().all(|&c| c == 1));
+            }
+        }
+        // Every dimension is at least a (0,1)-sequence
+        for dim in [3, 13, 500, lowdiscrepancy::N_SOBOL_DIMENSIONS - 1].iter() {
+            assert!(stratified(64, &|i| lowdiscrepancy::sobol_sample(i, *dim, RandomizeStrategy::None, 0)));
+        }
+    }
+}
@@ -1,13 +1,6 @@
-use std::cmp;
-use std::num;
-use std::ops;
-
-use num_traits::{Float, One, Zero};
-
 use pbrt;
 use pbrt::geometry::Point2f;
 use pbrt::geometry::Vector3f;
-use pbrt::geometry::ray::Ray;
 use pbrt::Spectrum;
 
 pub trait PhaseFunction {
@@ -15,7 +8,7 @@ pub trait PhaseFunction {
     fn sample_p(&self, wo: &Vector3f, wi: &Vector3f, u: &Point2f) -> f64;
 }
 
-pub fn get_medium_scattering_properties(name: &String, sigma_a: &Spectrum, sigma_s: &Spectrum) -> bool {
+pub fn get_medium_scattering_properties(_name: &str, _sigma_a: &Spectrum, _sigma_s: &Spectrum) -> bool {
     // TODO
     return true;
 }
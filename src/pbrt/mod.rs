@@ -1,16 +1,117 @@
+pub mod accelerators;
+pub mod bluenoise;
+pub mod camera;
+pub mod film;
+pub mod filter;
 pub mod geometry;
+pub mod io;
+pub mod lowdiscrepancy;
 pub mod medium;
+pub mod pmj02;
+pub mod rng;
 pub mod sampler;
+pub mod sampling;
 pub mod spectrum;
+pub mod stats;
+pub mod tonemap;
 
-use num_traits::{Float, One};
-use typenum::{U3, U60};
+use std::f64;
 
+use num_traits::Float;
 
-type Spectrum = spectrum::RGBSpectrum;
 
-pub const INV4PI:f64 = 0.07957747154594766788;
+pub type Spectrum = spectrum::RGBSpectrum;
+
+pub const INV4PI:f64 = 0.07957747154594767;
+pub const MACHINE_EPSILON: f64 = f64::EPSILON * 0.5;
+pub const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON * 0.5;
 
 pub fn lerp<T: Float>(t: T, v1: T, v2: T) -> T {
     return (T::one() - t) * v1 + t * v2;
 }
+
+pub fn clamp<T: Float>(val: T, low: T, high: T) -> T {
+    if val < low {
+        return low;
+    }
+    if val > high {
+        return high;
+    }
+    return val;
+}
+
+// Conservative bound on the relative error of n chained floating-point operations.
+pub fn gamma(n: i32) -> f64 {
+    return (n as f64 * MACHINE_EPSILON) / (1.0 - n as f64 * MACHINE_EPSILON);
+}
+
+// sRGB transfer curve, mapping linear values to display values.
+pub fn gamma_correct(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        return 12.92 * value;
+    }
+    return 1.055 * value.powf(1.0 / 2.4) - 0.055;
+}
+
+// Inverse of `gamma_correct`, for decoding 8-bit images.
+pub fn inverse_gamma_correct(value: f64) -> f64 {
+    if value <= 0.04045 {
+        return value / 12.92;
+    }
+    return ((value + 0.055) / 1.055).powf(2.4);
+}
+
+// Scrambles the bits of `v` so that nearby inputs give unrelated outputs.
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    return v;
+}
+
+pub fn radians(deg: f64) -> f64 {
+    return (f64::consts::PI / 180.0) * deg;
+}
+
+// Computes a * b - c * d without the catastrophic cancellation of the naive form.
+pub fn difference_of_products(a: f64, b: f64, c: f64, d: f64) -> f64 {
+    let cd = c * d;
+    let err = (-c).mul_add(d, cd);
+    let dop = a.mul_add(b, -cd);
+    return dop + err;
+}
+
+// Solves a * t^2 + b * t + c = 0, returning the roots in ascending order.
+pub fn quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discrim = difference_of_products(b, b, 4.0 * a, c);
+    if discrim < 0.0 {
+        return None;
+    }
+    let root_discrim = discrim.sqrt();
+
+    let q = if b < 0.0 {
+        -0.5 * (b - root_discrim)
+    } else {
+        -0.5 * (b + root_discrim)
+    };
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+
+    let t0 = q / a;
+    let t1 = c / q;
+    if t0 > t1 {
+        return Some((t1, t0));
+    }
+    return Some((t0, t1));
+}
@@ -0,0 +1,147 @@
+use std::sync::OnceLock;
+
+use pbrt::geometry::Point2f;
+use pbrt::rng::Rng;
+
+
+pub const N_PMJ02_SETS: usize = 5;
+pub const PMJ02_SET_SIZE: usize = 4096;
+
+// Point `index` of progressive multi-jittered (0,2) set `set`. Every
+// power-of-two prefix of a set is a (0,m,2)-net: it has one point in each
+// elementary interval of its size.
+pub fn pmj02_sample(set: usize, index: usize) -> Point2f {
+    static SETS: [OnceLock<Vec<Point2f>>; N_PMJ02_SETS] = [const { OnceLock::new() }; N_PMJ02_SETS];
+    let samples = SETS[set].get_or_init(|| generate_pmj02(PMJ02_SET_SIZE, &mut Rng::new(set as u64)));
+    return samples[index];
+}
+
+
+// Which elementary intervals of 2^log_n points hold a point, tracked at the
+// finest resolution: column and row indices of width 2^-log_n.
+struct Strata {
+    log_n: u32,
+    // Shape a has 2^a columns by 2^(log_n - a) rows
+    occupied: Vec<Vec<bool>>,
+}
+
+impl Strata {
+    fn new(log_n: u32, samples: &[Point2f]) -> Strata {
+        let mut strata = Strata { log_n, occupied: vec![vec![false; 1 << log_n]; log_n as usize + 1] };
+        let n = (1usize << log_n) as f64;
+        for p in samples.iter() {
+            strata.mark((p.x * n) as usize, (p.y * n) as usize);
+        }
+        return strata;
+    }
+
+    fn index(&self, a: u32, sx: usize, sy: usize) -> usize {
+        return ((sx >> (self.log_n - a)) << (self.log_n - a)) | (sy >> a);
+    }
+
+    fn is_free(&self, sx: usize, sy: usize) -> bool {
+        return (0..=self.log_n).all(|a| !self.occupied[a as usize][self.index(a, sx, sy)]);
+    }
+
+    fn mark(&mut self, sx: usize, sy: usize) {
+        for a in 0..=self.log_n {
+            let i = self.index(a, sx, sy);
+            self.occupied[a as usize][i] = true;
+        }
+    }
+}
+
+// A point in subquadrant (x_half, y_half) of cell (i, j) of an n by n grid
+// that shares no elementary interval with the points in `strata`.
+fn place(i: usize, j: usize, x_half: usize, y_half: usize, n: usize, strata: &mut Strata, rng: &mut Rng) -> Option<Point2f> {
+    let m = (1 << strata.log_n) / (2 * n);
+    let (x0, y0) = ((2 * i + x_half) * m, (2 * j + y_half) * m);
+
+    // A few random tries usually land on a free stratum; otherwise pick
+    // uniformly among all of them
+    let mut choice = None;
+    for _ in 0..16 {
+        let sx = x0 + rng.uniform_u32_below(m as u32) as usize;
+        let sy = y0 + rng.uniform_u32_below(m as u32) as usize;
+        if strata.is_free(sx, sy) {
+            choice = Some((sx, sy));
+            break;
+        }
+    }
+    if choice.is_none() {
+        let free: Vec<(usize, usize)> =
+            (x0..x0 + m).flat_map(|sx| (y0..y0 + m).map(move |sy| (sx, sy))).filter(|&(sx, sy)| strata.is_free(sx, sy)).collect();
+        if free.is_empty() {
+            return None;
+        }
+        choice = Some(free[rng.uniform_u32_below(free.len() as u32) as usize]);
+    }
+
+    let (sx, sy) = choice.unwrap();
+    strata.mark(sx, sy);
+    let inv_n = 1.0 / (1u64 << strata.log_n) as f64;
+    return Some(Point2f::new((sx as f64 + rng.uniform_f64()) * inv_n, (sy as f64 + rng.uniform_f64()) * inv_n));
+}
+
+// Christensen, Kensler and Kilpatrick's construction. With n * n points,
+// each point's next partner goes in the diagonally opposite subquadrant of
+// its grid cell, and then two more fill the cell's remaining subquadrants.
+// A step that paints itself into a corner is retried.
+fn generate_pmj02(n_samples: usize, rng: &mut Rng) -> Vec<Point2f> {
+    let mut samples = vec![Point2f::new(rng.uniform_f64(), rng.uniform_f64())];
+    let mut n: usize = 1;
+    while samples.len() < n_samples {
+        for &odd in [false, true].iter() {
+            let n_old = n * n;
+            let total = if odd { 4 * n_old } else { 2 * n_old };
+            loop {
+                let mut strata = Strata::new(total.trailing_zeros(), &samples);
+                let (mut first, mut second) = (Vec::with_capacity(n_old), Vec::with_capacity(n_old));
+                for p in samples[..n_old].iter() {
+                    let (i, j) = ((p.x * n as f64) as usize, (p.y * n as f64) as usize);
+                    let x_half = ((p.x * n as f64 - i as f64) * 2.0) as usize;
+                    let y_half = ((p.y * n as f64 - j as f64) * 2.0) as usize;
+                    if !odd {
+                        first.push(place(i, j, 1 - x_half, 1 - y_half, n, &mut strata, rng));
+                    } else {
+                        // The cell's two free subquadrants share a column
+                        // or a row with this point; take them in either order
+                        let (x, y) = if rng.uniform_u32() & 1 == 0 { (1 - x_half, y_half) } else { (x_half, 1 - y_half) };
+                        first.push(place(i, j, x, y, n, &mut strata, rng));
+                        second.push(place(i, j, 1 - x, 1 - y, n, &mut strata, rng));
+                    }
+                }
+                if first.iter().chain(second.iter()).all(|p| p.is_some()) {
+                    samples.extend(first.into_iter().chain(second).map(|p| p.unwrap()));
+                    break;
+                }
+            }
+        }
+        n *= 2;
+    }
+    samples.truncate(n_samples);
+    return samples;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::pmj02;
+    use pbrt::rng::Rng;
+
+    #[test]
+    fn prefixes_are_nets() {
+        let samples = pmj02::generate_pmj02(1024, &mut Rng::new(11));
+        for log_n in 0..=10 {
+            let n = 1 << log_n;
+            for a in 0..=log_n {
+                let mut counts = vec![0; n];
+                for p in samples[..n].iter() {
+                    let (x, y) = ((p.x * (1 << a) as f64) as usize, (p.y * (n >> a) as f64) as usize);
+                    counts[(x << (log_n - a)) | y] += 1;
+                }
+                assert!(counts.iter().all(|&c| c == 1));
+            }
+        }
+    }
+}
@@ -1,23 +0,0 @@
-
-use pbrt::geometry::Point2i;
-use pbrt::geometry::Point2f;
-
-pub trait Sampler {
-//    fn start_pixel(p: Point2i);
-//    fn get_1d() -> f64;
-//    fn get_2d() -> Point2f;
-//    fn get_camera_sample(p: &Point2i) -> CameraSample;
-//    fn request_1d_array(n: i32);
-//    fn request_2d_array(n: i32);
-//    fn round_count(n: i32) -> i32;
-//    fn get_1d_array(n: i32) -> f64;
-//    fn get_2d_array(n: i32) -> Point2f;
-//    fn start_next_sample() -> bool;
-//    fn clone(seed: i32) -> Sampler;
-//    fn set_sample_number(sample_num: i64) -> bool;
-}
-
-pub struct PixelSampler {
-
-}
-
@@ -0,0 +1,169 @@
+use pbrt::Spectrum;
+use pbrt::camera::CameraSample;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+
+
+// Wraps another sampler and ends each pixel early once the standard error
+// of its recorded mean luminance is within `max_error` of the mean, after at least
+// `min_samples` samples. The wrapped sampler's samples per pixel is the most
+// any pixel takes. With samplers whose power-of-two prefixes are nets, it
+// only stops after a power of two samples, so the ones taken stay evenly
+// spread; other samplers may stop after any sample.
+pub struct AdaptiveSampler {
+    sampler: Box<dyn Sampler>,
+    min_samples: i64,
+    max_error: f64,
+}
+
+impl AdaptiveSampler {
+    pub fn new(sampler: Box<dyn Sampler>, min_samples: i64, max_error: f64) -> AdaptiveSampler {
+        AdaptiveSampler { sampler, min_samples, max_error }
+    }
+
+    fn converged(&self) -> bool {
+        let stats = &self.state().radiance_stats;
+        let n = stats.count();
+        if n < self.min_samples || (self.sampler.prefixes_are_nets() && !(n as u64).is_power_of_two()) {
+            return false;
+        }
+        return stats.relative_error() <= self.max_error;
+    }
+}
+
+impl Sampler for AdaptiveSampler {
+    fn state(&self) -> &SamplerState {
+        return self.sampler.state();
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return self.sampler.state_mut();
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        return self.sampler.get_1d();
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        return self.sampler.get_2d();
+    }
+
+    fn clone_with_seed(&self, seed: u64) -> Box<dyn Sampler> {
+        return Box::new(AdaptiveSampler::new(self.sampler.clone_with_seed(seed), self.min_samples, self.max_error));
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.sampler.start_pixel(p);
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        if self.converged() {
+            let spp = self.sampler.samples_per_pixel();
+            self.sampler.set_sample_number(spp);
+            return false;
+        }
+        return self.sampler.start_next_sample();
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        return self.sampler.set_sample_number(sample_num);
+    }
+
+    fn get_camera_sample(&mut self, p_raster: &Point2i) -> CameraSample {
+        return self.sampler.get_camera_sample(p_raster);
+    }
+
+    fn request_1d_array(&mut self, n: usize) {
+        self.sampler.request_1d_array(n);
+    }
+
+    fn request_2d_array(&mut self, n: usize) {
+        self.sampler.request_2d_array(n);
+    }
+
+    fn round_count(&self, n: usize) -> usize {
+        return self.sampler.round_count(n);
+    }
+
+    fn get_1d_array(&mut self, n: usize) -> Option<&[f64]> {
+        return self.sampler.get_1d_array(n);
+    }
+
+    fn get_2d_array(&mut self, n: usize) -> Option<&[Point2f]> {
+        return self.sampler.get_2d_array(n);
+    }
+
+    fn prefixes_are_nets(&self) -> bool {
+        return self.sampler.prefixes_are_nets();
+    }
+
+    fn record_radiance(&mut self, l: &Spectrum) {
+        self.sampler.record_radiance(l);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::Spectrum;
+    use pbrt::geometry::Bounds2i;
+    use pbrt::geometry::Point2i;
+    use pbrt::lowdiscrepancy::RandomizeStrategy;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::adaptive::AdaptiveSampler;
+    use pbrt::sampler::random::RandomSampler;
+    use pbrt::sampler::sobol::SobolSampler;
+
+    // Samples taken in `pixel` when each sample's radiance is `f` of it.
+    fn samples_taken(sampler: &mut dyn Sampler, pixel: &Point2i, f: &dyn Fn(f64) -> f64) -> i64 {
+        sampler.start_pixel(pixel);
+        let mut n = 0;
+        loop {
+            let u = sampler.get_1d();
+            sampler.record_radiance(&Spectrum::new(f(u)));
+            n += 1;
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        return n;
+    }
+
+    #[test]
+    fn stops_once_converged() {
+        let mut sampler = AdaptiveSampler::new(Box::new(RandomSampler::new(256, 0)), 8, 0.05);
+        assert_eq!(sampler.samples_per_pixel(), 256);
+
+        // Flat pixels stop at the minimum, very noisy ones run to the maximum
+        assert_eq!(samples_taken(&mut sampler, &Point2i::new(0, 0), &|_| 0.5), 8);
+        assert_eq!(samples_taken(&mut sampler, &Point2i::new(1, 0), &|_| 0.0), 8);
+        assert_eq!(samples_taken(&mut sampler, &Point2i::new(2, 0), &|u| if u < 0.01 { 100.0 } else { 0.0 }), 256);
+
+        // Uniform noise has a relative error of 1 / sqrt(3n), under 0.05 from
+        // about 133 samples
+        for x in 3..13 {
+            let n = samples_taken(&mut sampler, &Point2i::new(x, 0), &|u| u);
+            assert!((80..200).contains(&n), "took {} samples", n);
+        }
+    }
+
+    #[test]
+    fn stops_only_after_powers_of_two_of_nets() {
+        let bounds = Bounds2i::new(Point2i::new(0, 0), Point2i::new(20, 1));
+        let sobol = SobolSampler::new(64, &bounds, RandomizeStrategy::Owen, 0);
+        let mut sampler = AdaptiveSampler::new(Box::new(sobol), 5, 0.05);
+
+        // A minimum of 5 waits for 8, the end of a whole net
+        assert_eq!(samples_taken(&mut sampler, &Point2i::new(0, 0), &|_| 0.5), 8);
+        for x in 1..20 {
+            let n = samples_taken(&mut sampler, &Point2i::new(x, 0), &|u| 1.0 + 0.3 * u);
+            assert!((n as u64).is_power_of_two(), "took {} samples", n);
+        }
+
+        // Random samples have no structure to wait for
+        let mut sampler = AdaptiveSampler::new(Box::new(RandomSampler::new(64, 0)), 5, 0.05);
+        assert_eq!(samples_taken(&mut sampler, &Point2i::new(0, 0), &|_| 0.5), 5);
+    }
+}
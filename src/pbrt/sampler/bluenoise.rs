@@ -0,0 +1,197 @@
+use pbrt;
+use pbrt::bluenoise;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::lowdiscrepancy;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+
+
+// Blue noise texture indices for arrays start here, clear of the dimensions
+const ARRAY_TEXTURE_START: usize = 1 << 20;
+
+// A rank-1 lattice in every pixel, shifted by a screen-space blue noise
+// value per dimension. Any one sample's error then varies between
+// neighbouring pixels at high frequencies only, which looks better at low
+// sample counts and filters away more easily. Samples are reordered per
+// dimension to decorrelate the dimensions, the same way in every pixel.
+#[derive(Clone, Debug)]
+pub struct BlueNoiseSampler {
+    state: SamplerState,
+    seed: u64,
+    dimension: usize,
+    // Lattice generators for the pixel samples and for each 2D array
+    generator: u64,
+    array_generators: Vec<u64>,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(samples_per_pixel: i64, seed: u64) -> BlueNoiseSampler {
+        BlueNoiseSampler {
+            state: SamplerState::new(samples_per_pixel),
+            seed,
+            dimension: 0,
+            generator: lowdiscrepancy::rank1_lattice_generator(samples_per_pixel as u32) as u64,
+            array_generators: Vec::new(),
+        }
+    }
+
+    // Sample `i` of dimension `dimension`'s order, out of `n`.
+    fn permute(&self, i: usize, n: usize, dimension: usize) -> u64 {
+        let hash = pbrt::mix_bits(dimension as u64 ^ pbrt::mix_bits(self.seed)) as u32;
+        return lowdiscrepancy::permutation_element(i as u32, n as u32, hash) as u64;
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn state(&self) -> &SamplerState {
+        return &self.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.state.start_pixel(p);
+        self.dimension = 0;
+
+        // Sample j's arrays are lattices shifted by the blue noise plus the
+        // jth pixel sample, so the arrays of all the samples together are
+        // stratified too
+        let spp = self.state.samples_per_pixel as usize;
+        for k in 0..self.state.sample_array_1d.len() {
+            let count = self.state.samples_1d_array_sizes[k];
+            let texture = ARRAY_TEXTURE_START + 2 * k;
+            let delta = bluenoise::blue_noise(texture, p);
+            for j in 0..spp {
+                let shift = (delta + j as f64 / spp as f64).fract();
+                for i in 0..count {
+                    let v = ((i as f64 + shift) / count as f64).min(pbrt::ONE_MINUS_EPSILON);
+                    let index = self.permute(i, count, texture + j * count);
+                    self.state.sample_array_1d[k][j * count + index as usize] = v;
+                }
+            }
+        }
+        for k in 0..self.state.sample_array_2d.len() {
+            let count = self.state.samples_2d_array_sizes[k];
+            let texture = ARRAY_TEXTURE_START + 2 * (self.state.sample_array_1d.len() + k);
+            let delta = (bluenoise::blue_noise(texture, p), bluenoise::blue_noise(texture + 1, p));
+            for j in 0..spp {
+                let pj = lattice_point(j as u64, spp as u64, self.generator);
+                let shift = ((delta.0 + pj.x).fract(), (delta.1 + pj.y).fract());
+                for i in 0..count {
+                    let u = lattice_point(i as u64, count as u64, self.array_generators[k]);
+                    let index = self.permute(i, count, texture + j * count);
+                    self.state.sample_array_2d[k][j * count + index as usize] = Point2f::new(
+                        (u.x + shift.0).fract().min(pbrt::ONE_MINUS_EPSILON),
+                        (u.y + shift.1).fract().min(pbrt::ONE_MINUS_EPSILON),
+                    );
+                }
+            }
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        self.dimension = 0;
+        return self.state.start_next_sample();
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        self.dimension = 0;
+        return self.state.set_sample_number(sample_num);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let spp = self.state.samples_per_pixel as usize;
+        let index = self.permute(self.state.current_pixel_sample_index as usize, spp, self.dimension);
+        let delta = bluenoise::blue_noise(self.dimension, &self.state.current_pixel);
+        self.dimension += 1;
+        return ((index as f64 / spp as f64 + delta).fract()).min(pbrt::ONE_MINUS_EPSILON);
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        let spp = self.state.samples_per_pixel as usize;
+        let index = self.permute(self.state.current_pixel_sample_index as usize, spp, self.dimension);
+        let u = lattice_point(index, spp as u64, self.generator);
+        let p = &self.state.current_pixel;
+        let delta = (bluenoise::blue_noise(self.dimension, p), bluenoise::blue_noise(self.dimension + 1, p));
+        self.dimension += 2;
+        return Point2f::new(
+            (u.x + delta.0).fract().min(pbrt::ONE_MINUS_EPSILON),
+            (u.y + delta.1).fract().min(pbrt::ONE_MINUS_EPSILON),
+        );
+    }
+
+    // The reordering of samples is shared by all pixels and must not change
+    // with the seed, or the dithering would break up at tile boundaries.
+    fn clone_with_seed(&self, _seed: u64) -> Box<dyn Sampler> {
+        return Box::new(self.clone());
+    }
+
+    fn request_2d_array(&mut self, n: usize) {
+        self.array_generators.push(lowdiscrepancy::rank1_lattice_generator(n as u32) as u64);
+        self.state.request_2d_array(n);
+    }
+}
+
+// Point i of the rank-1 lattice of n points with generator g.
+fn lattice_point(i: u64, n: u64, g: u64) -> Point2f {
+    return Point2f::new(i as f64 / n as f64, (i * g % n) as f64 / n as f64);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Point2i;
+    use pbrt::lowdiscrepancy;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::bluenoise::BlueNoiseSampler;
+
+    #[test]
+    fn lattice_is_stratified_and_dithered() {
+        // The Fibonacci lattice
+        assert_eq!(lowdiscrepancy::rank1_lattice_generator(13), 5);
+
+        let mut sampler = BlueNoiseSampler::new(8, 0);
+        sampler.request_2d_array(5);
+
+        // Sample 0's time over a block of pixels
+        let mut times = vec![vec![0.0; 16]; 16];
+        for y in 0..16 {
+            for x in 0..16 {
+                let pixel = Point2i::new(x, y);
+                sampler.start_pixel(&pixel);
+                let (mut t, mut u, mut cells) = ([0; 8], [0; 8], [0; 40]);
+                loop {
+                    let sample = sampler.get_camera_sample(&pixel);
+                    if sampler.current_sample_number() == 0 {
+                        times[y as usize][x as usize] = sample.time;
+                    }
+                    t[(sample.time * 8.0) as usize] += 1;
+                    u[((sample.p_film.x - x as f64) * 8.0) as usize] += 1;
+                    for p in sampler.get_2d_array(5).unwrap().iter() {
+                        cells[(p.x * 40.0) as usize] += 1;
+                    }
+                    if !sampler.start_next_sample() {
+                        break;
+                    }
+                }
+                assert_eq!(t, [1; 8]);
+                assert_eq!(u, [1; 8]);
+                assert_eq!(cells, [1; 40]);
+            }
+        }
+
+        // Neighbours differ, on the circle, by more than the 1/4 expected of
+        // white noise
+        let mut difference = 0.0;
+        for row in times.iter() {
+            for x in 0..15 {
+                let d = (row[x] - row[x + 1]).abs();
+                difference += d.min(1.0 - d);
+            }
+        }
+        assert!(difference / (16.0 * 15.0) > 0.3);
+    }
+}
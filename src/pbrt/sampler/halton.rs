@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use pbrt;
+use pbrt::geometry::Bounds2i;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::lowdiscrepancy;
+use pbrt::lowdiscrepancy::RandomizeStrategy;
+use pbrt::rng::Rng;
+use pbrt::sampler::GlobalSampler;
+use pbrt::sampler::GlobalSequence;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+
+
+// Beyond this many pixels in each direction the pattern of sample indices
+// repeats, keeping the indices small.
+const MAX_RESOLUTION: i64 = 128;
+
+// The state a Halton sampler needs to find and evaluate its samples.
+#[derive(Clone, Debug)]
+struct Halton {
+    // 2^j and 3^k, the first powers at least the (capped) image resolution
+    base_scales: [u64; 2],
+    base_exponents: [u32; 2],
+    // Consecutive samples in one pixel are this many indices apart
+    sample_stride: u64,
+    mult_inverse: [u64; 2],
+    pixel_for_offset: Option<Point2i>,
+    offset_for_current_pixel: u64,
+    sample_at_pixel_center: bool,
+    randomize: RandomizeStrategy,
+    seed: u64,
+    // Only for RandomizeStrategy::PermuteDigits
+    permutations: Arc<Vec<u16>>,
+    prime_sums: Arc<Vec<usize>>,
+}
+
+impl GlobalSequence for Halton {
+    // The first two dimensions scaled by base_scales give the pixel, so the
+    // pixel's samples are the indices congruent to one offset modulo
+    // sample_stride, found with the Chinese remainder theorem.
+    fn get_index_for_sample(&mut self, p: &Point2i, sample_num: u64) -> u64 {
+        if self.pixel_for_offset != Some(*p) {
+            self.offset_for_current_pixel = 0;
+            if self.sample_stride > 1 {
+                let pm = [p.x.rem_euclid(MAX_RESOLUTION) as u64, p.y.rem_euclid(MAX_RESOLUTION) as u64];
+                for (i, &pm) in pm.iter().enumerate() {
+                    let base = if i == 0 { 2 } else { 3 };
+                    let dim_offset = lowdiscrepancy::inverse_radical_inverse(base, pm, self.base_exponents[i]);
+                    self.offset_for_current_pixel +=
+                        dim_offset * (self.sample_stride / self.base_scales[i]) % self.sample_stride * self.mult_inverse[i];
+                }
+                self.offset_for_current_pixel %= self.sample_stride;
+            }
+            self.pixel_for_offset = Some(*p);
+        }
+        return self.offset_for_current_pixel + sample_num * self.sample_stride;
+    }
+
+    fn sample_dimension(&self, index: u64, dimension: usize) -> f64 {
+        if dimension < 2 && self.sample_at_pixel_center {
+            return 0.5;
+        }
+        // The pixel dimensions drop the digits that select the pixel
+        if dimension == 0 {
+            return lowdiscrepancy::radical_inverse(2, index >> self.base_exponents[0]);
+        }
+        if dimension == 1 {
+            return lowdiscrepancy::radical_inverse(3, index / self.base_scales[1]);
+        }
+
+        let base = lowdiscrepancy::primes()[dimension % lowdiscrepancy::PRIME_TABLE_SIZE];
+        return match self.randomize {
+            RandomizeStrategy::None => lowdiscrepancy::radical_inverse(base, index),
+            RandomizeStrategy::PermuteDigits => {
+                let start = self.prime_sums[dimension % lowdiscrepancy::PRIME_TABLE_SIZE];
+                lowdiscrepancy::scrambled_radical_inverse(base, index, &self.permutations[start..start + base as usize])
+            }
+            RandomizeStrategy::Owen => {
+                let hash = pbrt::mix_bits(dimension as u64 ^ pbrt::mix_bits(self.seed)) as u32;
+                lowdiscrepancy::owen_scrambled_radical_inverse(base, index, hash)
+            }
+        };
+    }
+}
+
+// x such that a * x = 1 (mod n), for coprime a and n.
+fn multiplicative_inverse(a: i64, n: i64) -> u64 {
+    fn extended_gcd(a: i64, b: i64) -> (i64, i64) {
+        if b == 0 {
+            return (1, 0);
+        }
+        let (xp, yp) = extended_gcd(b, a % b);
+        return (yp, xp - (a / b) * yp);
+    }
+    return extended_gcd(a, n).0.rem_euclid(n) as u64;
+}
+
+
+// Samples the Halton sequence, bases 2 and 3 in the first two dimensions
+// and successive primes after, with each pixel taking the sequence points
+// whose first two dimensions fall in it.
+#[derive(Clone, Debug)]
+pub struct HaltonSampler {
+    global: GlobalSampler,
+    halton: Halton,
+}
+
+impl HaltonSampler {
+    pub fn new(
+        samples_per_pixel: i64,
+        sample_bounds: &Bounds2i,
+        randomize: RandomizeStrategy,
+        sample_at_pixel_center: bool,
+        seed: u64,
+    ) -> HaltonSampler {
+        let res = [sample_bounds.max.x - sample_bounds.min.x, sample_bounds.max.y - sample_bounds.min.y];
+        let mut base_scales = [1u64; 2];
+        let mut base_exponents = [0u32; 2];
+        for i in 0..2 {
+            let base = if i == 0 { 2 } else { 3 };
+            while (base_scales[i] as i64) < res[i].min(MAX_RESOLUTION) {
+                base_scales[i] *= base;
+                base_exponents[i] += 1;
+            }
+        }
+
+        let (permutations, prime_sums) = if randomize == RandomizeStrategy::PermuteDigits {
+            let primes = lowdiscrepancy::primes();
+            let prime_sums = (0..primes.len()).map(|i| primes[..i].iter().sum::<u64>() as usize).collect();
+            (lowdiscrepancy::compute_radical_inverse_permutations(&mut Rng::new(seed)), prime_sums)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        HaltonSampler {
+            global: GlobalSampler::new(samples_per_pixel),
+            halton: Halton {
+                base_scales,
+                base_exponents,
+                sample_stride: base_scales[0] * base_scales[1],
+                mult_inverse: [
+                    multiplicative_inverse(base_scales[1] as i64, base_scales[0] as i64),
+                    multiplicative_inverse(base_scales[0] as i64, base_scales[1] as i64),
+                ],
+                pixel_for_offset: None,
+                offset_for_current_pixel: 0,
+                sample_at_pixel_center,
+                randomize,
+                seed,
+                permutations: Arc::new(permutations),
+                prime_sums: Arc::new(prime_sums),
+            },
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn state(&self) -> &SamplerState {
+        return &self.global.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.global.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.global.start_pixel(p, &mut self.halton);
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        return self.global.start_next_sample(&mut self.halton);
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        return self.global.set_sample_number(sample_num, &mut self.halton);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        return self.global.get_1d(&self.halton);
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        return self.global.get_2d(&self.halton);
+    }
+
+    fn clone_with_seed(&self, _seed: u64) -> Box<dyn Sampler> {
+        return Box::new(self.clone());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Bounds2i;
+    use pbrt::geometry::Point2i;
+    use pbrt::lowdiscrepancy;
+    use pbrt::lowdiscrepancy::RandomizeStrategy;
+    use pbrt::sampler::GlobalSequence;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::halton::HaltonSampler;
+
+    #[test]
+    fn samples_land_in_their_pixel() {
+        let bounds = Bounds2i::new(Point2i::new(-2, -2), Point2i::new(20, 12));
+        for randomize in [RandomizeStrategy::None, RandomizeStrategy::PermuteDigits, RandomizeStrategy::Owen].iter() {
+            let mut sampler = HaltonSampler::new(8, &bounds, *randomize, false, 7);
+            for &(x, y) in [(-2, -2), (0, 0), (5, 3), (19, 11)].iter() {
+                let pixel = Point2i::new(x, y);
+                sampler.start_pixel(&pixel);
+
+                // The pixel's samples are also well spread out within it
+                let mut strata = [0; 8];
+                loop {
+                    // The unscaled first two dimensions pick out the pixel,
+                    // modulo the tile size
+                    let n = sampler.current_sample_number() as u64;
+                    let index = sampler.halton.get_index_for_sample(&pixel, n);
+                    let scales = sampler.halton.base_scales;
+                    let u = (lowdiscrepancy::radical_inverse(2, index) * scales[0] as f64) as i64;
+                    let v = (lowdiscrepancy::radical_inverse(3, index) * scales[1] as f64) as i64;
+                    assert_eq!(u, x.rem_euclid(128) % scales[0] as i64);
+                    assert_eq!(v, y.rem_euclid(128) % scales[1] as i64);
+
+                    let sample = sampler.get_camera_sample(&pixel);
+                    assert!(sample.p_film.x >= x as f64 && sample.p_film.x < x as f64 + 1.0);
+                    assert!(sample.p_film.y >= y as f64 && sample.p_film.y < y as f64 + 1.0);
+                    strata[((sample.p_film.x - x as f64) * 8.0) as usize] += 1;
+                    assert!((0.0..1.0).contains(&sampler.get_1d()));
+                    if !sampler.start_next_sample() {
+                        break;
+                    }
+                }
+                assert!(strata.iter().all(|&c| c == 1));
+            }
+        }
+    }
+}
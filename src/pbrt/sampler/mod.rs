@@ -0,0 +1,355 @@
+pub mod adaptive;
+pub mod bluenoise;
+pub mod halton;
+pub mod pmj02;
+pub mod random;
+pub mod sobol;
+pub mod stratified;
+pub mod zerotwosequence;
+
+use pbrt;
+use pbrt::Spectrum;
+use pbrt::camera::CameraSample;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::rng::Rng;
+use pbrt::stats::VarianceEstimator;
+
+
+// Bookkeeping shared by every sampler: where it is in the image, which
+// sample of the pixel it is on, and the sample arrays integrators asked for.
+#[derive(Clone, Debug)]
+pub struct SamplerState {
+    pub samples_per_pixel: i64,
+    pub current_pixel: Point2i,
+    pub current_pixel_sample_index: i64,
+    pub samples_1d_array_sizes: Vec<usize>,
+    pub samples_2d_array_sizes: Vec<usize>,
+    // One entry per requested array, holding that array for every sample of
+    // the pixel back to back.
+    pub sample_array_1d: Vec<Vec<f64>>,
+    pub sample_array_2d: Vec<Vec<Point2f>>,
+    // Luminance of the radiance recorded for the current pixel's samples
+    pub radiance_stats: VarianceEstimator,
+    array_1d_offset: usize,
+    array_2d_offset: usize,
+}
+
+impl SamplerState {
+    pub fn new(samples_per_pixel: i64) -> SamplerState {
+        SamplerState {
+            samples_per_pixel,
+            current_pixel: Point2i::new(0, 0),
+            current_pixel_sample_index: 0,
+            samples_1d_array_sizes: Vec::new(),
+            samples_2d_array_sizes: Vec::new(),
+            sample_array_1d: Vec::new(),
+            sample_array_2d: Vec::new(),
+            radiance_stats: VarianceEstimator::default(),
+            array_1d_offset: 0,
+            array_2d_offset: 0,
+        }
+    }
+
+    pub fn start_pixel(&mut self, p: &Point2i) {
+        self.current_pixel = *p;
+        self.current_pixel_sample_index = 0;
+        self.radiance_stats = VarianceEstimator::default();
+        self.array_1d_offset = 0;
+        self.array_2d_offset = 0;
+    }
+
+    pub fn start_next_sample(&mut self) -> bool {
+        return self.set_sample_number(self.current_pixel_sample_index + 1);
+    }
+
+    pub fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        self.array_1d_offset = 0;
+        self.array_2d_offset = 0;
+        self.current_pixel_sample_index = sample_num;
+        return self.current_pixel_sample_index < self.samples_per_pixel;
+    }
+
+    pub fn request_1d_array(&mut self, n: usize) {
+        self.samples_1d_array_sizes.push(n);
+        self.sample_array_1d.push(vec![0.0; n * self.samples_per_pixel as usize]);
+    }
+
+    pub fn request_2d_array(&mut self, n: usize) {
+        self.samples_2d_array_sizes.push(n);
+        self.sample_array_2d.push(vec![Point2f::new(0.0, 0.0); n * self.samples_per_pixel as usize]);
+    }
+
+    // The next requested array for the current sample; arrays must be taken
+    // in the order and with the sizes they were requested.
+    pub fn get_1d_array(&mut self, n: usize) -> Option<&[f64]> {
+        if self.array_1d_offset == self.sample_array_1d.len() {
+            return None;
+        }
+        assert_eq!(self.samples_1d_array_sizes[self.array_1d_offset], n);
+        let start = self.current_pixel_sample_index as usize * n;
+        self.array_1d_offset += 1;
+        return Some(&self.sample_array_1d[self.array_1d_offset - 1][start..start + n]);
+    }
+
+    pub fn get_2d_array(&mut self, n: usize) -> Option<&[Point2f]> {
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return None;
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        let start = self.current_pixel_sample_index as usize * n;
+        self.array_2d_offset += 1;
+        return Some(&self.sample_array_2d[self.array_2d_offset - 1][start..start + n]);
+    }
+}
+
+
+// Source of sample vectors for an integrator. Each call to `get_1d` or
+// `get_2d` consumes the next dimension of the current sample; arrays are
+// requested up front and handed out whole per sample.
+pub trait Sampler {
+    fn state(&self) -> &SamplerState;
+
+    fn state_mut(&mut self) -> &mut SamplerState;
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> Point2f;
+
+    // A copy for another thread. Pixel samplers reseed from `seed`; global
+    // samplers already give every pixel its own part of the sequence.
+    fn clone_with_seed(&self, seed: u64) -> Box<dyn Sampler>;
+
+    fn samples_per_pixel(&self) -> i64 {
+        return self.state().samples_per_pixel;
+    }
+
+    fn current_sample_number(&self) -> i64 {
+        return self.state().current_pixel_sample_index;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.state_mut().start_pixel(p);
+    }
+
+    // Returns false once the pixel's samples are used up.
+    fn start_next_sample(&mut self) -> bool {
+        return self.state_mut().start_next_sample();
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        return self.state_mut().set_sample_number(sample_num);
+    }
+
+    fn get_camera_sample(&mut self, p_raster: &Point2i) -> CameraSample {
+        let offset = self.get_2d();
+        let p_film = Point2f::new(p_raster.x as f64 + offset.x, p_raster.y as f64 + offset.y);
+        let time = self.get_1d();
+        let p_lens = self.get_2d();
+        return CameraSample::new(p_film, p_lens, time);
+    }
+
+    // Arrays must be requested before the first `start_pixel`.
+    fn request_1d_array(&mut self, n: usize) {
+        let n = self.round_count(n);
+        self.state_mut().request_1d_array(n);
+    }
+
+    fn request_2d_array(&mut self, n: usize) {
+        let n = self.round_count(n);
+        self.state_mut().request_2d_array(n);
+    }
+
+    // The array size the sampler would rather produce for a request of `n`.
+    fn round_count(&self, n: usize) -> usize {
+        return n;
+    }
+
+    fn get_1d_array(&mut self, n: usize) -> Option<&[f64]> {
+        return self.state_mut().get_1d_array(n);
+    }
+
+    fn get_2d_array(&mut self, n: usize) -> Option<&[Point2f]> {
+        return self.state_mut().get_2d_array(n);
+    }
+
+    // Whether the first 2^k samples of every pixel are themselves well
+    // distributed, so that stopping after a power of two samples leaves no
+    // pattern half finished.
+    fn prefixes_are_nets(&self) -> bool {
+        return false;
+    }
+
+    // The radiance the current sample gave, including the camera's weight.
+    // Adaptive samplers use the pixel's statistics to decide when it is done.
+    fn record_radiance(&mut self, l: &Spectrum) {
+        self.state_mut().radiance_stats.add(l.y());
+    }
+}
+
+
+// Seeds a generator for pixel `p` so that its samples do not depend on the
+// order pixels are visited in.
+pub fn pixel_sequence(p: &Point2i, seed: u64) -> u64 {
+    return pbrt::mix_bits((p.x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (p.y as u64) ^ pbrt::mix_bits(seed));
+}
+
+
+// Shared state for samplers that generate all of a pixel's samples when
+// the pixel starts, for a fixed number of dimensions. Dimensions past those
+// fall back to uniform random values.
+#[derive(Clone, Debug)]
+pub struct PixelSampler {
+    pub state: SamplerState,
+    pub samples_1d: Vec<Vec<f64>>,
+    pub samples_2d: Vec<Vec<Point2f>>,
+    current_1d_dimension: usize,
+    current_2d_dimension: usize,
+    pub rng: Rng,
+    pub seed: u64,
+}
+
+impl PixelSampler {
+    pub fn new(samples_per_pixel: i64, n_sampled_dimensions: usize, seed: u64) -> PixelSampler {
+        let n = samples_per_pixel as usize;
+        PixelSampler {
+            state: SamplerState::new(samples_per_pixel),
+            samples_1d: vec![vec![0.0; n]; n_sampled_dimensions],
+            samples_2d: vec![vec![Point2f::new(0.0, 0.0); n]; n_sampled_dimensions],
+            current_1d_dimension: 0,
+            current_2d_dimension: 0,
+            rng: Rng::default(),
+            seed,
+        }
+    }
+
+    // Resets to the pixel's first sample and reseeds `rng` for it, ready for
+    // the owner to fill in the sample tables.
+    pub fn start_pixel(&mut self, p: &Point2i) {
+        self.state.start_pixel(p);
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        self.rng.set_sequence(pixel_sequence(p, self.seed));
+    }
+
+    pub fn start_next_sample(&mut self) -> bool {
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        return self.state.start_next_sample();
+    }
+
+    pub fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        self.current_1d_dimension = 0;
+        self.current_2d_dimension = 0;
+        return self.state.set_sample_number(sample_num);
+    }
+
+    pub fn get_1d(&mut self) -> f64 {
+        if self.current_1d_dimension < self.samples_1d.len() {
+            let v = self.samples_1d[self.current_1d_dimension][self.state.current_pixel_sample_index as usize];
+            self.current_1d_dimension += 1;
+            return v;
+        }
+        return self.rng.uniform_f64();
+    }
+
+    pub fn get_2d(&mut self) -> Point2f {
+        if self.current_2d_dimension < self.samples_2d.len() {
+            let v = self.samples_2d[self.current_2d_dimension][self.state.current_pixel_sample_index as usize];
+            self.current_2d_dimension += 1;
+            return v;
+        }
+        let x = self.rng.uniform_f64();
+        return Point2f::new(x, self.rng.uniform_f64());
+    }
+}
+
+
+// The part of a global sampler that defines its sequence: which sample
+// indices fall in a pixel and the value of each dimension of a sample.
+pub trait GlobalSequence {
+    fn get_index_for_sample(&mut self, p: &Point2i, sample_num: u64) -> u64;
+
+    fn sample_dimension(&self, index: u64, dimension: usize) -> f64;
+}
+
+// Shared state for samplers that draw from one low-discrepancy sequence
+// spanning the whole image. Dimensions 0 and 1 place the sample in the
+// pixel; requested arrays take the dimensions after the camera sample.
+#[derive(Clone, Debug)]
+pub struct GlobalSampler {
+    pub state: SamplerState,
+    dimension: usize,
+    interval_sample_index: u64,
+    array_end_dim: usize,
+}
+
+const ARRAY_START_DIM: usize = 5;
+
+impl GlobalSampler {
+    pub fn new(samples_per_pixel: i64) -> GlobalSampler {
+        GlobalSampler {
+            state: SamplerState::new(samples_per_pixel),
+            dimension: 0,
+            interval_sample_index: 0,
+            array_end_dim: ARRAY_START_DIM,
+        }
+    }
+
+    pub fn start_pixel(&mut self, p: &Point2i, sequence: &mut dyn GlobalSequence) {
+        self.state.start_pixel(p);
+        self.dimension = 0;
+        self.interval_sample_index = sequence.get_index_for_sample(p, 0);
+        let state = &mut self.state;
+        self.array_end_dim = ARRAY_START_DIM + state.sample_array_1d.len() + 2 * state.sample_array_2d.len();
+
+        // Array entry j takes the values of the pixel's jth sample
+        for (i, array) in state.sample_array_1d.iter_mut().enumerate() {
+            for (j, v) in array.iter_mut().enumerate() {
+                let index = sequence.get_index_for_sample(p, j as u64);
+                *v = sequence.sample_dimension(index, ARRAY_START_DIM + i);
+            }
+        }
+        let mut dim = ARRAY_START_DIM + state.sample_array_1d.len();
+        for array in state.sample_array_2d.iter_mut() {
+            for (j, v) in array.iter_mut().enumerate() {
+                let index = sequence.get_index_for_sample(p, j as u64);
+                *v = Point2f::new(sequence.sample_dimension(index, dim), sequence.sample_dimension(index, dim + 1));
+            }
+            dim += 2;
+        }
+    }
+
+    pub fn start_next_sample(&mut self, sequence: &mut dyn GlobalSequence) -> bool {
+        let next = self.state.current_pixel_sample_index + 1;
+        return self.set_sample_number(next, sequence);
+    }
+
+    pub fn set_sample_number(&mut self, sample_num: i64, sequence: &mut dyn GlobalSequence) -> bool {
+        self.dimension = 0;
+        self.interval_sample_index = sequence.get_index_for_sample(&self.state.current_pixel, sample_num as u64);
+        return self.state.set_sample_number(sample_num);
+    }
+
+    // Skips over the dimensions that arrays use.
+    fn next_dimension(&mut self, n: usize) -> usize {
+        if self.dimension >= ARRAY_START_DIM && self.dimension < self.array_end_dim {
+            self.dimension = self.array_end_dim;
+        }
+        self.dimension += n;
+        return self.dimension - n;
+    }
+
+    pub fn get_1d(&mut self, sequence: &dyn GlobalSequence) -> f64 {
+        let dim = self.next_dimension(1);
+        return sequence.sample_dimension(self.interval_sample_index, dim);
+    }
+
+    pub fn get_2d(&mut self, sequence: &dyn GlobalSequence) -> Point2f {
+        let dim = self.next_dimension(2);
+        return Point2f::new(
+            sequence.sample_dimension(self.interval_sample_index, dim),
+            sequence.sample_dimension(self.interval_sample_index, dim + 1),
+        );
+    }
+}
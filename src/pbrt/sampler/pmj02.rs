@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use pbrt;
+use pbrt::bluenoise;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::lowdiscrepancy;
+use pbrt::pmj02;
+use pbrt::rng::Rng;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+use pbrt::sampler::pixel_sequence;
+use pbrt::sampling;
+
+
+// Progressive multi-jittered (0,2) samples. The pixel dimensions come from
+// one PMJ02 set spread over a tile of pixels, so neighbouring pixels'
+// samples are stratified together. Other dimensions give each pixel a
+// shuffled prefix of a set, offset by blue noise so the remaining error is
+// spread at high frequencies across the image.
+#[derive(Clone, Debug)]
+pub struct PMJ02Sampler {
+    state: SamplerState,
+    seed: u64,
+    dimension: usize,
+    pixel_tile_size: i64,
+    // Each pixel of the tile's samples, consecutive and relative to the pixel
+    pixel_samples: Arc<Vec<Point2f>>,
+    rng: Rng,
+}
+
+impl PMJ02Sampler {
+    // The sample count is rounded up to a power of four, so each pixel takes
+    // whole strata of the tile, and capped at the set size.
+    pub fn new(samples_per_pixel: i64, seed: u64) -> PMJ02Sampler {
+        let mut spp = 1;
+        while spp < samples_per_pixel as usize && spp < pmj02::PMJ02_SET_SIZE {
+            spp *= 4;
+        }
+        let tile = 1usize << ((pmj02::PMJ02_SET_SIZE / spp).trailing_zeros() / 2);
+
+        let mut pixel_samples = vec![Point2f::new(0.0, 0.0); pmj02::PMJ02_SET_SIZE];
+        let mut n_stored = vec![0; tile * tile];
+        for i in 0..pmj02::PMJ02_SET_SIZE {
+            let p = pmj02::pmj02_sample(0, i);
+            let (x, y) = (p.x * tile as f64, p.y * tile as f64);
+            let pixel_offset = x as usize + y as usize * tile;
+            pixel_samples[pixel_offset * spp + n_stored[pixel_offset]] = Point2f::new(x - x.floor(), y - y.floor());
+            n_stored[pixel_offset] += 1;
+        }
+
+        PMJ02Sampler {
+            state: SamplerState::new(spp as i64),
+            seed,
+            dimension: 0,
+            pixel_tile_size: tile as i64,
+            pixel_samples: Arc::new(pixel_samples),
+            rng: Rng::default(),
+        }
+    }
+
+    // This pixel's shuffled order of the samples for the current dimension.
+    fn sample_index(&self) -> u32 {
+        let hash = pbrt::mix_bits(pixel_sequence(&self.state.current_pixel, self.seed) ^ self.dimension as u64);
+        let spp = self.state.samples_per_pixel as u32;
+        return lowdiscrepancy::permutation_element(self.state.current_pixel_sample_index as u32, spp, hash as u32);
+    }
+}
+
+impl Sampler for PMJ02Sampler {
+    fn state(&self) -> &SamplerState {
+        return &self.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.state.start_pixel(p);
+        self.dimension = 0;
+        self.rng.set_sequence(pixel_sequence(p, self.seed));
+
+        // There are no 1D sets, so 1D arrays are jittered strata. Each 2D
+        // array takes a prefix of a set the pixel dimensions don't use,
+        // randomly shifted for every pixel sample; arrays too large for a set
+        // get Latin hypercube samples instead.
+        let state = &mut self.state;
+        for (array, &count) in state.sample_array_1d.iter_mut().zip(state.samples_1d_array_sizes.iter()) {
+            for samples in array.chunks_mut(count.max(1)) {
+                sampling::stratified_sample_1d(samples, &mut self.rng, true);
+                sampling::shuffle(samples, 1, &mut self.rng);
+            }
+        }
+        for (k, array) in state.sample_array_2d.iter_mut().enumerate() {
+            let set = (k + 1) % pmj02::N_PMJ02_SETS;
+            let count = state.samples_2d_array_sizes[k].max(1);
+            for samples in array.chunks_mut(count) {
+                if count > pmj02::PMJ02_SET_SIZE {
+                    sampling::latin_hypercube_2d(samples, &mut self.rng);
+                    continue;
+                }
+                let offset = (self.rng.uniform_f64(), self.rng.uniform_f64());
+                for (i, s) in samples.iter_mut().enumerate() {
+                    let u = pmj02::pmj02_sample(set, i);
+                    *s = Point2f::new(wrap(u.x + offset.0), wrap(u.y + offset.1));
+                }
+            }
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        self.dimension = 0;
+        return self.state.start_next_sample();
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        self.dimension = 0;
+        return self.state.set_sample_number(sample_num);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let index = self.sample_index();
+        let delta = bluenoise::blue_noise(self.dimension, &self.state.current_pixel);
+        self.dimension += 1;
+        return ((index as f64 + delta) / self.state.samples_per_pixel as f64).min(pbrt::ONE_MINUS_EPSILON);
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        if self.dimension == 0 {
+            let p = &self.state.current_pixel;
+            let (px, py) = (p.x.rem_euclid(self.pixel_tile_size), p.y.rem_euclid(self.pixel_tile_size));
+            let offset = (px + py * self.pixel_tile_size) * self.state.samples_per_pixel;
+            self.dimension += 2;
+            return self.pixel_samples[(offset + self.state.current_pixel_sample_index) as usize];
+        }
+
+        let index = self.sample_index();
+        let u = pmj02::pmj02_sample((self.dimension / 2) % pmj02::N_PMJ02_SETS, index as usize);
+        let p = &self.state.current_pixel;
+        let delta = (bluenoise::blue_noise(self.dimension, p), bluenoise::blue_noise(self.dimension + 1, p));
+        self.dimension += 2;
+        return Point2f::new(wrap(u.x + delta.0), wrap(u.y + delta.1));
+    }
+
+    fn clone_with_seed(&self, seed: u64) -> Box<dyn Sampler> {
+        let mut sampler = self.clone();
+        sampler.seed = seed;
+        return Box::new(sampler);
+    }
+
+    // Powers of two keep array prefixes (0,2)-nets, up to the set size.
+    fn round_count(&self, n: usize) -> usize {
+        return n.max(1).next_power_of_two().min(pmj02::PMJ02_SET_SIZE);
+    }
+}
+
+// Brings a value offset by less than 1 back into [0, 1).
+fn wrap(v: f64) -> f64 {
+    return if v >= 1.0 { (v - 1.0).min(pbrt::ONE_MINUS_EPSILON) } else { v };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Point2i;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::pmj02::PMJ02Sampler;
+
+    #[test]
+    fn pixels_and_dimensions_are_stratified() {
+        let mut sampler = PMJ02Sampler::new(10, 0);
+        assert_eq!(sampler.samples_per_pixel(), 16);
+
+        // Pixels of a 2x2 block tile the 8x8 grid of their 64 samples
+        let mut cells = [0; 64];
+        for &(x, y) in [(6, -2), (7, -2), (6, -1), (7, -1)].iter() {
+            let pixel = Point2i::new(x, y);
+            sampler.start_pixel(&pixel);
+            let mut strata = [0; 16];
+            loop {
+                let sample = sampler.get_camera_sample(&pixel);
+                let (u, v) = (sample.p_film.x - 6.0, sample.p_film.y + 2.0);
+                assert!((0.0..2.0).contains(&u) && (0.0..2.0).contains(&v));
+                cells[(u * 4.0) as usize + 8 * (v * 4.0) as usize] += 1;
+
+                strata[(sample.time * 16.0) as usize] += 1;
+                let p = sampler.get_2d();
+                assert!((0.0..1.0).contains(&p.x) && (0.0..1.0).contains(&p.y));
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+            // Blue noise offsets 1D samples within their strata
+            assert_eq!(strata, [1; 16]);
+        }
+        assert_eq!(cells, [1; 64]);
+    }
+
+    #[test]
+    fn large_arrays_fit_the_sets() {
+        let mut sampler = PMJ02Sampler::new(4, 0);
+        assert_eq!(sampler.round_count(5000), 4096);
+        assert_eq!(sampler.round_count(300), 512);
+        sampler.request_2d_array(5000);
+        // Past the set size when the rounding is bypassed
+        sampler.state_mut().request_2d_array(5000);
+
+        sampler.start_pixel(&Point2i::new(3, 1));
+        for &n in [4096, 5000].iter() {
+            let samples = sampler.get_2d_array(n).unwrap();
+            assert_eq!(samples.len(), n);
+            assert!(samples.iter().all(|p| (0.0..1.0).contains(&p.x) && (0.0..1.0).contains(&p.y)));
+        }
+    }
+}
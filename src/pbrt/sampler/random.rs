@@ -0,0 +1,128 @@
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::rng::Rng;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+use pbrt::sampler::pixel_sequence;
+
+
+// Independent uniform samples. Each pixel sample draws from its own stretch
+// of the pixel's PCG32 stream, so any sample can be regenerated on its own.
+#[derive(Clone, Debug)]
+pub struct RandomSampler {
+    state: SamplerState,
+    seed: u64,
+    rng: Rng,
+}
+
+impl RandomSampler {
+    pub fn new(samples_per_pixel: i64, seed: u64) -> RandomSampler {
+        RandomSampler { state: SamplerState::new(samples_per_pixel), seed, rng: Rng::default() }
+    }
+
+    // Outputs [0, 2^32) of the pixel's stream hold its sample arrays; sample
+    // i starts at (i + 1) * 2^32.
+    fn seek(&mut self, block: u64) {
+        self.rng.set_sequence(pixel_sequence(&self.state.current_pixel, self.seed));
+        self.rng.advance(block << 32);
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn state(&self) -> &SamplerState {
+        return &self.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.state.start_pixel(p);
+        self.seek(0);
+        for array in self.state.sample_array_1d.iter_mut() {
+            for v in array.iter_mut() {
+                *v = self.rng.uniform_f64();
+            }
+        }
+        for array in self.state.sample_array_2d.iter_mut() {
+            for v in array.iter_mut() {
+                let x = self.rng.uniform_f64();
+                *v = Point2f::new(x, self.rng.uniform_f64());
+            }
+        }
+        self.seek(1);
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        let more = self.state.start_next_sample();
+        self.seek(self.state.current_pixel_sample_index as u64 + 1);
+        return more;
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        let more = self.state.set_sample_number(sample_num);
+        self.seek(sample_num as u64 + 1);
+        return more;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        return self.rng.uniform_f64();
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        let x = self.rng.uniform_f64();
+        return Point2f::new(x, self.rng.uniform_f64());
+    }
+
+    fn clone_with_seed(&self, seed: u64) -> Box<dyn Sampler> {
+        let mut sampler = self.clone();
+        sampler.seed = seed;
+        return Box::new(sampler);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Point2i;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::random::RandomSampler;
+
+    #[test]
+    fn samples_are_repeatable_per_pixel() {
+        let mut sampler = RandomSampler::new(4, 0);
+        sampler.request_2d_array(3);
+
+        let draw = |sampler: &mut dyn Sampler, p: &Point2i| {
+            let mut values = Vec::new();
+            sampler.start_pixel(p);
+            loop {
+                values.push(sampler.get_1d());
+                let array = sampler.get_2d_array(3).unwrap();
+                assert_eq!(array.len(), 3);
+                values.push(array[2].y);
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+            return values;
+        };
+
+        let a = draw(&mut sampler, &Point2i::new(3, 5));
+        assert_eq!(a.len(), 8);
+        assert!(a.iter().all(|&v| (0.0..1.0).contains(&v)));
+        // Visiting another pixel first changes nothing
+        let b = draw(&mut sampler, &Point2i::new(4, 5));
+        assert!(a != b);
+        assert_eq!(draw(&mut sampler, &Point2i::new(3, 5)), a);
+
+        // Nor does jumping straight to a sample
+        sampler.start_pixel(&Point2i::new(3, 5));
+        sampler.set_sample_number(2);
+        assert_eq!(sampler.get_1d(), a[4]);
+
+        let mut other = sampler.clone_with_seed(1);
+        assert!(draw(&mut *other, &Point2i::new(3, 5)) != a);
+    }
+}
@@ -0,0 +1,224 @@
+use pbrt;
+use pbrt::geometry::Bounds2i;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::lowdiscrepancy;
+use pbrt::lowdiscrepancy::RandomizeStrategy;
+use pbrt::sampler::GlobalSampler;
+use pbrt::sampler::GlobalSequence;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+
+
+// The state a Sobol sampler needs to find and evaluate its samples.
+#[derive(Clone, Debug)]
+struct Sobol {
+    sample_bounds: Bounds2i,
+    // Sample bounds rounded up to a power of two square, 2^log2_resolution
+    resolution: i64,
+    log2_resolution: u32,
+    // The first two dimensions scaled by resolution give the pixel, through
+    // a linear map of the low 2 * log2_resolution index bits. Column r of its
+    // inverse gives the index bits for pixel bit r; frame_columns undo what
+    // the higher index bits add to the pixel.
+    pixel_columns: Vec<u64>,
+    frame_columns: Vec<u64>,
+    current_pixel: Point2i,
+    pixel_for_offset: Option<Point2i>,
+    offset_for_current_pixel: u64,
+    randomize: RandomizeStrategy,
+    seed: u64,
+}
+
+impl Sobol {
+    fn new(sample_bounds: &Bounds2i, randomize: RandomizeStrategy, seed: u64) -> Sobol {
+        let extent = (sample_bounds.max.x - sample_bounds.min.x).max(sample_bounds.max.y - sample_bounds.min.y);
+        let resolution = (extent.max(1) as u64).next_power_of_two() as i64;
+        let m = resolution.trailing_zeros();
+
+        let matrices = lowdiscrepancy::sobol_matrices();
+        let pixel_bits = |c: usize| -> u64 {
+            if m == 0 {
+                return 0;
+            }
+            let x = (matrices[c] >> (32 - m)) as u64;
+            let y = (matrices[lowdiscrepancy::SOBOL_MATRIX_SIZE + c] >> (32 - m)) as u64;
+            return (x << m) | y;
+        };
+
+        // Gauss-Jordan elimination over GF(2), tracking which index bits
+        // combine into each pixel bit
+        let n = 2 * m as usize;
+        let mut columns: Vec<(u64, u64)> = (0..n).map(|c| (pixel_bits(c), 1 << c)).collect();
+        for r in 0..n {
+            let pivot = (r..n).find(|&i| columns[i].0 & (1 << r) != 0).expect("Sobol pixel map is singular");
+            columns.swap(r, pivot);
+            for i in 0..n {
+                if i != r && columns[i].0 & (1 << r) != 0 {
+                    columns[i].0 ^= columns[r].0;
+                    columns[i].1 ^= columns[r].1;
+                }
+            }
+        }
+        let pixel_columns: Vec<u64> = columns.iter().map(|c| c.1).collect();
+        let solve = |bits: u64| -> u64 {
+            return (0..n).filter(|&r| bits & (1 << r) != 0).fold(0, |index, r| index ^ pixel_columns[r]);
+        };
+        let frame_columns = (n..lowdiscrepancy::SOBOL_MATRIX_SIZE).map(|c| solve(pixel_bits(c))).collect();
+
+        Sobol {
+            sample_bounds: *sample_bounds,
+            resolution,
+            log2_resolution: m,
+            pixel_columns,
+            frame_columns,
+            current_pixel: sample_bounds.min,
+            pixel_for_offset: None,
+            offset_for_current_pixel: 0,
+            randomize,
+            seed,
+        }
+    }
+}
+
+impl GlobalSequence for Sobol {
+    fn get_index_for_sample(&mut self, p: &Point2i, sample_num: u64) -> u64 {
+        let m2 = 2 * self.log2_resolution;
+        assert!(sample_num < 1 << (lowdiscrepancy::SOBOL_MATRIX_SIZE as u32 - m2), "too many Sobol samples per pixel");
+        self.current_pixel = *p;
+        if self.pixel_for_offset != Some(*p) {
+            let x = (p.x - self.sample_bounds.min.x) as u64;
+            let y = (p.y - self.sample_bounds.min.y) as u64;
+            let bits = (x << self.log2_resolution) | y;
+            self.offset_for_current_pixel = (0..m2 as usize)
+                .filter(|&r| bits & (1 << r) != 0)
+                .fold(0, |index, r| index ^ self.pixel_columns[r]);
+            self.pixel_for_offset = Some(*p);
+        }
+
+        let mut index = self.offset_for_current_pixel;
+        let mut frame = sample_num;
+        let mut c = 0;
+        while frame != 0 {
+            if frame & 1 != 0 {
+                index ^= self.frame_columns[c];
+            }
+            frame >>= 1;
+            c += 1;
+        }
+        return (sample_num << m2) | index;
+    }
+
+    fn sample_dimension(&self, index: u64, dimension: usize) -> f64 {
+        // The pixel dimensions stay unscrambled, relative to the pixel
+        if dimension < 2 {
+            let v = lowdiscrepancy::sobol_sample(index, dimension, RandomizeStrategy::None, 0);
+            let (min, pixel) = if dimension == 0 {
+                (self.sample_bounds.min.x, self.current_pixel.x)
+            } else {
+                (self.sample_bounds.min.y, self.current_pixel.y)
+            };
+            return pbrt::clamp(v * self.resolution as f64 + (min - pixel) as f64, 0.0, pbrt::ONE_MINUS_EPSILON);
+        }
+
+        let hash = pbrt::mix_bits(dimension as u64 ^ pbrt::mix_bits(self.seed)) as u32;
+        return lowdiscrepancy::sobol_sample(index, dimension, self.randomize, hash);
+    }
+}
+
+
+// Samples the Sobol sequence over the whole image, each pixel taking the
+// sequence points whose first two dimensions fall in it. The sample count
+// is rounded up to a power of two so a pixel's samples are well stratified.
+#[derive(Clone, Debug)]
+pub struct SobolSampler {
+    global: GlobalSampler,
+    sobol: Sobol,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: i64, sample_bounds: &Bounds2i, randomize: RandomizeStrategy, seed: u64) -> SobolSampler {
+        SobolSampler {
+            global: GlobalSampler::new((samples_per_pixel.max(1) as u64).next_power_of_two() as i64),
+            sobol: Sobol::new(sample_bounds, randomize, seed),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn state(&self) -> &SamplerState {
+        return &self.global.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.global.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.global.start_pixel(p, &mut self.sobol);
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        return self.global.start_next_sample(&mut self.sobol);
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        return self.global.set_sample_number(sample_num, &mut self.sobol);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        return self.global.get_1d(&self.sobol);
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        return self.global.get_2d(&self.sobol);
+    }
+
+    fn clone_with_seed(&self, _seed: u64) -> Box<dyn Sampler> {
+        return Box::new(self.clone());
+    }
+
+    // A pixel's samples are consecutive points of the sequence.
+    fn prefixes_are_nets(&self) -> bool {
+        return true;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Bounds2i;
+    use pbrt::geometry::Point2i;
+    use pbrt::lowdiscrepancy::RandomizeStrategy;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::sobol::SobolSampler;
+
+    #[test]
+    fn pixel_samples_form_a_net_in_the_pixel() {
+        let bounds = Bounds2i::new(Point2i::new(-2, -2), Point2i::new(21, 12));
+        for randomize in [RandomizeStrategy::None, RandomizeStrategy::PermuteDigits, RandomizeStrategy::Owen].iter() {
+            let mut sampler = SobolSampler::new(10, &bounds, *randomize, 3);
+            assert_eq!(sampler.samples_per_pixel(), 16);
+            for &(x, y) in [(-2, -2), (0, 0), (7, 3), (20, 11)].iter() {
+                let pixel = Point2i::new(x, y);
+                sampler.start_pixel(&pixel);
+
+                // One sample in each 4x4 cell of the pixel, and the next
+                // dimension stratified as well
+                let (mut cells, mut d2) = ([0; 16], [0; 16]);
+                loop {
+                    let sample = sampler.get_camera_sample(&pixel);
+                    let (u, v) = (sample.p_film.x - x as f64, sample.p_film.y - y as f64);
+                    assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                    cells[(u * 4.0) as usize + 4 * (v * 4.0) as usize] += 1;
+                    d2[(sample.time * 16.0) as usize] += 1;
+                    if !sampler.start_next_sample() {
+                        break;
+                    }
+                }
+                assert!(cells.iter().all(|&c| c == 1));
+                assert!(d2.iter().all(|&c| c == 1));
+            }
+        }
+    }
+}
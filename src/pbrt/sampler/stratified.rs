@@ -0,0 +1,147 @@
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::sampler::PixelSampler;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+use pbrt::sampling;
+
+
+// Divides each pixel's sample domain into x_pixel_samples * y_pixel_samples
+// strata per dimension. Strata are shuffled independently in every
+// dimension so the dimensions stay uncorrelated; 2D arrays use Latin
+// hypercube sampling since their sizes need not factor into a grid.
+#[derive(Clone, Debug)]
+pub struct StratifiedSampler {
+    pixel: PixelSampler,
+    x_pixel_samples: usize,
+    y_pixel_samples: usize,
+    jitter_samples: bool,
+}
+
+impl StratifiedSampler {
+    pub fn new(
+        x_pixel_samples: usize,
+        y_pixel_samples: usize,
+        jitter_samples: bool,
+        n_sampled_dimensions: usize,
+        seed: u64,
+    ) -> StratifiedSampler {
+        StratifiedSampler {
+            pixel: PixelSampler::new((x_pixel_samples * y_pixel_samples) as i64, n_sampled_dimensions, seed),
+            x_pixel_samples,
+            y_pixel_samples,
+            jitter_samples,
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn state(&self) -> &SamplerState {
+        return &self.pixel.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.pixel.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.pixel.start_pixel(p);
+        let jitter = self.jitter_samples;
+        let pixel = &mut self.pixel;
+
+        for samples in pixel.samples_1d.iter_mut() {
+            sampling::stratified_sample_1d(samples, &mut pixel.rng, jitter);
+            sampling::shuffle(samples, 1, &mut pixel.rng);
+        }
+        for samples in pixel.samples_2d.iter_mut() {
+            sampling::stratified_sample_2d(samples, self.x_pixel_samples, self.y_pixel_samples, &mut pixel.rng, jitter);
+            sampling::shuffle(samples, 1, &mut pixel.rng);
+        }
+
+        // Arrays are stratified within each pixel sample
+        let state = &mut pixel.state;
+        for (array, &count) in state.sample_array_1d.iter_mut().zip(state.samples_1d_array_sizes.iter()) {
+            for samples in array.chunks_mut(count.max(1)) {
+                sampling::stratified_sample_1d(samples, &mut pixel.rng, jitter);
+                sampling::shuffle(samples, 1, &mut pixel.rng);
+            }
+        }
+        for (array, &count) in state.sample_array_2d.iter_mut().zip(state.samples_2d_array_sizes.iter()) {
+            for samples in array.chunks_mut(count.max(1)) {
+                sampling::latin_hypercube_2d(samples, &mut pixel.rng);
+            }
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        return self.pixel.start_next_sample();
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        return self.pixel.set_sample_number(sample_num);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        return self.pixel.get_1d();
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        return self.pixel.get_2d();
+    }
+
+    fn clone_with_seed(&self, seed: u64) -> Box<dyn Sampler> {
+        let mut sampler = self.clone();
+        sampler.pixel.seed = seed;
+        return Box::new(sampler);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Point2i;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::stratified::StratifiedSampler;
+
+    // Whether `values` has exactly one entry in each of its len() intervals.
+    fn one_per_stratum(values: &[f64]) -> bool {
+        let mut seen = vec![false; values.len()];
+        for &v in values.iter() {
+            let i = (v * values.len() as f64) as usize;
+            if seen[i] {
+                return false;
+            }
+            seen[i] = true;
+        }
+        return true;
+    }
+
+    #[test]
+    fn dimensions_and_arrays_are_stratified() {
+        let mut sampler = StratifiedSampler::new(3, 2, true, 2, 0);
+        sampler.request_1d_array(5);
+        sampler.request_2d_array(7);
+        sampler.start_pixel(&Point2i::new(1, 2));
+
+        let (mut d1, mut cells) = (Vec::new(), Vec::new());
+        loop {
+            d1.push(sampler.get_1d());
+            let p = sampler.get_2d();
+            cells.push((p.x * 3.0).floor() + 3.0 * (p.y * 2.0).floor());
+
+            assert!(one_per_stratum(sampler.get_1d_array(5).unwrap()));
+            let array = sampler.get_2d_array(7).unwrap().to_vec();
+            assert!(one_per_stratum(&array.iter().map(|p| p.x).collect::<Vec<f64>>()));
+            assert!(one_per_stratum(&array.iter().map(|p| p.y).collect::<Vec<f64>>()));
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+
+        assert_eq!(d1.len(), 6);
+        assert!(one_per_stratum(&d1));
+        // Every cell of the 3x2 grid is hit once
+        cells.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(cells, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
@@ -0,0 +1,117 @@
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Point2i;
+use pbrt::lowdiscrepancy;
+use pbrt::sampler::PixelSampler;
+use pbrt::sampler::Sampler;
+use pbrt::sampler::SamplerState;
+
+
+// Draws each pixel's samples from randomly scrambled van der Corput points
+// in 1D and a scrambled (0,2)-sequence in 2D, shuffled independently per
+// dimension. Sample counts and array sizes are rounded up to powers of two
+// so the points form complete nets.
+#[derive(Clone, Debug)]
+pub struct ZeroTwoSequenceSampler {
+    pixel: PixelSampler,
+}
+
+impl ZeroTwoSequenceSampler {
+    pub fn new(samples_per_pixel: i64, n_sampled_dimensions: usize, seed: u64) -> ZeroTwoSequenceSampler {
+        let samples_per_pixel = (samples_per_pixel.max(1) as u64).next_power_of_two() as i64;
+        ZeroTwoSequenceSampler { pixel: PixelSampler::new(samples_per_pixel, n_sampled_dimensions, seed) }
+    }
+}
+
+impl Sampler for ZeroTwoSequenceSampler {
+    fn state(&self) -> &SamplerState {
+        return &self.pixel.state;
+    }
+
+    fn state_mut(&mut self) -> &mut SamplerState {
+        return &mut self.pixel.state;
+    }
+
+    fn start_pixel(&mut self, p: &Point2i) {
+        self.pixel.start_pixel(p);
+        let pixel = &mut self.pixel;
+        let spp = pixel.state.samples_per_pixel as usize;
+
+        for samples in pixel.samples_1d.iter_mut() {
+            lowdiscrepancy::van_der_corput(1, spp, samples, &mut pixel.rng);
+        }
+        for samples in pixel.samples_2d.iter_mut() {
+            lowdiscrepancy::sobol_2d(1, spp, samples, &mut pixel.rng);
+        }
+
+        let state = &mut pixel.state;
+        for (array, &count) in state.sample_array_1d.iter_mut().zip(state.samples_1d_array_sizes.iter()) {
+            lowdiscrepancy::van_der_corput(count, spp, array, &mut pixel.rng);
+        }
+        for (array, &count) in state.sample_array_2d.iter_mut().zip(state.samples_2d_array_sizes.iter()) {
+            lowdiscrepancy::sobol_2d(count, spp, array, &mut pixel.rng);
+        }
+    }
+
+    fn start_next_sample(&mut self) -> bool {
+        return self.pixel.start_next_sample();
+    }
+
+    fn set_sample_number(&mut self, sample_num: i64) -> bool {
+        return self.pixel.set_sample_number(sample_num);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        return self.pixel.get_1d();
+    }
+
+    fn get_2d(&mut self) -> Point2f {
+        return self.pixel.get_2d();
+    }
+
+    fn clone_with_seed(&self, seed: u64) -> Box<dyn Sampler> {
+        let mut sampler = self.clone();
+        sampler.pixel.seed = seed;
+        return Box::new(sampler);
+    }
+
+    fn round_count(&self, n: usize) -> usize {
+        return n.max(1).next_power_of_two();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::geometry::Point2i;
+    use pbrt::sampler::Sampler;
+    use pbrt::sampler::zerotwosequence::ZeroTwoSequenceSampler;
+
+    #[test]
+    fn samples_and_arrays_are_nets() {
+        let mut sampler = ZeroTwoSequenceSampler::new(6, 2, 0);
+        assert_eq!(sampler.samples_per_pixel(), 8);
+        assert_eq!(sampler.round_count(3), 4);
+        sampler.request_2d_array(3);
+        sampler.start_pixel(&Point2i::new(4, 1));
+
+        let (mut d1, mut cells) = ([0; 8], [0; 8]);
+        loop {
+            d1[(sampler.get_1d() * 8.0) as usize] += 1;
+            let p = sampler.get_2d();
+            cells[(p.x * 4.0) as usize + 4 * (p.y * 2.0) as usize] += 1;
+
+            // Each sample's array is a (0,2,2)-net on its own
+            let array = sampler.get_2d_array(4).unwrap();
+            let mut quadrants = [0; 4];
+            for p in array.iter() {
+                quadrants[(p.x * 2.0) as usize + 2 * (p.y * 2.0) as usize] += 1;
+            }
+            assert_eq!(quadrants, [1; 4]);
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert_eq!(d1, [1; 8]);
+        assert_eq!(cells, [1; 8]);
+    }
+}
@@ -0,0 +1,143 @@
+use std::f64;
+
+use pbrt;
+use pbrt::geometry::Point2f;
+use pbrt::geometry::Vector3f;
+use pbrt::rng::Rng;
+
+pub fn uniform_sample_sphere(u: &Point2f) -> Vector3f {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * f64::consts::PI * u.y;
+    return Vector3f::new(r * phi.cos(), r * phi.sin(), z);
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    return pbrt::INV4PI;
+}
+
+// Returns barycentrics (b0, b1) uniformly distributed over a triangle.
+pub fn uniform_sample_triangle(u: &Point2f) -> Point2f {
+    let su0 = u.x.sqrt();
+    return Point2f::new(1.0 - su0, u.y * su0);
+}
+
+// Maps the unit square to the unit disk, keeping adjacent squares adjacent
+// so stratification survives the warp.
+pub fn concentric_sample_disk(u: &Point2f) -> Point2f {
+    let u_offset = Point2f::new(2.0 * u.x - 1.0, 2.0 * u.y - 1.0);
+    if u_offset.x == 0.0 && u_offset.y == 0.0 {
+        return Point2f::new(0.0, 0.0);
+    }
+
+    let (r, theta) = if u_offset.x.abs() > u_offset.y.abs() {
+        (u_offset.x, f64::consts::FRAC_PI_4 * (u_offset.y / u_offset.x))
+    } else {
+        (u_offset.y, f64::consts::FRAC_PI_2 - f64::consts::FRAC_PI_4 * (u_offset.x / u_offset.y))
+    };
+    return Point2f::new(r * theta.cos(), r * theta.sin());
+}
+
+// Clarberg's equal-area mapping from the unit square to the unit sphere,
+// laid out as an octahedron unfolded around the +z pole.
+pub fn equal_area_square_to_sphere(p: &Point2f) -> Vector3f {
+    let u = 2.0 * p.x - 1.0;
+    let v = 2.0 * p.y - 1.0;
+    let up = u.abs();
+    let vp = v.abs();
+
+    // Distance from the diagonal separating the two hemispheres
+    let signed_distance = 1.0 - (up + vp);
+    let r = 1.0 - signed_distance.abs();
+    let phi = if r == 0.0 { 1.0 } else { (vp - up) / r + 1.0 } * f64::consts::FRAC_PI_4;
+
+    let z = (1.0 - r * r).copysign(signed_distance);
+    let cos_phi = phi.cos().copysign(u);
+    let sin_phi = phi.sin().copysign(v);
+    let s = r * (2.0 - r * r).max(0.0).sqrt();
+    return Vector3f::new(cos_phi * s, sin_phi * s, z);
+}
+
+// Folds points just outside the unit square back in, matching the way the
+// equal-area layout's edges meet on the sphere.
+pub fn wrap_equal_area_square(p: &Point2f) -> Point2f {
+    let mut p = *p;
+    if p.x < 0.0 {
+        p.x = -p.x;
+        p.y = 1.0 - p.y;
+    } else if p.x > 1.0 {
+        p.x = 2.0 - p.x;
+        p.y = 1.0 - p.y;
+    }
+    if p.y < 0.0 {
+        p.x = 1.0 - p.x;
+        p.y = -p.y;
+    } else if p.y > 1.0 {
+        p.x = 1.0 - p.x;
+        p.y = 2.0 - p.y;
+    }
+    return p;
+}
+
+
+// One sample in each of samp.len() equal intervals of [0, 1), at a random
+// offset within it or at its center.
+pub fn stratified_sample_1d(samp: &mut [f64], rng: &mut Rng, jitter: bool) {
+    let inv_n_samples = 1.0 / samp.len() as f64;
+    for (i, s) in samp.iter_mut().enumerate() {
+        let delta = if jitter { rng.uniform_f64() } else { 0.5 };
+        *s = ((i as f64 + delta) * inv_n_samples).min(pbrt::ONE_MINUS_EPSILON);
+    }
+}
+
+// One sample in each cell of an nx by ny grid over [0, 1)^2, row by row.
+pub fn stratified_sample_2d(samp: &mut [Point2f], nx: usize, ny: usize, rng: &mut Rng, jitter: bool) {
+    let (dx, dy) = (1.0 / nx as f64, 1.0 / ny as f64);
+    for y in 0..ny {
+        for x in 0..nx {
+            let jx = if jitter { rng.uniform_f64() } else { 0.5 };
+            let jy = if jitter { rng.uniform_f64() } else { 0.5 };
+            samp[y * nx + x] = Point2f::new(
+                ((x as f64 + jx) * dx).min(pbrt::ONE_MINUS_EPSILON),
+                ((y as f64 + jy) * dy).min(pbrt::ONE_MINUS_EPSILON),
+            );
+        }
+    }
+}
+
+// Stratifies each axis separately: every one of the samples.len() intervals
+// holds exactly one sample in x and one in y, whatever the count.
+pub fn latin_hypercube_2d(samples: &mut [Point2f], rng: &mut Rng) {
+    let n = samples.len();
+    let inv_n_samples = 1.0 / n as f64;
+    for (i, s) in samples.iter_mut().enumerate() {
+        let x = ((i as f64 + rng.uniform_f64()) * inv_n_samples).min(pbrt::ONE_MINUS_EPSILON);
+        let y = ((i as f64 + rng.uniform_f64()) * inv_n_samples).min(pbrt::ONE_MINUS_EPSILON);
+        *s = Point2f::new(x, y);
+    }
+
+    // Permute each dimension independently
+    for j in 0..n {
+        let other = j + rng.uniform_u32_below((n - j) as u32) as usize;
+        let x = samples[j].x;
+        samples[j].x = samples[other].x;
+        samples[other].x = x;
+    }
+    for j in 0..n {
+        let other = j + rng.uniform_u32_below((n - j) as u32) as usize;
+        let y = samples[j].y;
+        samples[j].y = samples[other].y;
+        samples[other].y = y;
+    }
+}
+
+// Randomly permutes groups of `n_dimensions` consecutive values.
+pub fn shuffle<T>(samp: &mut [T], n_dimensions: usize, rng: &mut Rng) {
+    let count = samp.len() / n_dimensions;
+    for i in 0..count {
+        let other = i + rng.uniform_u32_below((count - i) as u32) as usize;
+        for j in 0..n_dimensions {
+            samp.swap(n_dimensions * i + j, n_dimensions * other + j);
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::clone;
 use std::cmp;
 use std::ops;
 
@@ -13,6 +12,10 @@ impl RGBSpectrum {
         RGBSpectrum { samples: [default, default, default] }
     }
 
+    pub fn from_rgb(r: f64, g: f64, b: f64) -> RGBSpectrum {
+        RGBSpectrum { samples: [r, g, b] }
+    }
+
     pub fn is_black(&self) -> bool {
         for i in 0..self.samples.len() {
             if self.samples[i] == 0.0 {
@@ -22,6 +25,10 @@ impl RGBSpectrum {
         return true;
     }
 
+    // Luminance, with the Rec. 709 weights.
+    pub fn y(&self) -> f64 {
+        return 0.212671 * self.samples[0] + 0.715160 * self.samples[1] + 0.072169 * self.samples[2];
+    }
 }
 
 impl ops::Add for RGBSpectrum {
@@ -84,6 +91,18 @@ impl ops::MulAssign for RGBSpectrum {
     }
 }
 
+impl ops::Mul<f64> for RGBSpectrum {
+    type Output = RGBSpectrum;
+
+    fn mul(self, rhs: f64) -> RGBSpectrum {
+        let mut ret = self;
+        for i in 0..ret.samples.len() {
+            ret.samples[i] *= rhs;
+        }
+        return ret;
+    }
+}
+
 impl ops::Div for RGBSpectrum {
     type Output = RGBSpectrum;
 
@@ -104,6 +123,19 @@ impl ops::DivAssign for RGBSpectrum {
     }
 }
 
+impl ops::Div<f64> for RGBSpectrum {
+    type Output = RGBSpectrum;
+
+    fn div(self, rhs: f64) -> RGBSpectrum {
+        let inv = 1.0 / rhs;
+        let mut ret = self;
+        for i in 0..ret.samples.len() {
+            ret.samples[i] *= inv;
+        }
+        return ret;
+    }
+}
+
 impl cmp::PartialEq for RGBSpectrum {
     fn eq(&self, other: &RGBSpectrum) -> bool {
         for i in 0..self.samples.len() {
@@ -0,0 +1,76 @@
+use std::f64;
+
+
+// Running mean and variance by Welford's method, which stays accurate over
+// long runs of samples where summing squares would cancel badly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VarianceEstimator {
+    n: i64,
+    mean: f64,
+    // Sum of squared differences from the current mean
+    s: f64,
+}
+
+impl VarianceEstimator {
+    pub fn add(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.s += delta * (x - self.mean);
+    }
+
+    pub fn count(&self) -> i64 {
+        return self.n;
+    }
+
+    pub fn mean(&self) -> f64 {
+        return self.mean;
+    }
+
+    // The unbiased sample variance.
+    pub fn variance(&self) -> f64 {
+        return if self.n > 1 { self.s / (self.n - 1) as f64 } else { 0.0 };
+    }
+
+    // Standard error of the mean over the mean. Infinite until there are two
+    // values to compare, or while values vary about a zero mean.
+    pub fn relative_error(&self) -> f64 {
+        if self.n < 2 {
+            return f64::INFINITY;
+        }
+        let standard_error = (self.variance() / self.n as f64).sqrt();
+        if standard_error == 0.0 {
+            return 0.0;
+        }
+        return standard_error / self.mean.abs();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::stats::VarianceEstimator;
+
+    #[test]
+    fn matches_two_pass_variance() {
+        // A large offset, where the naive sum of squares loses every digit
+        let values: Vec<f64> = (0..100).map(|i| 1e9 + ((i * 37) % 11) as f64).collect();
+        let mut estimator = VarianceEstimator::default();
+        assert_eq!(estimator.relative_error(), f64::INFINITY);
+        for &v in values.iter() {
+            estimator.add(v);
+        }
+
+        let mean = values.iter().sum::<f64>() / 100.0;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / 99.0;
+        assert_eq!(estimator.count(), 100);
+        assert!((estimator.mean() - mean).abs() < 1e-6);
+        assert!((estimator.variance() - variance).abs() < 1e-6 * variance);
+        assert!((estimator.relative_error() - (variance / 100.0).sqrt() / mean).abs() < 1e-15);
+
+        let mut constant = VarianceEstimator::default();
+        constant.add(0.0);
+        constant.add(0.0);
+        assert_eq!(constant.relative_error(), 0.0);
+    }
+}
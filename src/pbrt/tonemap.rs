@@ -0,0 +1,189 @@
+use std::str;
+
+use pbrt;
+use pbrt::Spectrum;
+
+
+// Curve taking exposed scene-linear values to display-linear values in
+// [0, 1], before the sRGB encoding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneCurve {
+    // Plain clipping at 1
+    Clamp,
+    Reinhard,
+    // Hill's fit of the ACES reference rendering and sRGB output transforms
+    AcesFilmic,
+    // Log encoding in an inset gamut followed by a sigmoid, after AgX
+    Agx,
+}
+
+impl str::FromStr for ToneCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ToneCurve, String> {
+        match &s.to_lowercase()[..] {
+            "clamp" | "none" => Ok(ToneCurve::Clamp),
+            "reinhard" => Ok(ToneCurve::Reinhard),
+            "aces" => Ok(ToneCurve::AcesFilmic),
+            "agx" => Ok(ToneCurve::Agx),
+            _ => Err(format!("unknown tone curve \"{}\" (expected clamp, reinhard, aces or agx)", s)),
+        }
+    }
+}
+
+
+// The view transform applied when quantizing linear radiance to 8 bits.
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    // In stops; each one doubles the image brightness.
+    pub exposure: f64,
+    pub curve: ToneCurve,
+    // Adds triangular noise of one code value before rounding, trading
+    // banding in smooth gradients for fine grain.
+    pub dither: bool,
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping { exposure: 0.0, curve: ToneCurve::Clamp, dither: false }
+    }
+}
+
+impl ToneMapping {
+    pub fn new(exposure: f64, curve: ToneCurve, dither: bool) -> ToneMapping {
+        ToneMapping { exposure, curve, dither }
+    }
+
+    // Display-linear RGB in [0, 1] for scene-linear `rgb`.
+    pub fn map(&self, rgb: &Spectrum) -> [f64; 3] {
+        let scale = 2f64.powf(self.exposure);
+        let c = [rgb.samples[0] * scale, rgb.samples[1] * scale, rgb.samples[2] * scale];
+        let c = match self.curve {
+            ToneCurve::Clamp => c,
+            ToneCurve::Reinhard => {
+                let f = |v: f64| v.max(0.0) / (1.0 + v.max(0.0));
+                [f(c[0]), f(c[1]), f(c[2])]
+            }
+            ToneCurve::AcesFilmic => aces_filmic(&c),
+            ToneCurve::Agx => agx(&c),
+        };
+        return [pbrt::clamp(c[0], 0.0, 1.0), pbrt::clamp(c[1], 0.0, 1.0), pbrt::clamp(c[2], 0.0, 1.0)];
+    }
+
+    // sRGB encoded 8-bit values for scene-linear `rgb` at pixel (x, y). The
+    // pixel position seeds the dither so output is repeatable.
+    pub fn quantize(&self, rgb: &Spectrum, x: u32, y: u32) -> [u8; 3] {
+        let c = self.map(rgb);
+        let mut bytes = [0u8; 3];
+        for i in 0..3 {
+            let mut v = 255.0 * pbrt::gamma_correct(c[i]);
+            if self.dither {
+                v += dither_noise(x, y, i as u32);
+            }
+            bytes[i] = pbrt::clamp(v + 0.5, 0.0, 255.0) as u8;
+        }
+        return bytes;
+    }
+}
+
+
+fn mul(m: &[[f64; 3]; 3], c: &[f64; 3]) -> [f64; 3] {
+    let row = |r: &[f64; 3]| r[0] * c[0] + r[1] * c[1] + r[2] * c[2];
+    return [row(&m[0]), row(&m[1]), row(&m[2])];
+}
+
+fn aces_filmic(c: &[f64; 3]) -> [f64; 3] {
+    // sRGB to the ACES working space with the RRT saturation, and back
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let fit = |v: f64| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+        return a / b;
+    };
+
+    let v = mul(&INPUT, c);
+    return mul(&OUTPUT, &[fit(v[0]), fit(v[1]), fit(v[2])]);
+}
+
+fn agx(c: &[f64; 3]) -> [f64; 3] {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    // Range of the log encoding, in stops around middle grey
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let encode = |v: f64| {
+        let x = (pbrt::clamp(v.max(1e-10).log2(), MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        // Polynomial fit of the default AgX contrast sigmoid
+        let (x2, x4) = (x * x, x * x * x * x);
+        return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    };
+
+    let v = mul(&INSET, c);
+    let v = mul(&OUTSET, &[encode(v[0]), encode(v[1]), encode(v[2])]);
+    // The sigmoid yields display-encoded values; undo a 2.2 display gamma
+    return [v[0].max(0.0).powf(2.2), v[1].max(0.0).powf(2.2), v[2].max(0.0).powf(2.2)];
+}
+
+// Triangular noise in (-1, 1) from a hash of the pixel and channel.
+fn dither_noise(x: u32, y: u32, channel: u32) -> f64 {
+    let hash = |mut h: u32| {
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb_352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846c_a68b);
+        h ^= h >> 16;
+        return h;
+    };
+    let h = hash(x.wrapping_mul(0x9e37_79b9) ^ hash(y ^ hash(channel)));
+    let u1 = (h & 0xffff) as f64 / 65536.0;
+    let u2 = (h >> 16) as f64 / 65536.0;
+    return u1 + u2 - 1.0;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pbrt::Spectrum;
+    use pbrt::tonemap::ToneCurve;
+    use pbrt::tonemap::ToneMapping;
+
+    #[test]
+    fn curves_are_monotonic_and_bounded() {
+        for curve in [ToneCurve::Clamp, ToneCurve::Reinhard, ToneCurve::AcesFilmic, ToneCurve::Agx].iter() {
+            let tone_mapping = ToneMapping::new(0.0, *curve, false);
+            assert!(tone_mapping.map(&Spectrum::new(0.0))[0] < 0.01);
+
+            let mut last = 0.0;
+            for i in 0..200 {
+                let v = 1e-3 * 1.08f64.powi(i);
+                let grey = tone_mapping.map(&Spectrum::new(v));
+                assert!(grey[0] >= last && grey[0] <= 1.0);
+                // Neutral stays neutral
+                assert!((grey[0] - grey[1]).abs() < 1e-3 && (grey[0] - grey[2]).abs() < 1e-3);
+                last = grey[0];
+            }
+        }
+
+        // Exposure is in stops
+        let brighter = ToneMapping::new(1.0, ToneCurve::Clamp, false);
+        assert!((brighter.map(&Spectrum::new(0.25))[0] - 0.5).abs() < 1e-12);
+    }
+}